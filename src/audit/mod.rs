@@ -0,0 +1,22 @@
+use sea_orm::{ActiveModelTrait, ActiveValue::Set, ConnectionTrait, DbErr};
+
+use crate::entities::audit_event;
+
+/// Records a security relevant event against the account of `user_id`.
+pub async fn record<C: ConnectionTrait>(
+    db: &C,
+    user_id: i32,
+    action: &str,
+    detail: Option<String>,
+) -> Result<(), DbErr> {
+    audit_event::ActiveModel {
+        user_id: Set(user_id),
+        action: Set(action.to_string()),
+        detail: Set(detail),
+        ..Default::default()
+    }
+    .insert(db)
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,67 @@
+use std::time::{Duration, SystemTime};
+
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::Set, ColumnTrait, DatabaseConnection, DbErr, EntityTrait,
+    QueryFilter, prelude::DateTimeUtc,
+};
+
+use super::{hash_token, random_token};
+use crate::entities::{api_key, prelude::*};
+
+/// Every API key starts with this, which is how the guard tells them apart
+/// from JWTs.
+pub const API_KEY_PREFIX: &str = "bks_";
+
+const LAST_USED_RESOLUTION: Duration = Duration::from_secs(60);
+
+/// Generates a new key as `bks_<prefix>_<secret>`, returning the key and its
+/// prefix. The prefix is stored in clear so keys can be recognised and
+/// looked up; the full key is only stored hashed.
+pub fn generate() -> (String, String) {
+    let prefix = random_token(8);
+    let key = format!("{}{}_{}", API_KEY_PREFIX, prefix, random_token(32));
+
+    (key, prefix)
+}
+
+/// Looks up the key, returning it when it exists, matches and has not
+/// expired. Successful lookups refresh `last_used_at`.
+pub async fn authenticate(
+    db: &DatabaseConnection,
+    key: &str,
+) -> Result<Option<api_key::Model>, DbErr> {
+    let prefix = match key
+        .strip_prefix(API_KEY_PREFIX)
+        .and_then(|rest| rest.split_once('_'))
+    {
+        Some((prefix, _)) => prefix,
+        None => return Ok(None),
+    };
+
+    let k = match ApiKey::find()
+        .filter(api_key::Column::Prefix.eq(prefix))
+        .one(db)
+        .await?
+    {
+        Some(k) if k.key_hash == hash_token(key) => k,
+        _ => return Ok(None),
+    };
+
+    let now = SystemTime::now();
+
+    if k.expires_at
+        .is_some_and(|expires| expires < DateTimeUtc::from(now))
+    {
+        return Ok(None);
+    }
+
+    let stale = DateTimeUtc::from(now - LAST_USED_RESOLUTION);
+
+    if k.last_used_at.is_none_or(|last_used| last_used < stale) {
+        let mut active: api_key::ActiveModel = k.clone().into();
+        active.last_used_at = Set(Some(DateTimeUtc::from(now)));
+        active.update(db).await?;
+    }
+
+    Ok(Some(k))
+}
@@ -0,0 +1,252 @@
+use std::{collections::HashMap, str::FromStr};
+
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey};
+use pkcs1::{RsaPublicKey, der::Decode};
+use rocket::serde::json::{Value, json};
+use spki::{ObjectIdentifier, SubjectPublicKeyInfoRef};
+
+use crate::config::AppConfig;
+
+const RSA_ENCRYPTION: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.1");
+const EC_PUBLIC_KEY: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.2.1");
+const ED25519: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.101.112");
+
+/// A key pair configured in `jwt_keys`. Keys without a private key are
+/// retired: tokens they signed are still accepted until they expire.
+#[derive(Debug)]
+pub struct JwtKeyConfig {
+    pub kid: String,
+    pub algorithm: Algorithm,
+    pub public_key_path: String,
+    pub private_key_path: Option<String>,
+}
+
+impl JwtKeyConfig {
+    /// Parses `kid:alg:public.pem[:private.pem]`.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let parts = value.split(':').collect::<Vec<_>>();
+
+        if parts.len() < 3 || parts.len() > 4 {
+            return Err(format!(
+                "Invalid JWT key `{}`, expected kid:alg:public.pem[:private.pem]",
+                value
+            ));
+        }
+
+        Ok(Self {
+            kid: parts[0].to_string(),
+            algorithm: parts[1]
+                .parse()
+                .map_err(|_| format!("Unsupported JWT algorithm `{}`", parts[1]))?,
+            public_key_path: parts[2].to_string(),
+            private_key_path: parts.get(3).map(|p| p.to_string()),
+        })
+    }
+}
+
+impl FromStr for JwtKeyConfig {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Self::parse(value)
+    }
+}
+
+struct VerificationKey {
+    algorithm: Algorithm,
+    decoding_key: DecodingKey,
+    jwk: Value,
+}
+
+/// Keys used to sign and verify tokens.
+///
+/// Without configured key pairs tokens are signed with HS256 and
+/// `jwt_secret`. Once key pairs are configured only those are
+/// accepted, and their public halves are published at
+/// `/.well-known/jwks.json`.
+pub struct KeyStore {
+    signing_kid: Option<String>,
+    signing_algorithm: Algorithm,
+    encoding_key: EncodingKey,
+    secret: Option<DecodingKey>,
+    keys: HashMap<String, VerificationKey>,
+}
+
+impl KeyStore {
+    pub fn load(config: &AppConfig) -> Result<Self, String> {
+        if config.jwt_keys.is_empty() {
+            let secret = config
+                .jwt_secret
+                .as_ref()
+                .ok_or("jwt_secret must be set unless jwt_keys are configured")?
+                .expose()
+                .as_bytes();
+
+            return Ok(Self {
+                signing_kid: None,
+                signing_algorithm: Algorithm::HS256,
+                encoding_key: EncodingKey::from_secret(secret),
+                secret: Some(DecodingKey::from_secret(secret)),
+                keys: HashMap::new(),
+            });
+        }
+
+        let mut keys = HashMap::new();
+        let mut signing = None;
+
+        for key in &config.jwt_keys {
+            let public_pem = read(&key.public_key_path)?;
+
+            keys.insert(
+                key.kid.to_owned(),
+                VerificationKey {
+                    algorithm: key.algorithm,
+                    decoding_key: decoding_key(key.algorithm, &public_pem)
+                        .map_err(|err| format!("{}: {}", key.public_key_path, err))?,
+                    jwk: jwk(&key.kid, key.algorithm, &public_pem)
+                        .map_err(|err| format!("{}: {}", key.public_key_path, err))?,
+                },
+            );
+
+            if Some(&key.kid) == config.jwt_active_kid.as_ref() {
+                let private_key_path = key.private_key_path.as_ref().ok_or(format!(
+                    "The active JWT key `{}` has no private key",
+                    key.kid
+                ))?;
+
+                signing = Some((
+                    key.kid.to_owned(),
+                    key.algorithm,
+                    encoding_key(key.algorithm, &read(private_key_path)?)
+                        .map_err(|err| format!("{}: {}", private_key_path, err))?,
+                ));
+            }
+        }
+
+        let (kid, algorithm, encoding_key) =
+            signing.ok_or("jwt_active_kid must name one of the keys in jwt_keys".to_string())?;
+
+        Ok(Self {
+            signing_kid: Some(kid),
+            signing_algorithm: algorithm,
+            encoding_key,
+            secret: None,
+            keys,
+        })
+    }
+
+    pub fn signing_kid(&self) -> Option<&String> {
+        self.signing_kid.as_ref()
+    }
+
+    pub fn signing_algorithm(&self) -> Algorithm {
+        self.signing_algorithm
+    }
+
+    pub fn encoding_key(&self) -> &EncodingKey {
+        &self.encoding_key
+    }
+
+    /// Returns the key and algorithm a token with the given `kid` header must
+    /// be verified with.
+    pub fn decoding_key(&self, kid: Option<&str>) -> Option<(&DecodingKey, Algorithm)> {
+        match (kid, &self.secret) {
+            (None, Some(secret)) => Some((secret, Algorithm::HS256)),
+            (Some(kid), _) => self
+                .keys
+                .get(kid)
+                .map(|key| (&key.decoding_key, key.algorithm)),
+            _ => None,
+        }
+    }
+
+    /// The public keys as a JSON Web Key Set.
+    pub fn jwks(&self) -> Value {
+        let mut keys = self
+            .keys
+            .values()
+            .map(|k| k.jwk.clone())
+            .collect::<Vec<_>>();
+        keys.sort_by(|a, b| a["kid"].as_str().cmp(&b["kid"].as_str()));
+
+        json!({ "keys": keys })
+    }
+}
+
+fn read(path: &str) -> Result<Vec<u8>, String> {
+    std::fs::read(path).map_err(|err| format!("{}: {}", path, err))
+}
+
+fn encoding_key(algorithm: Algorithm, pem: &[u8]) -> Result<EncodingKey, String> {
+    match algorithm {
+        Algorithm::RS256 | Algorithm::RS384 | Algorithm::RS512 => EncodingKey::from_rsa_pem(pem),
+        Algorithm::ES256 | Algorithm::ES384 => EncodingKey::from_ec_pem(pem),
+        Algorithm::EdDSA => EncodingKey::from_ed_pem(pem),
+        _ => return Err(format!("{:?} is not an asymmetric algorithm", algorithm)),
+    }
+    .map_err(|err| err.to_string())
+}
+
+fn decoding_key(algorithm: Algorithm, pem: &[u8]) -> Result<DecodingKey, String> {
+    match algorithm {
+        Algorithm::RS256 | Algorithm::RS384 | Algorithm::RS512 => DecodingKey::from_rsa_pem(pem),
+        Algorithm::ES256 | Algorithm::ES384 => DecodingKey::from_ec_pem(pem),
+        Algorithm::EdDSA => DecodingKey::from_ed_pem(pem),
+        _ => return Err(format!("{:?} is not an asymmetric algorithm", algorithm)),
+    }
+    .map_err(|err| err.to_string())
+}
+
+/// Builds the JWK for a PEM encoded `SubjectPublicKeyInfo`.
+fn jwk(kid: &str, algorithm: Algorithm, pem: &[u8]) -> Result<Value, String> {
+    let pem = pem::parse(pem).map_err(|err| err.to_string())?;
+    let spki = SubjectPublicKeyInfoRef::try_from(pem.contents()).map_err(|err| err.to_string())?;
+    let public_key = spki.subject_public_key.raw_bytes();
+    let alg = format!("{:?}", algorithm);
+
+    match (spki.algorithm.oid, algorithm) {
+        (RSA_ENCRYPTION, Algorithm::RS256 | Algorithm::RS384 | Algorithm::RS512) => {
+            let key = RsaPublicKey::from_der(public_key).map_err(|err| err.to_string())?;
+
+            Ok(json!({
+                "kty": "RSA",
+                "use": "sig",
+                "kid": kid,
+                "alg": alg,
+                "n": URL_SAFE_NO_PAD.encode(key.modulus.as_bytes()),
+                "e": URL_SAFE_NO_PAD.encode(key.public_exponent.as_bytes()),
+            }))
+        }
+        (EC_PUBLIC_KEY, Algorithm::ES256 | Algorithm::ES384) => {
+            let (crv, size) = match algorithm {
+                Algorithm::ES256 => ("P-256", 32),
+                _ => ("P-384", 48),
+            };
+
+            // Uncompressed point: 0x04 || x || y
+            if public_key.len() != 1 + 2 * size || public_key[0] != 0x04 {
+                return Err(format!("Expected an uncompressed {} public key", crv));
+            }
+
+            Ok(json!({
+                "kty": "EC",
+                "use": "sig",
+                "kid": kid,
+                "alg": alg,
+                "crv": crv,
+                "x": URL_SAFE_NO_PAD.encode(&public_key[1..1 + size]),
+                "y": URL_SAFE_NO_PAD.encode(&public_key[1 + size..]),
+            }))
+        }
+        (ED25519, Algorithm::EdDSA) => Ok(json!({
+            "kty": "OKP",
+            "use": "sig",
+            "kid": kid,
+            "alg": alg,
+            "crv": "Ed25519",
+            "x": URL_SAFE_NO_PAD.encode(public_key),
+        })),
+        _ => Err(format!("The public key does not match {}", alg)),
+    }
+}
@@ -1,11 +1,29 @@
-use jsonwebtoken::{DecodingKey, Validation, decode};
+use std::{
+    sync::Once,
+    time::{Duration, SystemTime},
+};
+
+use jsonwebtoken::{Header, Validation, decode, decode_header, encode};
+use rand::{Rng, distributions::Alphanumeric};
 use rocket::{
-    http::Status,
+    http::{Cookie, CookieJar, Method, SameSite, Status},
     request::{self, FromRequest, Outcome, Request},
-    serde::{Deserialize, Serialize},
+    serde::{Deserialize, Serialize, de::DeserializeOwned},
 };
+use sea_orm::{DatabaseConnection, EntityTrait};
+use sha2::{Digest, Sha256};
+
+use crate::config::AppConfig;
+use crate::entities::{prelude::*, session, user};
+
+pub mod api_keys;
+pub mod keys;
+pub mod oauth;
+pub mod oidc;
+pub mod sessions;
 
-use crate::AppConfig;
+use api_keys::API_KEY_PREFIX;
+use keys::KeyStore;
 
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(crate = "rocket::serde")]
@@ -13,36 +31,417 @@ pub struct Claims {
     pub sub: i32,
     pub role: String,
     pub exp: u64,
+    /// The user's `token_version` when the token was issued. Bumping the
+    /// version on the user row invalidates every token issued before.
+    #[serde(default)]
+    pub ver: i32,
+    /// Set on OAuth access tokens: the granted scopes, the client they were
+    /// issued to and the id of their `oauth_token` row.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+    /// The session a sign in token belongs to. Ending the session revokes
+    /// the token.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<i32>,
+    /// The admin acting as `sub` when the token was issued through
+    /// impersonation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub act: Option<Actor>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Actor {
+    pub sub: i32,
 }
 
+pub const USER_ROLE: &str = "user";
+pub const ADMIN_ROLE: &str = "admin";
+pub const ROLES: [&str; 2] = [USER_ROLE, ADMIN_ROLE];
+
+#[derive(Clone)]
 pub struct AuthenticatedUser {
     pub id: i32,
+    pub role: String,
+    /// The session of the token, if the user signed in with one.
+    pub session_id: Option<i32>,
+    /// The admin impersonating the user, if any.
+    pub impersonator_id: Option<i32>,
+}
+
+impl AuthenticatedUser {
+    /// The user, when a guard already authenticated the request. Nothing is
+    /// looked up otherwise, so response fairings can ask without the cost.
+    pub fn cached<'r>(req: &'r Request<'_>) -> Option<&'r AuthenticatedUser> {
+        let outcome = req.local_cache(|| {
+            request::Outcome::<AuthenticatedUser, String>::Forward(Status::Unauthorized)
+        });
+
+        match outcome {
+            Outcome::Success(user) => Some(user),
+            _ => None,
+        }
+    }
+}
+
+/// An admin acting as themselves.
+pub struct AdminUser(pub AuthenticatedUser);
+
+/// Scopes restricted credentials (API keys, OAuth tokens) can be limited
+/// to, with the description shown on the OAuth consent screen.
+pub const SCOPES: [(&str, &str); 4] = [
+    ("authors:read", "View authors"),
+    ("authors:write", "Create, edit and delete authors"),
+    ("books:read", "View books"),
+    ("books:write", "Create, edit and delete books"),
+];
+
+/// Joins scopes into the space separated form they are stored in, rejecting
+/// unknown scopes.
+pub fn join_scopes(scopes: &[String]) -> Result<String, String> {
+    match scopes
+        .iter()
+        .find(|scope| !SCOPES.iter().any(|(s, _)| s == scope))
+    {
+        Some(scope) => Err(format!("Unknown scope `{}`", scope)),
+        None => Ok(scopes.join(" ")),
+    }
+}
+
+/// How long a sign in token, and so its session, is valid.
+pub const TOKEN_TTL: Duration = Duration::from_secs(4 * 60 * 60);
+
+/// Signs a fresh token for `u` belonging to session `s` with the active key.
+pub fn issue_token(u: &user::Model, s: &session::Model, keys: &KeyStore) -> String {
+    sign(&session_claims(u, s, None), keys)
+}
+
+/// Signs a token letting `admin` act as `u`. The admin stays recorded in the
+/// token's `act` claim.
+pub fn issue_impersonation_token(
+    u: &user::Model,
+    s: &session::Model,
+    admin: &AuthenticatedUser,
+    keys: &KeyStore,
+) -> String {
+    sign(&session_claims(u, s, Some(Actor { sub: admin.id })), keys)
+}
+
+fn session_claims(u: &user::Model, s: &session::Model, act: Option<Actor>) -> Claims {
+    Claims {
+        sub: u.id,
+        role: u.role.to_owned(),
+        exp: unix_time() + TOKEN_TTL.as_secs(),
+        ver: u.token_version,
+        scope: None,
+        client_id: None,
+        jti: None,
+        sid: Some(s.id),
+        act,
+    }
+}
+
+/// Signs `claims` with the active key.
+pub fn sign(claims: &impl Serialize, keys: &KeyStore) -> String {
+    let mut header = Header::new(keys.signing_algorithm());
+    header.kid = keys.signing_kid().cloned();
+
+    encode(&header, claims, keys.encoding_key()).unwrap()
+}
+
+/// Seconds since the Unix epoch, as used by the `exp` claim.
+pub fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// Generates a random alphanumeric string suitable for one-time tokens.
+pub fn random_token(len: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(len)
+        .map(char::from)
+        .collect()
+}
+
+/// Hashes a one-time token before it is stored, so a database leak does not
+/// leak usable tokens.
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+pub const ACCESS_TOKEN_COOKIE: &str = "access_token";
+pub const CSRF_COOKIE: &str = "csrf_token";
+pub const CSRF_HEADER: &str = "X-CSRF-Token";
+/// The header tokens were sent in before `Authorization: Bearer`, still
+/// accepted but deprecated.
+pub const LEGACY_TOKEN_HEADER: &str = "token";
+
+/// Hands the token to browser clients as an HttpOnly cookie, together with a
+/// readable CSRF cookie whose value must be echoed in the `X-CSRF-Token`
+/// header on state-changing requests.
+pub fn set_auth_cookies(cookies: &CookieJar<'_>, token: String, config: &AppConfig) {
+    cookies.add(
+        Cookie::build((ACCESS_TOKEN_COOKIE, token))
+            .path("/")
+            .http_only(true)
+            .secure(config.auth_cookie_secure)
+            .same_site(SameSite::Lax),
+    );
+    cookies.add(
+        Cookie::build((CSRF_COOKIE, random_token(32)))
+            .path("/")
+            .secure(config.auth_cookie_secure)
+            .same_site(SameSite::Lax),
+    );
+}
+
+pub fn remove_auth_cookies(cookies: &CookieJar<'_>) {
+    cookies.remove(Cookie::build(ACCESS_TOKEN_COOKIE).path("/"));
+    cookies.remove(Cookie::build(CSRF_COOKIE).path("/"));
+}
+
+/// Finds the token sent with the request, looking at the `Authorization:
+/// Bearer` header, then the deprecated `token` header and finally, when
+/// cookie auth is enabled, the access token cookie.
+fn token_from_request(req: &Request<'_>, config: &AppConfig) -> Result<Option<String>, String> {
+    if let Some(authorization) = req.headers().get_one("Authorization") {
+        return match bearer_token(authorization) {
+            Some(token) => Ok(Some(token.to_string())),
+            None => Err("Unsupported authorization scheme".to_string()),
+        };
+    }
+
+    if let Some(token) = req.headers().get_one(LEGACY_TOKEN_HEADER) {
+        // Clients are told in the Deprecation header, the log only needs
+        // to hear of it once.
+        static WARNED: Once = Once::new();
+        WARNED.call_once(|| {
+            tracing::warn!("the token header is deprecated, send Authorization: Bearer instead");
+        });
+
+        return Ok(Some(token.to_string()));
+    }
+
+    if !config.auth_cookie {
+        return Ok(None);
+    }
+
+    let token = match req.cookies().get(ACCESS_TOKEN_COOKIE) {
+        Some(cookie) => cookie.value().to_string(),
+        None => return Ok(None),
+    };
+
+    // Browsers attach cookies to cross-site requests too, so anything that
+    // changes state has to prove it can read the CSRF cookie.
+    if !matches!(req.method(), Method::Get | Method::Head | Method::Options) {
+        let csrf_cookie = req.cookies().get(CSRF_COOKIE).map(|c| c.value());
+        let csrf_header = req.headers().get_one(CSRF_HEADER);
+
+        if csrf_cookie.is_none() || csrf_cookie != csrf_header {
+            return Err("Invalid CSRF token".to_string());
+        }
+    }
+
+    Ok(Some(token))
+}
+
+/// The token of an `Authorization` header value using the Bearer scheme,
+/// whose name is case-insensitive.
+pub fn bearer_token(authorization: &str) -> Option<&str> {
+    let (scheme, token) = authorization.trim().split_once(' ')?;
+
+    match scheme.eq_ignore_ascii_case("Bearer") {
+        true => Some(token.trim()),
+        false => None,
+    }
+}
+
+/// Verifies the token's signature and expiry against the key store.
+pub fn decode_token(token: &str, keys: &KeyStore) -> Option<Claims> {
+    verify(token, keys)
+}
+
+/// Verifies a token signed with [`sign`], returning its claims.
+pub fn verify<T: DeserializeOwned>(token: &str, keys: &KeyStore) -> Option<T> {
+    let kid = decode_header(token).ok()?.kid;
+    let (decoding_key, algorithm) = keys.decoding_key(kid.as_deref())?;
+
+    decode::<T>(token, decoding_key, &Validation::new(algorithm))
+        .ok()
+        .map(|data| data.claims)
+}
+
+/// The scope a restricted credential needs for the matched route: read
+/// access for safe methods and write access otherwise. Routes outside the
+/// catalogue have no scope and need an unrestricted credential.
+fn required_scope(req: &Request<'_>) -> Option<String> {
+    let resource = match req.route()?.uri.base().trim_start_matches('/') {
+        resource @ ("authors" | "books") => resource,
+        _ => return None,
+    };
+
+    match req.method() {
+        Method::Get | Method::Head => Some(format!("{}:read", resource)),
+        _ => Some(format!("{}:write", resource)),
+    }
 }
 
 #[rocket::async_trait]
 impl<'r> FromRequest<'r> for AuthenticatedUser {
     type Error = String;
 
+    /// Authenticates once per request, however many guards ask for the
+    /// user.
     async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
-        if let Some(token) = req.headers().get_one("token") {
-            let config = req.rocket().state::<AppConfig>().unwrap();
-
-            let data = decode::<Claims>(
-                token,
-                &DecodingKey::from_secret(config.jwt_secret.as_bytes()),
-                &Validation::new(jsonwebtoken::Algorithm::HS256),
-            );
-
-            let claims = match data {
-                Ok(p) => p.claims,
-                Err(_) => {
-                    return Outcome::Error((Status::Unauthorized, "Invalid token".to_string()));
+        req.local_cache_async(authenticate(req)).await.clone()
+    }
+}
+
+async fn authenticate(req: &Request<'_>) -> request::Outcome<AuthenticatedUser, String> {
+    let config = req.rocket().state::<AppConfig>().unwrap();
+    let keys = req.rocket().state::<KeyStore>().unwrap();
+    let db = req.rocket().state::<DatabaseConnection>().unwrap();
+
+    let token = match token_from_request(req, config) {
+        Ok(Some(token)) => token,
+        Ok(None) => return Outcome::Error((Status::Unauthorized, "Token absent".to_string())),
+        Err(err) => return Outcome::Error((Status::Unauthorized, err)),
+    };
+
+    let mut session_id = None;
+    let mut impersonator_id = None;
+
+    let (user_id, token_version, scopes) = if token.starts_with(API_KEY_PREFIX) {
+        match api_keys::authenticate(db, &token).await {
+            Ok(Some(k)) => (
+                k.user_id,
+                None,
+                k.scopes
+                    .map(|s| s.split_whitespace().map(String::from).collect::<Vec<_>>()),
+            ),
+            Ok(None) => {
+                return Outcome::Error((Status::Unauthorized, "Invalid API key".to_string()));
+            }
+            Err(err) => return Outcome::Error((Status::InternalServerError, err.to_string())),
+        }
+    } else {
+        let claims = match decode_token(&token, keys) {
+            Some(claims) => claims,
+            None => {
+                return Outcome::Error((Status::Unauthorized, "Invalid token".to_string()));
+            }
+        };
+
+        // OAuth access tokens stay valid only while their row has not
+        // been revoked.
+        if let Some(jti) = &claims.jti {
+            match oauth::find_active_token(db, jti).await {
+                Ok(Some(_)) => (),
+                Ok(None) => {
+                    return Outcome::Error((Status::Unauthorized, "Token revoked".to_string()));
+                }
+                Err(err) => {
+                    return Outcome::Error((Status::InternalServerError, err.to_string()));
                 }
-            };
+            }
+        }
 
-            Outcome::Success(AuthenticatedUser { id: claims.sub })
-        } else {
-            Outcome::Error((Status::Unauthorized, "Token absent".to_string()))
+        // Impersonation ends as soon as the admin loses their role.
+        if let Some(act) = &claims.act {
+            match User::find_by_id(act.sub).one(db).await {
+                Ok(Some(admin)) if admin.role == ADMIN_ROLE && admin.disabled_at.is_none() => {
+                    impersonator_id = Some(admin.id)
+                }
+                Ok(_) => {
+                    return Outcome::Error((Status::Unauthorized, "Token revoked".to_string()));
+                }
+                Err(err) => {
+                    return Outcome::Error((Status::InternalServerError, err.to_string()));
+                }
+            }
+        }
+
+        if let Some(sid) = claims.sid {
+            match sessions::touch(db, claims.sub, sid).await {
+                Ok(true) => session_id = Some(sid),
+                Ok(false) => {
+                    return Outcome::Error((Status::Unauthorized, "Session ended".to_string()));
+                }
+                Err(err) => {
+                    return Outcome::Error((Status::InternalServerError, err.to_string()));
+                }
+            }
+        }
+
+        (
+            claims.sub,
+            Some(claims.ver),
+            claims.jti.as_ref().map(|_| {
+                claims
+                    .scope
+                    .unwrap_or_default()
+                    .split_whitespace()
+                    .map(String::from)
+                    .collect::<Vec<_>>()
+            }),
+        )
+    };
+
+    let u = match User::find_by_id(user_id).one(db).await {
+        Ok(Some(u)) => u,
+        Ok(None) => {
+            return Outcome::Error((Status::Unauthorized, "Invalid token".to_string()));
+        }
+        Err(err) => return Outcome::Error((Status::InternalServerError, err.to_string())),
+    };
+
+    if u.deleted_at.is_some() || token_version.is_some_and(|ver| ver != u.token_version) {
+        return Outcome::Error((Status::Unauthorized, "Token revoked".to_string()));
+    }
+
+    if u.disabled_at.is_some() {
+        return Outcome::Error((Status::Forbidden, "Account disabled".to_string()));
+    }
+
+    // Restricted credentials only reach the routes their scopes cover.
+    if let Some(scopes) = &scopes
+        && !required_scope(req).is_some_and(|scope| scopes.contains(&scope))
+    {
+        return Outcome::Error((Status::Forbidden, "Insufficient scope".to_string()));
+    }
+
+    Outcome::Success(AuthenticatedUser {
+        id: user_id,
+        role: u.role,
+        session_id,
+        impersonator_id,
+    })
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AdminUser {
+    type Error = String;
+
+    async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let user = match req.guard::<AuthenticatedUser>().await {
+            Outcome::Success(user) => user,
+            Outcome::Error(err) => return Outcome::Error(err),
+            Outcome::Forward(status) => return Outcome::Forward(status),
+        };
+
+        // The role is read from the user row on every request, so demoting
+        // an admin takes effect immediately.
+        if user.role != ADMIN_ROLE || user.impersonator_id.is_some() {
+            return Outcome::Error((Status::Forbidden, "Admin access required".to_string()));
         }
+
+        Outcome::Success(AdminUser(user))
     }
 }
@@ -0,0 +1,154 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime},
+};
+
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::Set, ColumnTrait, DatabaseConnection, DbErr, EntityTrait,
+    QueryFilter, prelude::DateTimeUtc,
+};
+use sha2::{Digest, Sha256};
+
+use super::{Claims, keys::KeyStore, random_token, sign, unix_time};
+use crate::entities::{oauth_client, oauth_token, prelude::*, user};
+
+/// How long an access token issued to a client is valid.
+pub const ACCESS_TOKEN_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// How long a client has to redeem an authorization code.
+pub const AUTHORIZATION_CODE_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// How long the user has to answer the consent screen.
+const CONSENT_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Generates a client id, and a secret for confidential clients. Public
+/// clients (mobile and single page apps) cannot keep a secret and rely on
+/// PKCE instead.
+pub fn generate_client_credentials(confidential: bool) -> (String, Option<String>) {
+    (random_token(24), confidential.then(|| random_token(48)))
+}
+
+/// Checks a PKCE `code_verifier` against the `S256` challenge sent with the
+/// authorization request.
+pub fn verify_pkce(code_verifier: &str, code_challenge: &str) -> bool {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes())) == code_challenge
+}
+
+/// Issues an access token acting as `u` for `client`, limited to `scopes`.
+/// Returns the token and its lifetime in seconds.
+pub async fn issue_access_token(
+    db: &DatabaseConnection,
+    keys: &KeyStore,
+    client: &oauth_client::Model,
+    u: &user::Model,
+    scopes: &str,
+) -> Result<(String, u64), DbErr> {
+    let jti = random_token(32);
+
+    oauth_token::ActiveModel {
+        jti: Set(jti.to_owned()),
+        client_id: Set(client.id),
+        user_id: Set(u.id),
+        scopes: Set(scopes.to_owned()),
+        expires_at: Set(DateTimeUtc::from(SystemTime::now() + ACCESS_TOKEN_TTL)),
+        ..Default::default()
+    }
+    .insert(db)
+    .await?;
+
+    let token = sign(
+        &Claims {
+            sub: u.id,
+            role: u.role.to_owned(),
+            exp: unix_time() + ACCESS_TOKEN_TTL.as_secs(),
+            ver: u.token_version,
+            scope: Some(scopes.to_owned()),
+            client_id: Some(client.client_id.to_owned()),
+            jti: Some(jti),
+            sid: None,
+            act: None,
+        },
+        keys,
+    );
+
+    Ok((token, ACCESS_TOKEN_TTL.as_secs()))
+}
+
+/// Looks up the row of an access token, returning it unless it has been
+/// revoked.
+pub async fn find_active_token(
+    db: &DatabaseConnection,
+    jti: &str,
+) -> Result<Option<oauth_token::Model>, DbErr> {
+    OauthToken::find()
+        .filter(oauth_token::Column::Jti.eq(jti))
+        .filter(oauth_token::Column::RevokedAt.is_null())
+        .one(db)
+        .await
+}
+
+/// An authorization request the user has been asked to consent to.
+pub struct AuthorizationRequest {
+    pub client_id: i32,
+    pub user_id: i32,
+    pub redirect_uri: String,
+    /// Whether the client named the redirect URI, rather than relying on
+    /// its only registered one. It must then name it again for the token.
+    pub redirect_uri_sent: bool,
+    pub scopes: String,
+    pub state: Option<String>,
+    pub code_challenge: String,
+    created_at: Instant,
+}
+
+impl AuthorizationRequest {
+    pub fn new(
+        client_id: i32,
+        user_id: i32,
+        redirect_uri: String,
+        redirect_uri_sent: bool,
+        scopes: String,
+        state: Option<String>,
+        code_challenge: String,
+    ) -> Self {
+        Self {
+            client_id,
+            user_id,
+            redirect_uri,
+            redirect_uri_sent,
+            scopes,
+            state,
+            code_challenge,
+            created_at: Instant::now(),
+        }
+    }
+}
+
+/// Authorization requests waiting for the user's decision, keyed by a
+/// one-time ticket embedded in the consent form. Only the user the consent
+/// screen was rendered for can know the ticket, so it doubles as the form's
+/// CSRF token.
+#[derive(Default)]
+pub struct ConsentRequests(Mutex<HashMap<String, AuthorizationRequest>>);
+
+impl ConsentRequests {
+    pub fn insert(&self, request: AuthorizationRequest) -> String {
+        let ticket = random_token(32);
+        let mut requests = self.0.lock().unwrap();
+
+        requests.retain(|_, r| r.created_at.elapsed() < CONSENT_TTL);
+        requests.insert(ticket.to_owned(), request);
+
+        ticket
+    }
+
+    pub fn take(&self, ticket: &str) -> Option<AuthorizationRequest> {
+        self.0
+            .lock()
+            .unwrap()
+            .remove(ticket)
+            .filter(|r| r.created_at.elapsed() < CONSENT_TTL)
+    }
+}
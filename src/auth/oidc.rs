@@ -0,0 +1,439 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use jsonwebtoken::{
+    Algorithm, DecodingKey, Validation, decode, decode_header,
+    jwk::{Jwk, JwkSet},
+};
+use reqwest::Url;
+use rocket::{
+    http::{Cookie, CookieJar, SameSite},
+    serde::{Deserialize, Serialize},
+};
+use sha2::{Digest, Sha256};
+
+use super::{hash_token, keys::KeyStore, random_token, sign, unix_time, verify};
+use crate::config::{AppConfig, Secret, list};
+
+/// How long a user has to complete the login at the provider.
+const LOGIN_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// The cookie tying a login to the browser that started it, so nobody can
+/// make a victim complete a login they started themselves.
+pub const STATE_COOKIE: &str = "oidc_state";
+
+/// Tells the state cookie apart from other tokens signed with our keys.
+const STATE_TYP: &str = "oidc_state";
+
+/// How long the provider's keys are used before they are fetched again.
+const JWKS_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// The least time between fetches of the provider's keys prompted by an
+/// unknown key id, so forged tokens cannot make us flood the provider.
+const JWKS_MIN_REFRESH: Duration = Duration::from_secs(60);
+
+/// A provider configured under `oidc.<name>`, e.g. through
+/// `BOOKSTORE_OIDC__GOOGLE__ISSUER`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct OidcProviderConfig {
+    /// Filled in from the provider's key once loaded.
+    #[serde(skip)]
+    pub name: String,
+    pub issuer: String,
+    pub client_id: String,
+    #[serde(default)]
+    pub client_secret: Option<Secret>,
+    pub redirect_uri: String,
+    #[serde(default = "default_scopes")]
+    pub scopes: String,
+    /// The algorithms the provider signs ID tokens with.
+    #[serde(default = "default_algorithms", deserialize_with = "list")]
+    pub algorithms: Vec<Algorithm>,
+    /// Links the first sign in through the provider to the account with the
+    /// same email address. Whoever the provider vouches for takes that
+    /// account over, so only for providers verifying addresses strictly.
+    /// Otherwise users link the provider from their signed in account.
+    #[serde(default)]
+    pub link_by_email: bool,
+}
+
+fn default_scopes() -> String {
+    "openid email profile".to_string()
+}
+
+fn default_algorithms() -> Vec<Algorithm> {
+    vec![Algorithm::RS256]
+}
+
+impl OidcProviderConfig {
+    pub(crate) fn validate(&self, errors: &mut Vec<String>) {
+        if self.algorithms.is_empty() {
+            errors.push(format!("oidc.{}.algorithms cannot be empty", self.name));
+        }
+
+        // Anyone holding the client secret could sign tokens with it.
+        for algorithm in &self.algorithms {
+            if matches!(
+                algorithm,
+                Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+            ) {
+                errors.push(format!(
+                    "oidc.{}.algorithms cannot include {:?}, ID tokens must be signed with the provider's keys",
+                    self.name, algorithm
+                ));
+            }
+        }
+    }
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct Discovery {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct TokenResponse {
+    id_token: String,
+}
+
+/// The claims of a validated ID token we care about.
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct IdTokenClaims {
+    pub sub: String,
+    pub email: Option<String>,
+    #[serde(default)]
+    pub email_verified: bool,
+    pub given_name: Option<String>,
+    pub family_name: Option<String>,
+    nonce: Option<String>,
+}
+
+struct PendingLogin {
+    provider: String,
+    code_verifier: String,
+    nonce: String,
+    link_user_id: Option<i32>,
+    started_at: Instant,
+}
+
+/// The claims of the state cookie. Only a hash of `state` is kept, it is
+/// in the provider URL already.
+#[derive(Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+struct StateClaims {
+    typ: String,
+    state: String,
+    exp: u64,
+}
+
+/// The signed cookie binding `state` to the browser being sent to the
+/// provider.
+pub fn state_cookie(state: &str, keys: &KeyStore, config: &AppConfig) -> Cookie<'static> {
+    let claims = StateClaims {
+        typ: STATE_TYP.to_string(),
+        state: hash_token(state),
+        exp: unix_time() + LOGIN_TTL.as_secs(),
+    };
+
+    Cookie::build((STATE_COOKIE, sign(&claims, keys)))
+        .path("/auth/oidc")
+        .http_only(true)
+        .secure(config.auth_cookie_secure)
+        .same_site(SameSite::Lax)
+        .max_age(rocket::time::Duration::seconds(LOGIN_TTL.as_secs() as i64))
+        .build()
+}
+
+/// Whether the browser returning from the provider is the one that started
+/// the login with `state`. The cookie is spent either way.
+pub fn take_state_cookie(cookies: &CookieJar<'_>, state: &str, keys: &KeyStore) -> bool {
+    let Some(cookie) = cookies.get(STATE_COOKIE) else {
+        return false;
+    };
+
+    let matches = verify::<StateClaims>(cookie.value(), keys)
+        .is_some_and(|claims| claims.typ == STATE_TYP && claims.state == hash_token(state));
+
+    cookies.remove(Cookie::build(STATE_COOKIE).path("/auth/oidc"));
+
+    matches
+}
+
+/// A login completed at a provider.
+pub struct ProviderLogin {
+    pub claims: IdTokenClaims,
+    /// The signed in user linking the provider to their account, when the
+    /// login was started for that.
+    pub link_user_id: Option<i32>,
+}
+
+struct ProviderKeys {
+    keys: JwkSet,
+    fetched_at: Instant,
+}
+
+/// A generic OpenID Connect relying party running the authorization code
+/// flow with PKCE against any number of providers.
+pub struct OidcClient {
+    providers: HashMap<String, OidcProviderConfig>,
+    http: reqwest::Client,
+    discovery: Mutex<HashMap<String, Discovery>>,
+    keys: Mutex<HashMap<String, ProviderKeys>>,
+    pending: Mutex<HashMap<String, PendingLogin>>,
+}
+
+impl OidcClient {
+    pub fn new(providers: &BTreeMap<String, OidcProviderConfig>) -> Self {
+        Self {
+            providers: providers.clone().into_iter().collect(),
+            http: reqwest::Client::new(),
+            discovery: Mutex::new(HashMap::new()),
+            keys: Mutex::new(HashMap::new()),
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn provider(&self, name: &str) -> Option<&OidcProviderConfig> {
+        self.providers.get(name)
+    }
+
+    /// Fetches and caches the provider's discovery document.
+    async fn discover(&self, provider: &OidcProviderConfig) -> Result<Discovery, String> {
+        if let Some(discovery) = self.discovery.lock().unwrap().get(&provider.name) {
+            return Ok(discovery.clone());
+        }
+
+        let url = format!(
+            "{}/.well-known/openid-configuration",
+            provider.issuer.trim_end_matches('/')
+        );
+
+        let discovery: Discovery = self
+            .http
+            .get(&url)
+            .send()
+            .await
+            .and_then(|res| res.error_for_status())
+            .map_err(|err| format!("Discovery failed: {}", err))?
+            .json()
+            .await
+            .map_err(|err| format!("Invalid discovery document: {}", err))?;
+
+        if discovery.issuer != provider.issuer {
+            return Err("The discovery document belongs to another issuer".to_string());
+        }
+
+        self.discovery
+            .lock()
+            .unwrap()
+            .insert(provider.name.to_owned(), discovery.clone());
+
+        Ok(discovery)
+    }
+
+    /// Starts a login, returning the provider URL to send the user to and
+    /// the login's `state`. A login started by a signed in user links the
+    /// provider to their account.
+    pub async fn authorization_url(
+        &self,
+        provider: &OidcProviderConfig,
+        link_user_id: Option<i32>,
+    ) -> Result<(String, String), String> {
+        let discovery = self.discover(provider).await?;
+
+        let state = random_token(32);
+        let nonce = random_token(32);
+        let code_verifier = random_token(64);
+        let code_challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()));
+
+        let url = Url::parse_with_params(
+            &discovery.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", &provider.client_id),
+                ("redirect_uri", &provider.redirect_uri),
+                ("scope", &provider.scopes),
+                ("state", &state),
+                ("nonce", &nonce),
+                ("code_challenge", &code_challenge),
+                ("code_challenge_method", "S256"),
+            ],
+        )
+        .map_err(|err| format!("Invalid authorization endpoint: {}", err))?;
+
+        let mut pending = self.pending.lock().unwrap();
+
+        pending.retain(|_, login| login.started_at.elapsed() < LOGIN_TTL);
+        pending.insert(
+            state.to_owned(),
+            PendingLogin {
+                provider: provider.name.to_owned(),
+                code_verifier,
+                nonce,
+                link_user_id,
+                started_at: Instant::now(),
+            },
+        );
+
+        Ok((url.to_string(), state))
+    }
+
+    /// Completes a login: checks `state`, redeems `code` and validates the
+    /// returned ID token.
+    pub async fn exchange(
+        &self,
+        provider: &OidcProviderConfig,
+        code: &str,
+        state: &str,
+    ) -> Result<ProviderLogin, String> {
+        let login = match self.pending.lock().unwrap().remove(state) {
+            Some(login)
+                if login.provider == provider.name && login.started_at.elapsed() < LOGIN_TTL =>
+            {
+                login
+            }
+            _ => return Err("Unknown or expired login state".to_string()),
+        };
+
+        let discovery = self.discover(provider).await?;
+
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", &provider.redirect_uri),
+            ("client_id", &provider.client_id),
+            ("code_verifier", &login.code_verifier),
+        ];
+
+        if let Some(client_secret) = &provider.client_secret {
+            form.push(("client_secret", client_secret.expose()));
+        }
+
+        let tokens: TokenResponse = self
+            .http
+            .post(&discovery.token_endpoint)
+            .form(&form)
+            .send()
+            .await
+            .and_then(|res| res.error_for_status())
+            .map_err(|err| format!("Token exchange failed: {}", err))?
+            .json()
+            .await
+            .map_err(|err| format!("Invalid token response: {}", err))?;
+
+        let claims = self
+            .validate_id_token(provider, &discovery, &tokens.id_token)
+            .await?;
+
+        if claims.nonce.as_deref() != Some(login.nonce.as_str()) {
+            return Err("The ID token nonce does not match".to_string());
+        }
+
+        Ok(ProviderLogin {
+            claims,
+            link_user_id: login.link_user_id,
+        })
+    }
+
+    /// The provider key `kid`, from the cached keys while they are fresh.
+    /// An unknown key id fetches them again, as the provider may have
+    /// rotated its keys.
+    async fn key(
+        &self,
+        provider: &OidcProviderConfig,
+        discovery: &Discovery,
+        kid: &str,
+    ) -> Result<Jwk, String> {
+        let unknown = || "The ID token was signed with an unknown key".to_string();
+
+        let cached = self
+            .keys
+            .lock()
+            .unwrap()
+            .get(&provider.name)
+            .map(|keys| (keys.keys.find(kid).cloned(), keys.fetched_at.elapsed()));
+
+        match cached {
+            Some((Some(jwk), age)) if age < JWKS_TTL => return Ok(jwk),
+            Some((None, age)) if age < JWKS_MIN_REFRESH => return Err(unknown()),
+            _ => (),
+        }
+
+        let keys: JwkSet = self
+            .http
+            .get(&discovery.jwks_uri)
+            .send()
+            .await
+            .and_then(|res| res.error_for_status())
+            .map_err(|err| format!("Fetching the provider keys failed: {}", err))?
+            .json()
+            .await
+            .map_err(|err| format!("Invalid provider keys: {}", err))?;
+
+        let jwk = keys.find(kid).cloned();
+
+        self.keys.lock().unwrap().insert(
+            provider.name.to_owned(),
+            ProviderKeys {
+                keys,
+                fetched_at: Instant::now(),
+            },
+        );
+
+        jwk.ok_or_else(unknown)
+    }
+
+    async fn validate_id_token(
+        &self,
+        provider: &OidcProviderConfig,
+        discovery: &Discovery,
+        id_token: &str,
+    ) -> Result<IdTokenClaims, String> {
+        let header = decode_header(id_token).map_err(|err| format!("Invalid ID token: {}", err))?;
+
+        // The header is the token's own claim, it only picks among what the
+        // provider is configured with.
+        if !provider.algorithms.contains(&header.alg) {
+            return Err(format!(
+                "The ID token was signed with {:?}, which is not allowed",
+                header.alg
+            ));
+        }
+
+        let kid = header
+            .kid
+            .as_deref()
+            .ok_or("The ID token does not name its signing key".to_string())?;
+
+        let jwk = self.key(provider, discovery, kid).await?;
+
+        // Both name algorithms as in JWA, e.g. `RS256`.
+        if let Some(algorithm) = jwk.common.key_algorithm
+            && algorithm.to_string() != format!("{:?}", header.alg)
+        {
+            return Err("The ID token was signed with another algorithm than its key".to_string());
+        }
+
+        let decoding_key =
+            DecodingKey::from_jwk(&jwk).map_err(|err| format!("Invalid provider key: {}", err))?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_audience(&[&provider.client_id]);
+        validation.set_issuer(&[&discovery.issuer]);
+
+        decode::<IdTokenClaims>(id_token, &decoding_key, &validation)
+            .map(|data| data.claims)
+            .map_err(|err| format!("Invalid ID token: {}", err))
+    }
+}
@@ -0,0 +1,100 @@
+use std::time::{Duration, SystemTime};
+
+use rocket::request::{FromRequest, Outcome, Request};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::Set, ColumnTrait, DatabaseConnection, DbErr, EntityTrait,
+    QueryFilter, prelude::DateTimeUtc,
+};
+
+use super::TOKEN_TTL;
+use crate::{
+    config::AppConfig,
+    entities::{prelude::*, session},
+};
+
+const LAST_SEEN_RESOLUTION: Duration = Duration::from_secs(60);
+
+/// The device a sign in comes from, recorded on its session.
+pub struct ClientInfo {
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ClientInfo {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let config = req.rocket().state::<AppConfig>().unwrap();
+
+        Outcome::Success(ClientInfo {
+            user_agent: req.headers().get_one("User-Agent").map(String::from),
+            ip: config.client_ip(req).map(|ip| ip.to_string()),
+        })
+    }
+}
+
+/// Starts a session for `user_id`, lasting as long as the token issued for
+/// it. Sessions of the user that have run out are cleaned up on the way.
+pub async fn start(
+    db: &DatabaseConnection,
+    user_id: i32,
+    client: &ClientInfo,
+) -> Result<session::Model, DbErr> {
+    let now = SystemTime::now();
+
+    Session::delete_many()
+        .filter(session::Column::UserId.eq(user_id))
+        .filter(session::Column::ExpiresAt.lt(DateTimeUtc::from(now)))
+        .exec(db)
+        .await?;
+
+    session::ActiveModel {
+        user_id: Set(user_id),
+        user_agent: Set(client.user_agent.to_owned()),
+        ip: Set(client.ip.to_owned()),
+        expires_at: Set(DateTimeUtc::from(now + TOKEN_TTL)),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+}
+
+/// Checks that session `id` of `user_id` has not been ended, refreshing its
+/// `last_seen_at`.
+pub async fn touch(db: &DatabaseConnection, user_id: i32, id: i32) -> Result<bool, DbErr> {
+    let s = match Session::find_by_id(id)
+        .filter(session::Column::UserId.eq(user_id))
+        .one(db)
+        .await?
+    {
+        Some(s) => s,
+        None => return Ok(false),
+    };
+
+    let now = SystemTime::now();
+    let stale = DateTimeUtc::from(now - LAST_SEEN_RESOLUTION);
+
+    if s.last_seen_at < stale {
+        let mut active: session::ActiveModel = s.into();
+        active.last_seen_at = Set(DateTimeUtc::from(now));
+        active.update(db).await?;
+    }
+
+    Ok(true)
+}
+
+/// Ends every session of `user_id`, optionally sparing one.
+pub async fn end_all(
+    db: &DatabaseConnection,
+    user_id: i32,
+    except: Option<i32>,
+) -> Result<(), DbErr> {
+    let mut delete = Session::delete_many().filter(session::Column::UserId.eq(user_id));
+
+    if let Some(except) = except {
+        delete = delete.filter(session::Column::Id.ne(except));
+    }
+
+    delete.exec(db).await.map(|_| ())
+}
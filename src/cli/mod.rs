@@ -0,0 +1,447 @@
+use std::path::PathBuf;
+
+use bcrypt::{DEFAULT_COST, hash};
+use clap::{Parser, Subcommand};
+use sea_orm::{ActiveModelTrait, ActiveValue::Set, DatabaseConnection, EntityTrait};
+use sea_orm_migration::MigratorTrait;
+use tracing_subscriber::EnvFilter;
+
+use crate::{
+    audit,
+    auth::{
+        ADMIN_ROLE, issue_token,
+        keys::KeyStore,
+        random_token,
+        sessions::{self, ClientInfo},
+    },
+    build_rocket,
+    config::AppConfig,
+    db,
+    entities::{prelude::*, user},
+    migrator::Migrator,
+    mount,
+    seed::{
+        self, Fixtures,
+        fake::{self, FakeOptions},
+    },
+};
+
+/// The bookstore API server and its management commands.
+#[derive(Parser)]
+#[command(version)]
+pub struct Cli {
+    /// Runs `serve` when left out.
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Starts the API server.
+    Serve {
+        /// Applies pending migrations before serving.
+        #[arg(long)]
+        migrate: bool,
+    },
+    /// Manages the database schema.
+    Migrate {
+        #[command(subcommand)]
+        action: MigrateCommand,
+    },
+    /// Loads fixture files, or the demo data when given none. Loading them
+    /// again updates what changed.
+    #[command(args_conflicts_with_subcommands = true)]
+    Seed {
+        #[command(subcommand)]
+        action: Option<SeedCommand>,
+        /// YAML or JSON files, merged in order.
+        files: Vec<PathBuf>,
+    },
+    /// Creates an admin account, or makes an existing account an admin.
+    CreateAdmin {
+        #[arg(long)]
+        email: String,
+        #[arg(long, default_value = "Admin")]
+        firstname: String,
+        #[arg(long, default_value = "User")]
+        lastname: String,
+        /// A random password is generated and printed when left out.
+        #[arg(long)]
+        password: Option<String>,
+    },
+    /// Prints every mounted route.
+    Routes,
+    /// Validates the configuration and prints it, secrets redacted.
+    CheckConfig,
+    /// Manages tokens, for debugging.
+    Token {
+        #[command(subcommand)]
+        action: TokenCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum MigrateCommand {
+    /// Applies pending migrations.
+    Up {
+        /// Only applies this many.
+        #[arg(long)]
+        steps: Option<u32>,
+    },
+    /// Rolls back applied migrations.
+    Down {
+        #[arg(long, default_value_t = 1)]
+        steps: u32,
+    },
+    /// Lists migrations and whether they have been applied.
+    Status,
+    /// Drops every table and applies all migrations.
+    Fresh {
+        /// Confirms that all data is to be deleted.
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Rolls back all migrations and applies them again.
+    Refresh {
+        /// Confirms that all data is to be deleted.
+        #[arg(long)]
+        yes: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum SeedCommand {
+    /// Generates authors and books for performance testing, replacing the
+    /// ones generated before.
+    Fake {
+        /// The owner of the generated data, created when missing. Accounts
+        /// the generator did not create are refused.
+        #[arg(long, default_value = "fake@example.com")]
+        user: String,
+        #[arg(long, default_value_t = 1000)]
+        authors: u32,
+        #[arg(long, default_value_t = 5)]
+        books_per_author: u32,
+        /// The same seed always generates the same data.
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+    },
+}
+
+#[derive(Subcommand)]
+enum TokenCommand {
+    /// Starts a session for a user and prints its access token.
+    Issue {
+        /// The user's ID or email address.
+        #[arg(long)]
+        user: String,
+    },
+}
+
+impl Cli {
+    fn serving(&self) -> bool {
+        matches!(self.command, None | Some(Command::Serve { .. }))
+    }
+}
+
+/// Logs JSON lines, filtered with `BOOKSTORE_LOG` (e.g. `info,bookstore=debug`
+/// to include every query). Rocket's own log output is routed here too. The
+/// server logs to stdout, other commands to stderr to keep their output
+/// clean.
+pub fn init_logging(cli: &Cli) {
+    let subscriber = tracing_subscriber::fmt()
+        .json()
+        .with_env_filter(
+            EnvFilter::try_from_env("BOOKSTORE_LOG").unwrap_or_else(|_| EnvFilter::new("info")),
+        )
+        .with_current_span(true)
+        .with_span_list(false);
+
+    match cli.serving() {
+        true => subscriber.init(),
+        false => subscriber.with_writer(std::io::stderr).init(),
+    }
+}
+
+pub async fn run(cli: Cli, config: AppConfig) -> Result<(), String> {
+    match cli.command.unwrap_or(Command::Serve { migrate: false }) {
+        Command::Serve { migrate } => serve(config, migrate).await,
+        Command::Migrate { action } => run_migrations(&config, action).await,
+        Command::Seed {
+            action:
+                Some(SeedCommand::Fake {
+                    user,
+                    authors,
+                    books_per_author,
+                    seed,
+                }),
+            ..
+        } => {
+            seed_fake(
+                &config,
+                FakeOptions {
+                    email: user,
+                    authors,
+                    books_per_author,
+                    seed,
+                },
+            )
+            .await
+        }
+        Command::Seed {
+            action: None,
+            files,
+        } => seed_fixtures(&config, &files).await,
+        Command::CreateAdmin {
+            email,
+            firstname,
+            lastname,
+            password,
+        } => create_admin(&config, email, firstname, lastname, password).await,
+        Command::Routes => {
+            print_routes();
+            Ok(())
+        }
+        Command::CheckConfig => check_config(&config),
+        Command::Token {
+            action: TokenCommand::Issue { user },
+        } => issue(&config, &user).await,
+    }
+}
+
+/// Failed attempts are logged by `db::connect`.
+async fn connect(config: &AppConfig) -> Result<DatabaseConnection, String> {
+    db::connect(config)
+        .await
+        .map_err(|_| "Could not connect to the database.".to_string())
+}
+
+async fn serve(config: AppConfig, migrate: bool) -> Result<(), String> {
+    let db = connect(&config).await?;
+
+    if migrate {
+        Migrator::up(&db, None)
+            .await
+            .map_err(|err| err.to_string())?;
+    } else {
+        let pending = Migrator::get_pending_migrations(&db)
+            .await
+            .map_err(|err| err.to_string())?;
+
+        if !pending.is_empty() {
+            tracing::warn!(
+                pending = pending.len(),
+                "there are pending migrations, run `migrate up` or `serve --migrate`"
+            );
+        }
+    }
+
+    let replicas = db::connect_replicas(&config)
+        .await
+        .map_err(|_| "Could not connect to the read replicas.".to_string())?;
+
+    build_rocket(config, db, replicas)?
+        .launch()
+        .await
+        .map_err(|err| err.to_string())?;
+
+    Ok(())
+}
+
+async fn run_migrations(config: &AppConfig, action: MigrateCommand) -> Result<(), String> {
+    let db = connect(config).await?;
+
+    let result = match action {
+        MigrateCommand::Up { steps } => Migrator::up(&db, steps).await,
+        MigrateCommand::Down { steps } => Migrator::down(&db, Some(steps)).await,
+        MigrateCommand::Status => {
+            for migration in Migrator::get_migration_with_status(&db)
+                .await
+                .map_err(|err| err.to_string())?
+            {
+                println!("{:<8} {}", migration.status(), migration.name());
+            }
+
+            Ok(())
+        }
+        MigrateCommand::Fresh { yes: false } | MigrateCommand::Refresh { yes: false } => {
+            return Err("This deletes all data, pass --yes to confirm.".to_string());
+        }
+        MigrateCommand::Fresh { yes: true } => Migrator::fresh(&db).await,
+        MigrateCommand::Refresh { yes: true } => Migrator::refresh(&db).await,
+    };
+
+    result.map_err(|err| err.to_string())
+}
+
+async fn seed_fixtures(config: &AppConfig, files: &[PathBuf]) -> Result<(), String> {
+    let fixtures = match files.is_empty() {
+        true => Fixtures::demo()?,
+        false => Fixtures::read(files)?,
+    };
+
+    let db = connect(config).await?;
+    let report = seed::load(&db, &fixtures).await?;
+
+    println!("Users:   {}", report.users);
+    println!("Authors: {}", report.authors);
+    println!("Books:   {}", report.books);
+
+    for (email, password) in report.generated_passwords {
+        println!("Created {} with password {}", email, password);
+    }
+
+    Ok(())
+}
+
+async fn seed_fake(config: &AppConfig, options: FakeOptions) -> Result<(), String> {
+    let db = connect(config).await?;
+    let report = fake::generate(&db, &options).await?;
+
+    println!(
+        "Generated {} authors and {} books for {}.",
+        report.authors, report.books, options.email
+    );
+
+    if let Some(password) = report.generated_password {
+        println!("Created {} with password {}", options.email, password);
+    }
+
+    Ok(())
+}
+
+async fn create_admin(
+    config: &AppConfig,
+    email: String,
+    firstname: String,
+    lastname: String,
+    password: Option<String>,
+) -> Result<(), String> {
+    let db = connect(config).await?;
+
+    let existing = User::find_by_email(&email)
+        .one(&db)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    if let Some(u) = existing {
+        let mut u: user::ActiveModel = u.into();
+
+        u.role = Set(ADMIN_ROLE.to_string());
+
+        let u = u.update(&db).await.map_err(|err| err.to_string())?;
+
+        audit::record(
+            &db,
+            u.id,
+            "role_changed",
+            Some(format!("{} from the command line", ADMIN_ROLE)),
+        )
+        .await
+        .map_err(|err| err.to_string())?;
+
+        println!("{} is now an admin.", email);
+
+        return Ok(());
+    }
+
+    let generated = password.is_none();
+    let password = password.unwrap_or_else(|| random_token(20));
+
+    let u = user::ActiveModel {
+        email: Set(email.to_owned()),
+        password: Set(hash(&password, DEFAULT_COST).unwrap()),
+        firstname: Set(firstname),
+        lastname: Set(lastname),
+        role: Set(ADMIN_ROLE.to_string()),
+        ..Default::default()
+    }
+    .insert(&db)
+    .await
+    .map_err(|err| err.to_string())?;
+
+    audit::record(
+        &db,
+        u.id,
+        "sign_up",
+        Some("admin created from the command line".to_string()),
+    )
+    .await
+    .map_err(|err| err.to_string())?;
+
+    match generated {
+        true => println!("Created admin {} with password {}", email, password),
+        false => println!("Created admin {}", email),
+    }
+
+    Ok(())
+}
+
+fn print_routes() {
+    let rocket = mount(rocket::custom(AppConfig::figment()));
+
+    let mut routes = rocket.routes().collect::<Vec<_>>();
+    routes.sort_by_key(|route| (route.uri.to_string(), route.method.as_str()));
+
+    for route in routes {
+        println!(
+            "{:<7} {:<60} {}",
+            route.method,
+            route.uri.to_string(),
+            route.name.as_deref().unwrap_or("")
+        );
+    }
+}
+
+fn check_config(config: &AppConfig) -> Result<(), String> {
+    KeyStore::load(config)?;
+
+    println!("{:#?}", config);
+    println!("The configuration is valid.");
+
+    Ok(())
+}
+
+async fn issue(config: &AppConfig, user: &str) -> Result<(), String> {
+    let keys = KeyStore::load(config)?;
+    let db = connect(config).await?;
+
+    let query = match user.parse::<i32>() {
+        Ok(id) => User::find_by_id(id),
+        Err(_) => User::find_by_email(user),
+    };
+
+    let u = query
+        .one(&db)
+        .await
+        .map_err(|err| err.to_string())?
+        .ok_or_else(|| format!("No user `{}`.", user))?;
+
+    if u.deleted_at.is_some() || u.disabled_at.is_some() {
+        return Err(format!("The account of `{}` is not active.", user));
+    }
+
+    let session = sessions::start(
+        &db,
+        u.id,
+        &ClientInfo {
+            user_agent: Some("bookstore token issue".to_string()),
+            ip: None,
+        },
+    )
+    .await
+    .map_err(|err| err.to_string())?;
+
+    audit::record(
+        &db,
+        u.id,
+        "token_issued",
+        Some("from the command line".to_string()),
+    )
+    .await
+    .map_err(|err| err.to_string())?;
+
+    println!("{}", issue_token(&u, &session, &keys));
+
+    Ok(())
+}
@@ -0,0 +1,374 @@
+use std::{collections::BTreeMap, fmt, net::IpAddr, path::PathBuf, str::FromStr};
+
+use regex::Regex;
+use rocket::{
+    Request,
+    figment::{
+        Figment,
+        providers::{Env, Format, Toml},
+    },
+    serde::{
+        Deserialize, Deserializer,
+        de::{self, MapAccess, Visitor},
+    },
+};
+
+use crate::{
+    auth::{keys::JwtKeyConfig, oidc::OidcProviderConfig},
+    db::{Backend, PoolConfig, ReplicaConfig},
+    fairings::{cors::CorsConfig, rate_limit::RouteLimit, security_headers::SecurityHeadersConfig},
+    metrics::{AllowedNetwork, MetricsConfig},
+};
+
+/// A configuration value that must not end up in logs. It is given either
+/// inline or as `{ file = "/run/secrets/..." }`, read once at startup, and
+/// printed as `[redacted]`.
+#[derive(Clone)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[redacted]")
+    }
+}
+
+impl<'de> Deserialize<'de> for Secret {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct SecretVisitor;
+
+        impl<'de> Visitor<'de> for SecretVisitor {
+            type Value = Secret;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a string or a table with a `file` path")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Secret, E> {
+                Ok(Secret(value.to_string()))
+            }
+
+            // Environment variables that look like numbers arrive as numbers.
+            fn visit_i64<E: de::Error>(self, value: i64) -> Result<Secret, E> {
+                Ok(Secret(value.to_string()))
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<Secret, E> {
+                Ok(Secret(value.to_string()))
+            }
+
+            fn visit_f64<E: de::Error>(self, value: f64) -> Result<Secret, E> {
+                Ok(Secret(value.to_string()))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Secret, A::Error> {
+                let mut file = None::<PathBuf>;
+
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "file" => file = Some(map.next_value()?),
+                        _ => return Err(de::Error::unknown_field(&key, &["file"])),
+                    }
+                }
+
+                let file = file.ok_or_else(|| de::Error::missing_field("file"))?;
+
+                std::fs::read_to_string(&file)
+                    .map(|secret| Secret(secret.trim_end_matches(['\r', '\n']).to_string()))
+                    .map_err(|err| {
+                        de::Error::custom(format!(
+                            "cannot read secret file `{}`: {}",
+                            file.display(),
+                            err
+                        ))
+                    })
+            }
+        }
+
+        deserializer.deserialize_any(SecretVisitor)
+    }
+}
+
+/// Deserializes a list given either as an array or as a comma-separated
+/// string, the only way to pass one in an environment variable, parsing
+/// every item.
+pub fn list<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    #[derive(Deserialize)]
+    #[serde(crate = "rocket::serde", untagged)]
+    enum List {
+        Joined(String),
+        Items(Vec<String>),
+    }
+
+    let items = match List::deserialize(deserializer)? {
+        List::Joined(items) => items
+            .split(',')
+            .map(|item| item.trim().to_string())
+            .filter(|item| !item.is_empty())
+            .collect(),
+        List::Items(items) => items,
+    };
+
+    items
+        .iter()
+        .map(|item| item.parse().map_err(de::Error::custom))
+        .collect()
+}
+
+/// Deserializes an optional regular expression, rejecting invalid ones.
+pub fn regex<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Regex>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|re| Regex::new(&re).map_err(de::Error::custom))
+        .transpose()
+}
+
+/// The application's configuration.
+///
+/// It is read from the selected profile (`ROCKET_PROFILE`, `debug` or
+/// `release` by default) of `Rocket.toml` and of `Bookstore.toml` (or the
+/// file named by `BOOKSTORE_CONFIG`), overridden by `BOOKSTORE_*` variables
+/// and `DATABASE_URL`. Nested keys are separated by `__` in variable names,
+/// e.g. `BOOKSTORE_CORS__ORIGINS` for `cors.origins`.
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct AppConfig {
+    /// Takes precedence over the `db_*` settings, which describe a Postgres
+    /// database. Its scheme picks the backend, e.g.
+    /// `sqlite://bookstore.db?mode=rwc` for a local SQLite file.
+    #[serde(default)]
+    pub database_url: Option<Secret>,
+    #[serde(default = "default_db_host")]
+    pub db_host: String,
+    #[serde(default = "default_db_port")]
+    pub db_port: u16,
+    #[serde(default = "default_db_username")]
+    pub db_username: String,
+    #[serde(default)]
+    pub db_password: Option<Secret>,
+    #[serde(default = "default_db_database")]
+    pub db_database: String,
+    #[serde(default)]
+    pub db_pool: PoolConfig,
+    #[serde(default)]
+    pub db_replicas: ReplicaConfig,
+    /// Signs tokens with HS256 unless `jwt_keys` are configured.
+    #[serde(default)]
+    pub jwt_secret: Option<Secret>,
+    #[serde(default, deserialize_with = "list")]
+    pub jwt_keys: Vec<JwtKeyConfig>,
+    #[serde(default)]
+    pub jwt_active_kid: Option<String>,
+    /// OpenID Connect providers by name, e.g. `[default.oidc.google]`.
+    #[serde(default)]
+    pub oidc: BTreeMap<String, OidcProviderConfig>,
+    #[serde(default)]
+    pub auth_cookie: bool,
+    #[serde(default = "default_true")]
+    pub auth_cookie_secure: bool,
+    #[serde(default = "default_export_dir")]
+    pub export_dir: String,
+    #[serde(default = "default_export_inline_limit")]
+    pub export_inline_limit: u64,
+    /// How long a background export can be downloaded before its file is
+    /// deleted.
+    #[serde(default = "default_export_ttl_secs")]
+    pub export_ttl_secs: u64,
+    /// An empty list turns rate limiting off.
+    #[serde(default = "RouteLimit::defaults", deserialize_with = "list")]
+    pub rate_limits: Vec<RouteLimit>,
+    #[serde(default)]
+    pub cors: CorsConfig,
+    #[serde(default)]
+    pub security_headers: SecurityHeadersConfig,
+    #[serde(default = "default_slow_query_ms")]
+    pub slow_query_ms: u64,
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+    /// Proxies trusted to pass on the client's address in Rocket's
+    /// `ip_header` (`X-Real-IP` unless configured otherwise). Anyone else
+    /// could forge it, so other requests are known by the address they come
+    /// from.
+    #[serde(default, deserialize_with = "list")]
+    pub trusted_proxies: Vec<AllowedNetwork>,
+}
+
+fn default_db_host() -> String {
+    "localhost".to_string()
+}
+
+fn default_db_port() -> u16 {
+    5432
+}
+
+fn default_db_username() -> String {
+    "postgres".to_string()
+}
+
+fn default_db_database() -> String {
+    "bookstore".to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_export_dir() -> String {
+    std::env::temp_dir()
+        .join("bookstore-exports")
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn default_export_inline_limit() -> u64 {
+    1000
+}
+
+fn default_export_ttl_secs() -> u64 {
+    24 * 60 * 60
+}
+
+fn default_slow_query_ms() -> u64 {
+    200
+}
+
+impl AppConfig {
+    /// The configuration sources, Rocket's own settings included, so both
+    /// are read from the same files and profile.
+    pub fn figment() -> Figment {
+        rocket::Config::figment()
+            .merge(Toml::file(Env::var_or("BOOKSTORE_CONFIG", "Bookstore.toml")).nested())
+            .merge(
+                Env::prefixed("BOOKSTORE_")
+                    .ignore(&["CONFIG", "LOG"])
+                    .split("__")
+                    .global(),
+            )
+            .merge(
+                Env::raw()
+                    .only(&["DATABASE_URL"])
+                    .map(|_| "database_url".into())
+                    .global(),
+            )
+    }
+
+    /// Reads and validates the configuration, describing every problem
+    /// found on failure.
+    pub fn load(figment: &Figment) -> Result<Self, String> {
+        let mut config: Self = figment.extract().map_err(|errors| {
+            errors
+                .into_iter()
+                .map(|err| err.to_string())
+                .collect::<Vec<_>>()
+                .join("\n")
+        })?;
+
+        for (name, provider) in config.oidc.iter_mut() {
+            provider.name = name.to_owned();
+        }
+
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let mut errors = Vec::new();
+
+        if self.jwt_keys.is_empty() && self.jwt_secret.is_none() {
+            errors.push(
+                "jwt_secret (BOOKSTORE_JWT_SECRET) must be set unless jwt_keys are configured"
+                    .to_string(),
+            );
+        }
+
+        if self
+            .jwt_secret
+            .as_ref()
+            .is_some_and(|secret| secret.expose().is_empty())
+        {
+            errors.push("jwt_secret must not be empty".to_string());
+        }
+
+        if !self.jwt_keys.is_empty()
+            && !self
+                .jwt_keys
+                .iter()
+                .any(|key| Some(&key.kid) == self.jwt_active_kid.as_ref())
+        {
+            errors.push("jwt_active_kid must name one of the keys in jwt_keys".to_string());
+        }
+
+        let backend = match Backend::from_url(&self.database_url()) {
+            Ok(backend) if !backend.enabled() => {
+                errors.push(format!(
+                    "database_url is a {0} URL but this build does not include {0}, rebuild with `--features {0}`",
+                    backend.feature()
+                ));
+                None
+            }
+            Ok(backend) => Some(backend),
+            Err(err) => {
+                errors.push(err);
+                None
+            }
+        };
+
+        for provider in self.oidc.values() {
+            provider.validate(&mut errors);
+        }
+
+        self.db_pool.validate(backend, &mut errors);
+        self.db_replicas.validate(backend, &mut errors);
+
+        if self.cors.credentials && self.cors.origins.iter().any(|o| o == "*") {
+            errors.push("cors.credentials cannot be combined with the `*` origin".to_string());
+        }
+
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(errors.join("\n")),
+        }
+    }
+
+    /// The address of the client, as passed on by a trusted proxy or else
+    /// as connected.
+    pub fn client_ip(&self, req: &Request<'_>) -> Option<IpAddr> {
+        let remote = req.remote()?.ip();
+
+        match self
+            .trusted_proxies
+            .iter()
+            .any(|proxy| proxy.contains(remote))
+        {
+            true => Some(req.real_ip().unwrap_or(remote)),
+            false => Some(remote),
+        }
+    }
+
+    /// `database_url`, else a URL built from the `db_*` settings.
+    pub fn database_url(&self) -> String {
+        match &self.database_url {
+            Some(url) => url.expose().to_string(),
+            None => format!(
+                "postgres://{}:{}@{}:{}/{}",
+                self.db_username,
+                self.db_password
+                    .as_ref()
+                    .map_or("", |password| password.expose()),
+                self.db_host,
+                self.db_port,
+                self.db_database
+            ),
+        }
+    }
+}
@@ -0,0 +1,421 @@
+use std::time::{Duration, SystemTime};
+
+use bcrypt::{DEFAULT_COST, hash};
+use rocket::{
+    State,
+    http::Status,
+    serde::{Deserialize, Serialize, json::Json},
+};
+use sea_orm::{
+    ActiveModelTrait,
+    ActiveValue::Set,
+    ColumnTrait, Condition, DatabaseConnection, EntityTrait, ModelTrait, PaginatorTrait,
+    QueryFilter, QueryOrder,
+    prelude::{DateTimeUtc, Expr},
+    sea_query::Func,
+};
+
+use super::{
+    ErrorResponse, Response, SuccessResponse,
+    auth::ResSignIn,
+    authors::{ResAuthor, ResAuthorList},
+    books::{ResBook, ResBookList},
+};
+use crate::{
+    audit,
+    auth::{
+        ADMIN_ROLE, AdminUser, ROLES, hash_token, issue_impersonation_token,
+        keys::KeyStore,
+        random_token,
+        sessions::{self, ClientInfo},
+    },
+    entities::{prelude::*, user},
+    mail,
+};
+
+const DEFAULT_PER_PAGE: u64 = 20;
+const MAX_PER_PAGE: u64 = 100;
+
+const PASSWORD_RESET_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ResAdminUser {
+    id: i32,
+    email: String,
+    firstname: String,
+    lastname: String,
+    role: String,
+    disabled_at: Option<DateTimeUtc>,
+    deleted_at: Option<DateTimeUtc>,
+    created_at: DateTimeUtc,
+    updated_at: DateTimeUtc,
+}
+
+impl From<&user::Model> for ResAdminUser {
+    fn from(u: &user::Model) -> Self {
+        Self {
+            id: u.id,
+            email: u.email.to_owned(),
+            firstname: u.firstname.to_owned(),
+            lastname: u.lastname.to_owned(),
+            role: u.role.to_owned(),
+            disabled_at: u.disabled_at,
+            deleted_at: u.deleted_at,
+            created_at: u.created_at,
+            updated_at: u.updated_at,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ResAdminUserList {
+    total: u64,
+    page: u64,
+    per_page: u64,
+    users: Vec<ResAdminUser>,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ReqRole {
+    role: String,
+}
+
+async fn find_user(db: &DatabaseConnection, id: i32) -> Result<user::Model, ErrorResponse> {
+    match User::find_by_id(id).one(db).await? {
+        Some(u) => Ok(u),
+        None => Err(ErrorResponse((
+            Status::NotFound,
+            "No user with the specified ID.".to_string(),
+        ))),
+    }
+}
+
+fn not_yourself(admin: &AdminUser, id: i32) -> Result<(), ErrorResponse> {
+    match admin.0.id == id {
+        true => Err(ErrorResponse((
+            Status::BadRequest,
+            "Admins cannot do this to their own account.".to_string(),
+        ))),
+        false => Ok(()),
+    }
+}
+
+/// Lists users, newest first, optionally searching email and name for `q`
+/// and filtering by `role`.
+#[get("/?<q>&<role>&<page>&<per_page>")]
+pub async fn index(
+    db: &State<DatabaseConnection>,
+    _admin: AdminUser,
+    q: Option<&str>,
+    role: Option<&str>,
+    page: Option<u64>,
+    per_page: Option<u64>,
+) -> Response<Json<ResAdminUserList>> {
+    let db = db as &DatabaseConnection;
+
+    let page = page.unwrap_or(1).max(1);
+    let per_page = per_page.unwrap_or(DEFAULT_PER_PAGE).clamp(1, MAX_PER_PAGE);
+
+    let mut query = User::find().order_by_desc(user::Column::CreatedAt);
+
+    if let Some(q) = q.filter(|q| !q.is_empty()) {
+        let pattern = format!("%{}%", q.to_lowercase());
+
+        query = query.filter(
+            [
+                user::Column::Email,
+                user::Column::Firstname,
+                user::Column::Lastname,
+            ]
+            .into_iter()
+            .fold(Condition::any(), |condition, column| {
+                condition.add(Expr::expr(Func::lower(Expr::col(column))).like(&pattern))
+            }),
+        );
+    }
+
+    if let Some(role) = role {
+        query = query.filter(user::Column::Role.eq(role));
+    }
+
+    let paginator = query.paginate(db, per_page);
+    let total = paginator.num_items().await?;
+
+    let users = paginator
+        .fetch_page(page - 1)
+        .await?
+        .iter()
+        .map(ResAdminUser::from)
+        .collect::<Vec<_>>();
+
+    Ok(SuccessResponse((
+        Status::Ok,
+        Json(ResAdminUserList {
+            total,
+            page,
+            per_page,
+            users,
+        }),
+    )))
+}
+
+#[get("/<id>")]
+pub async fn show(
+    db: &State<DatabaseConnection>,
+    _admin: AdminUser,
+    id: i32,
+) -> Response<Json<ResAdminUser>> {
+    let db = db as &DatabaseConnection;
+
+    let u = find_user(db, id).await?;
+
+    Ok(SuccessResponse((Status::Ok, Json(ResAdminUser::from(&u)))))
+}
+
+#[get("/<id>/authors")]
+pub async fn authors(
+    db: &State<DatabaseConnection>,
+    _admin: AdminUser,
+    id: i32,
+) -> Response<Json<ResAuthorList>> {
+    let db = db as &DatabaseConnection;
+
+    let authors = find_user(db, id)
+        .await?
+        .find_related(Author)
+        .all(db)
+        .await?
+        .iter()
+        .map(ResAuthor::from)
+        .collect::<Vec<_>>();
+
+    Ok(SuccessResponse((
+        Status::Ok,
+        Json(ResAuthorList {
+            total: authors.len(),
+            authors,
+        }),
+    )))
+}
+
+#[get("/<id>/books")]
+pub async fn books(
+    db: &State<DatabaseConnection>,
+    _admin: AdminUser,
+    id: i32,
+) -> Response<Json<ResBookList>> {
+    let db = db as &DatabaseConnection;
+
+    let books = find_user(db, id)
+        .await?
+        .find_related(Book)
+        .all(db)
+        .await?
+        .iter()
+        .map(ResBook::from)
+        .collect::<Vec<_>>();
+
+    Ok(SuccessResponse((
+        Status::Ok,
+        Json(ResBookList {
+            total: books.len(),
+            books,
+        }),
+    )))
+}
+
+#[put("/<id>/role", data = "<req_role>")]
+pub async fn update_role(
+    db: &State<DatabaseConnection>,
+    admin: AdminUser,
+    id: i32,
+    req_role: Json<ReqRole>,
+) -> Response<Json<ResAdminUser>> {
+    let db = db as &DatabaseConnection;
+
+    not_yourself(&admin, id)?;
+
+    if !ROLES.contains(&req_role.role.as_str()) {
+        return Err(ErrorResponse((
+            Status::BadRequest,
+            format!("Unknown role `{}`", req_role.role),
+        )));
+    }
+
+    let mut u: user::ActiveModel = find_user(db, id).await?.into();
+
+    u.role = Set(req_role.role.to_owned());
+
+    let u = u.update(db).await?;
+
+    audit::record(
+        db,
+        u.id,
+        "role_changed",
+        Some(format!("{} by admin {}", u.role, admin.0.id)),
+    )
+    .await?;
+
+    Ok(SuccessResponse((Status::Ok, Json(ResAdminUser::from(&u)))))
+}
+
+/// Disables the account, signing it out everywhere. The auth guard rejects
+/// every credential of a disabled account, API keys included.
+#[post("/<id>/disable")]
+pub async fn disable(
+    db: &State<DatabaseConnection>,
+    admin: AdminUser,
+    id: i32,
+) -> Response<Json<ResAdminUser>> {
+    let db = db as &DatabaseConnection;
+
+    not_yourself(&admin, id)?;
+
+    let now = DateTimeUtc::from(SystemTime::now());
+    let mut u: user::ActiveModel = find_user(db, id).await?.into();
+
+    u.disabled_at = Set(Some(now));
+
+    let u = u.update(db).await?;
+
+    sessions::end_all(db, u.id, None).await?;
+
+    audit::record(
+        db,
+        u.id,
+        "account_disabled",
+        Some(format!("by admin {}", admin.0.id)),
+    )
+    .await?;
+
+    Ok(SuccessResponse((Status::Ok, Json(ResAdminUser::from(&u)))))
+}
+
+#[post("/<id>/enable")]
+pub async fn enable(
+    db: &State<DatabaseConnection>,
+    admin: AdminUser,
+    id: i32,
+) -> Response<Json<ResAdminUser>> {
+    let db = db as &DatabaseConnection;
+
+    let mut u: user::ActiveModel = find_user(db, id).await?.into();
+
+    u.disabled_at = Set(None);
+
+    let u = u.update(db).await?;
+
+    audit::record(
+        db,
+        u.id,
+        "account_enabled",
+        Some(format!("by admin {}", admin.0.id)),
+    )
+    .await?;
+
+    Ok(SuccessResponse((Status::Ok, Json(ResAdminUser::from(&u)))))
+}
+
+/// Replaces the password with an unknown one, signs the user out everywhere
+/// and mails them a token to choose a new password with
+/// `POST /auth/reset-password`.
+#[post("/<id>/password-reset")]
+pub async fn force_password_reset(
+    db: &State<DatabaseConnection>,
+    admin: AdminUser,
+    id: i32,
+) -> Response<String> {
+    let db = db as &DatabaseConnection;
+
+    let u = find_user(db, id).await?;
+
+    if u.deleted_at.is_some() {
+        return Err(ErrorResponse((
+            Status::BadRequest,
+            "The account has been deleted.".to_string(),
+        )));
+    }
+
+    let token = random_token(32);
+    let now = SystemTime::now();
+    let token_version = u.token_version + 1;
+    let mut u: user::ActiveModel = u.into();
+
+    u.password = Set(hash(random_token(32), DEFAULT_COST).unwrap());
+    u.password_reset_token = Set(Some(hash_token(&token)));
+    u.password_reset_expires_at = Set(Some(DateTimeUtc::from(now + PASSWORD_RESET_TTL)));
+    u.token_version = Set(token_version);
+
+    let u = u.update(db).await?;
+
+    sessions::end_all(db, u.id, None).await?;
+
+    audit::record(
+        db,
+        u.id,
+        "password_reset_forced",
+        Some(format!("by admin {}", admin.0.id)),
+    )
+    .await?;
+
+    mail::send(
+        &u.email,
+        "Choose a new password",
+        &format!(
+            "An administrator reset your password. Use this token to choose a new one: {}",
+            token
+        ),
+    );
+
+    Ok(SuccessResponse((
+        Status::Ok,
+        "Password reset, the user has been sent a reset token.".to_string(),
+    )))
+}
+
+/// Issues a token acting as the user. The token names the admin in its `act`
+/// claim, and the impersonation is recorded in both users' audit logs.
+#[post("/<id>/impersonate")]
+pub async fn impersonate(
+    db: &State<DatabaseConnection>,
+    keys: &State<KeyStore>,
+    client: ClientInfo,
+    admin: AdminUser,
+    id: i32,
+) -> Response<Json<ResSignIn>> {
+    let db = db as &DatabaseConnection;
+
+    not_yourself(&admin, id)?;
+
+    let u = find_user(db, id).await?;
+
+    if u.deleted_at.is_some() || u.disabled_at.is_some() || u.role == ADMIN_ROLE {
+        return Err(ErrorResponse((
+            Status::BadRequest,
+            "Only active, non-admin accounts can be impersonated.".to_string(),
+        )));
+    }
+
+    let session = sessions::start(db, u.id, &client).await?;
+    let token = issue_impersonation_token(&u, &session, &admin.0, keys);
+
+    audit::record(
+        db,
+        admin.0.id,
+        "impersonation_started",
+        Some(u.id.to_string()),
+    )
+    .await?;
+    audit::record(
+        db,
+        u.id,
+        "impersonated",
+        Some(format!("by admin {}", admin.0.id)),
+    )
+    .await?;
+
+    Ok(SuccessResponse((Status::Ok, Json(ResSignIn { token }))))
+}
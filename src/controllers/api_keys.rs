@@ -0,0 +1,255 @@
+use std::time::{Duration, SystemTime};
+
+use rocket::{
+    State,
+    http::Status,
+    serde::{Deserialize, Serialize, json::Json},
+};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::Set, ColumnTrait, DatabaseConnection, EntityTrait, ModelTrait,
+    QueryFilter, QueryOrder, prelude::DateTimeUtc,
+};
+
+use super::{ErrorResponse, Response, SuccessResponse};
+use crate::{
+    audit,
+    auth::{AuthenticatedUser, api_keys, hash_token, join_scopes},
+    entities::{api_key, prelude::*},
+};
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ResApiKey {
+    id: i32,
+    name: String,
+    prefix: String,
+    scopes: Option<Vec<String>>,
+    expires_at: Option<DateTimeUtc>,
+    last_used_at: Option<DateTimeUtc>,
+    created_at: DateTimeUtc,
+}
+
+impl From<&api_key::Model> for ResApiKey {
+    fn from(k: &api_key::Model) -> Self {
+        Self {
+            id: k.id,
+            name: k.name.to_owned(),
+            prefix: k.prefix.to_owned(),
+            scopes: k
+                .scopes
+                .as_ref()
+                .map(|s| s.split_whitespace().map(String::from).collect()),
+            expires_at: k.expires_at,
+            last_used_at: k.last_used_at,
+            created_at: k.created_at,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ResApiKeyList {
+    total: usize,
+    api_keys: Vec<ResApiKey>,
+}
+
+/// A newly created key. This is the only time the full key is shown.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ResCreatedApiKey {
+    key: String,
+    #[serde(flatten)]
+    api_key: ResApiKey,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ReqApiKey {
+    name: String,
+    scopes: Option<Vec<String>>,
+    expires_in_days: Option<u64>,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ReqUpdateApiKey {
+    name: String,
+    scopes: Option<Vec<String>>,
+}
+
+fn validate_scopes(scopes: &Option<Vec<String>>) -> Result<Option<String>, ErrorResponse> {
+    scopes
+        .as_deref()
+        .map(join_scopes)
+        .transpose()
+        .map_err(|err| ErrorResponse((Status::BadRequest, err)))
+}
+
+/// The longest an API key may be issued for.
+const MAX_EXPIRES_IN_DAYS: u64 = 3650;
+
+fn expires_at(expires_in_days: Option<u64>) -> Result<Option<DateTimeUtc>, ErrorResponse> {
+    let Some(days) = expires_in_days else {
+        return Ok(None);
+    };
+
+    let invalid = || {
+        ErrorResponse((
+            Status::BadRequest,
+            format!(
+                "expires_in_days must be between 1 and {}",
+                MAX_EXPIRES_IN_DAYS
+            ),
+        ))
+    };
+
+    if !(1..=MAX_EXPIRES_IN_DAYS).contains(&days) {
+        return Err(invalid());
+    }
+
+    days.checked_mul(24 * 60 * 60)
+        .and_then(|secs| SystemTime::now().checked_add(Duration::from_secs(secs)))
+        .map(|at| Some(DateTimeUtc::from(at)))
+        .ok_or_else(invalid)
+}
+
+async fn find_own(
+    db: &DatabaseConnection,
+    user: &AuthenticatedUser,
+    id: i32,
+) -> Result<api_key::Model, ErrorResponse> {
+    match ApiKey::find_by_id(id)
+        .filter(api_key::Column::UserId.eq(user.id))
+        .one(db)
+        .await?
+    {
+        Some(k) => Ok(k),
+        None => Err(ErrorResponse((
+            Status::NotFound,
+            "No API key with the specified ID.".to_string(),
+        ))),
+    }
+}
+
+#[get("/")]
+pub async fn index(
+    db: &State<DatabaseConnection>,
+    user: AuthenticatedUser,
+) -> Response<Json<ResApiKeyList>> {
+    let db = db as &DatabaseConnection;
+
+    let api_keys = ApiKey::find()
+        .filter(api_key::Column::UserId.eq(user.id))
+        .order_by_desc(api_key::Column::CreatedAt)
+        .all(db)
+        .await?
+        .iter()
+        .map(ResApiKey::from)
+        .collect::<Vec<_>>();
+
+    Ok(SuccessResponse((
+        Status::Ok,
+        Json(ResApiKeyList {
+            total: api_keys.len(),
+            api_keys,
+        }),
+    )))
+}
+
+#[post("/", data = "<req_api_key>")]
+pub async fn create(
+    db: &State<DatabaseConnection>,
+    user: AuthenticatedUser,
+    req_api_key: Json<ReqApiKey>,
+) -> Response<Json<ResCreatedApiKey>> {
+    let db = db as &DatabaseConnection;
+
+    let (key, prefix) = api_keys::generate();
+
+    let api_key = api_key::ActiveModel {
+        user_id: Set(user.id),
+        name: Set(req_api_key.name.to_owned()),
+        prefix: Set(prefix),
+        key_hash: Set(hash_token(&key)),
+        scopes: Set(validate_scopes(&req_api_key.scopes)?),
+        expires_at: Set(expires_at(req_api_key.expires_in_days)?),
+        ..Default::default()
+    };
+
+    let api_key = api_key.insert(db).await?;
+
+    audit::record(
+        db,
+        user.id,
+        "api_key_created",
+        Some(api_key.prefix.to_owned()),
+    )
+    .await?;
+
+    Ok(SuccessResponse((
+        Status::Created,
+        Json(ResCreatedApiKey {
+            key,
+            api_key: ResApiKey::from(&api_key),
+        }),
+    )))
+}
+
+#[get("/<id>")]
+pub async fn show(
+    db: &State<DatabaseConnection>,
+    user: AuthenticatedUser,
+    id: i32,
+) -> Response<Json<ResApiKey>> {
+    let db = db as &DatabaseConnection;
+
+    let api_key = find_own(db, &user, id).await?;
+
+    Ok(SuccessResponse((
+        Status::Ok,
+        Json(ResApiKey::from(&api_key)),
+    )))
+}
+
+#[put("/<id>", data = "<req_api_key>")]
+pub async fn update(
+    db: &State<DatabaseConnection>,
+    user: AuthenticatedUser,
+    id: i32,
+    req_api_key: Json<ReqUpdateApiKey>,
+) -> Response<Json<ResApiKey>> {
+    let db = db as &DatabaseConnection;
+
+    let mut api_key: api_key::ActiveModel = find_own(db, &user, id).await?.into();
+
+    api_key.name = Set(req_api_key.name.to_owned());
+    api_key.scopes = Set(validate_scopes(&req_api_key.scopes)?);
+
+    let api_key = api_key.update(db).await?;
+
+    Ok(SuccessResponse((
+        Status::Ok,
+        Json(ResApiKey::from(&api_key)),
+    )))
+}
+
+#[delete("/<id>")]
+pub async fn delete(
+    db: &State<DatabaseConnection>,
+    user: AuthenticatedUser,
+    id: i32,
+) -> Response<String> {
+    let db = db as &DatabaseConnection;
+
+    let api_key = find_own(db, &user, id).await?;
+    let prefix = api_key.prefix.to_owned();
+
+    api_key.delete(db).await?;
+
+    audit::record(db, user.id, "api_key_deleted", Some(prefix)).await?;
+
+    Ok(SuccessResponse((
+        Status::Ok,
+        "API key deleted.".to_string(),
+    )))
+}
@@ -1,22 +1,34 @@
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
 use super::{ErrorResponse, Response, SuccessResponse};
 use crate::{
-    AppConfig,
-    auth::{AuthenticatedUser, Claims},
+    audit,
+    auth::{
+        AuthenticatedUser, hash_token, issue_token,
+        keys::KeyStore,
+        random_token, remove_auth_cookies,
+        sessions::{self, ClientInfo},
+        set_auth_cookies,
+    },
+    config::AppConfig,
+    db::transaction::Transaction,
     entities::{prelude::*, user},
+    mail,
+    metrics::Metrics,
 };
 use bcrypt::{DEFAULT_COST, hash, verify};
-use jsonwebtoken::{EncodingKey, Header, encode};
 use rocket::{
     State,
-    http::Status,
+    http::{CookieJar, Status},
     serde::{
         Deserialize, Serialize,
-        json::{Json, json},
+        json::{Json, Value, json},
     },
 };
-use sea_orm::{ActiveValue::Set, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+use sea_orm::prelude::DateTimeUtc;
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::Set, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter,
+};
 
 #[derive(Deserialize)]
 #[serde(crate = "rocket::serde")]
@@ -28,25 +40,26 @@ pub struct ReqSignIn {
 #[derive(Serialize, Deserialize, Responder)]
 #[serde(crate = "rocket::serde")]
 pub struct ResSignIn {
-    token: String,
+    pub token: String,
 }
 
 #[post("/sign-in", data = "<req_sign_in>")]
 pub async fn sign_in(
     db: &State<DatabaseConnection>,
     config: &State<AppConfig>,
+    keys: &State<KeyStore>,
+    metrics: &State<Metrics>,
+    cookies: &CookieJar<'_>,
+    client: ClientInfo,
     req_sign_in: Json<ReqSignIn>,
 ) -> Response<String> {
     let db = db as &DatabaseConnection;
     let config = config as &AppConfig;
 
-    let u: user::Model = match User::find()
-        .filter(user::Column::Email.eq(req_sign_in.email.clone()))
-        .one(db)
-        .await?
-    {
+    let u: user::Model = match User::find_by_email(&req_sign_in.email).one(db).await? {
         Some(u) => u,
         None => {
+            metrics.sign_in(false);
             return Err(ErrorResponse((
                 Status::Unauthorized,
                 "Invalid credentials".to_string(),
@@ -55,28 +68,30 @@ pub async fn sign_in(
     };
 
     if !verify(&req_sign_in.password, &u.password).unwrap() {
+        metrics.sign_in(false);
         return Err(ErrorResponse((
             Status::Unauthorized,
             "Invalid credentials".to_string(),
         )));
     }
 
-    let claims = Claims {
-        sub: u.id,
-        role: "user".to_string(),
-        exp: SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap()
-            .as_secs()
-            + 4 * 60 * 60,
-    };
+    if u.disabled_at.is_some() {
+        metrics.sign_in(false);
+        return Err(ErrorResponse((
+            Status::Forbidden,
+            "This account has been disabled.".to_string(),
+        )));
+    }
 
-    let token = encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(config.jwt_secret.as_bytes()),
-    )
-    .unwrap();
+    audit::record(db, u.id, "sign_in", None).await?;
+    metrics.sign_in(true);
+
+    let session = sessions::start(db, u.id, &client).await?;
+    let token = issue_token(&u, &session, keys);
+
+    if config.auth_cookie {
+        set_auth_cookies(cookies, token.to_owned(), config);
+    }
 
     Ok(SuccessResponse((
         Status::Ok,
@@ -84,6 +99,31 @@ pub async fn sign_in(
     )))
 }
 
+/// Ends the current session, which revokes its token, and clears the auth
+/// cookies of browser clients.
+#[post("/sign-out")]
+pub async fn sign_out(
+    db: &State<DatabaseConnection>,
+    cookies: &CookieJar<'_>,
+    user: Option<AuthenticatedUser>,
+) -> Response<String> {
+    let db = db as &DatabaseConnection;
+
+    if let Some(session_id) = user.and_then(|user| user.session_id) {
+        Session::delete_by_id(session_id).exec(db).await?;
+    }
+
+    remove_auth_cookies(cookies);
+
+    Ok(SuccessResponse((Status::Ok, "Signed out.".to_string())))
+}
+
+/// Publishes the public signing keys so other services can verify tokens.
+#[get("/jwks.json")]
+pub async fn jwks(keys: &State<KeyStore>) -> Response<Json<Value>> {
+    Ok(SuccessResponse((Status::Ok, Json(keys.jwks()))))
+}
+
 #[derive(Deserialize)]
 #[serde(crate = "rocket::serde")]
 pub struct ReqSignUp {
@@ -93,41 +133,36 @@ pub struct ReqSignUp {
     lastname: String,
 }
 
-#[derive(Serialize, Deserialize, Responder)]
-#[serde(crate = "rocket::serde")]
-pub struct ResSignUp {
-    token: String,
-}
-
 #[post("/sign-up", data = "<req_sign_up>")]
-pub async fn sign_up(
-    db: &State<DatabaseConnection>,
-    req_sign_up: Json<ReqSignUp>,
-) -> Response<String> {
-    let db = db as &DatabaseConnection;
+pub async fn sign_up(txn: Transaction<'_>, req_sign_up: Json<ReqSignUp>) -> Response<String> {
+    // Hashed before the transaction begins, which would otherwise hold a
+    // connection for as long.
+    let password = hash(&req_sign_up.password, DEFAULT_COST).unwrap();
+    let txn = txn.get().await?;
 
-    if User::find()
-        .filter(user::Column::Email.eq(&req_sign_up.email))
-        .one(db)
+    if User::find_by_email(&req_sign_up.email)
+        .one(&*txn)
         .await?
         .is_some()
     {
         return Err(super::ErrorResponse((
-            Status::InternalServerError,
+            Status::Conflict,
             "An account exists with that email address.".to_string(),
         )));
     }
 
-    User::insert(user::ActiveModel {
+    let u = user::ActiveModel {
         email: Set(req_sign_up.email.to_owned()),
-        password: Set(hash(req_sign_up.password.to_owned(), DEFAULT_COST).unwrap()),
+        password: Set(password),
         firstname: Set(req_sign_up.firstname.to_owned()),
         lastname: Set(req_sign_up.lastname.to_owned()),
         ..Default::default()
-    })
-    .exec(db)
+    }
+    .insert(&*txn)
     .await?;
 
+    audit::record(&*txn, u.id, "sign_up", None).await?;
+
     Ok(SuccessResponse((
         Status::Created,
         "Account created!".to_string(),
@@ -139,23 +174,330 @@ pub async fn sign_up(
 pub struct ResMe {
     id: i32,
     email: String,
+    pending_email: Option<String>,
     firstname: String,
     lastname: String,
 }
 
+impl From<&user::Model> for ResMe {
+    fn from(u: &user::Model) -> Self {
+        Self {
+            id: u.id,
+            email: u.email.to_owned(),
+            pending_email: u.pending_email.to_owned(),
+            firstname: u.firstname.to_owned(),
+            lastname: u.lastname.to_owned(),
+        }
+    }
+}
+
 #[get("/me")]
 pub async fn me(db: &State<DatabaseConnection>, user: AuthenticatedUser) -> Response<Json<ResMe>> {
     let db = db as &DatabaseConnection;
 
     let u = User::find_by_id(user.id).one(db).await?.unwrap();
 
+    Ok(SuccessResponse((Status::Ok, Json(ResMe::from(&u)))))
+}
+
+const EMAIL_VERIFICATION_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ReqUpdateMe {
+    email: String,
+    firstname: String,
+    lastname: String,
+}
+
+#[put("/me", data = "<req_update_me>")]
+pub async fn update_me(
+    db: &State<DatabaseConnection>,
+    user: AuthenticatedUser,
+    req_update_me: Json<ReqUpdateMe>,
+) -> Response<Json<ResMe>> {
+    let db = db as &DatabaseConnection;
+
+    let current = User::find_by_id(user.id).one(db).await?.unwrap();
+    let email_changed = current.email != req_update_me.email;
+
+    if email_changed
+        && User::find_by_email(&req_update_me.email)
+            .filter(user::Column::Id.ne(user.id))
+            .one(db)
+            .await?
+            .is_some()
+    {
+        return Err(ErrorResponse((
+            Status::Conflict,
+            "An account exists with that email address.".to_string(),
+        )));
+    }
+
+    let mut u: user::ActiveModel = current.into();
+
+    u.firstname = Set(req_update_me.firstname.to_owned());
+    u.lastname = Set(req_update_me.lastname.to_owned());
+
+    // A new email address only replaces the current one once the owner has
+    // proven access to it, see `verify_email`.
+    if email_changed {
+        let token = random_token(32);
+
+        u.pending_email = Set(Some(req_update_me.email.to_owned()));
+        u.email_verification_token = Set(Some(hash_token(&token)));
+        u.email_verification_expires_at = Set(Some(DateTimeUtc::from(
+            SystemTime::now() + EMAIL_VERIFICATION_TTL,
+        )));
+
+        audit::record(
+            db,
+            user.id,
+            "email_change_requested",
+            Some(req_update_me.email.to_owned()),
+        )
+        .await?;
+
+        mail::send(
+            &req_update_me.email,
+            "Confirm your new email address",
+            &format!(
+                "Use this token to confirm your new email address: {}",
+                token
+            ),
+        );
+    }
+
+    let u = u.update(db).await?;
+
+    audit::record(db, u.id, "profile_updated", None).await?;
+
+    Ok(SuccessResponse((Status::Ok, Json(ResMe::from(&u)))))
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ReqVerifyEmail {
+    token: String,
+}
+
+#[post("/verify-email", data = "<req_verify_email>")]
+pub async fn verify_email(
+    db: &State<DatabaseConnection>,
+    req_verify_email: Json<ReqVerifyEmail>,
+) -> Response<Json<ResMe>> {
+    let db = db as &DatabaseConnection;
+
+    let u = match User::find()
+        .filter(user::Column::EmailVerificationToken.eq(hash_token(&req_verify_email.token)))
+        .one(db)
+        .await?
+    {
+        Some(u) => u,
+        None => {
+            return Err(ErrorResponse((
+                Status::BadRequest,
+                "Invalid verification token.".to_string(),
+            )));
+        }
+    };
+
+    let now = DateTimeUtc::from(SystemTime::now());
+
+    if u.email_verification_expires_at
+        .is_none_or(|expires| expires < now)
+    {
+        return Err(ErrorResponse((
+            Status::BadRequest,
+            "The verification token has expired.".to_string(),
+        )));
+    }
+
+    let pending_email = u.pending_email.clone().unwrap_or_default();
+
+    if User::find_by_email(&pending_email)
+        .filter(user::Column::Id.ne(u.id))
+        .one(db)
+        .await?
+        .is_some()
+    {
+        return Err(ErrorResponse((
+            Status::Conflict,
+            "An account exists with that email address.".to_string(),
+        )));
+    }
+
+    let mut u: user::ActiveModel = u.into();
+
+    u.email = Set(pending_email);
+    u.pending_email = Set(None);
+    u.email_verification_token = Set(None);
+    u.email_verification_expires_at = Set(None);
+
+    let u = u.update(db).await?;
+
+    audit::record(db, u.id, "email_changed", Some(u.email.to_owned())).await?;
+
+    Ok(SuccessResponse((Status::Ok, Json(ResMe::from(&u)))))
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ReqChangePassword {
+    current_password: String,
+    new_password: String,
+}
+
+/// Changes the password, bumps the user's token version and ends every
+/// session. A fresh session and token are returned for the caller.
+#[post("/me/password", data = "<req_change_password>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn change_password(
+    db: &State<DatabaseConnection>,
+    config: &State<AppConfig>,
+    keys: &State<KeyStore>,
+    cookies: &CookieJar<'_>,
+    client: ClientInfo,
+    user: AuthenticatedUser,
+    req_change_password: Json<ReqChangePassword>,
+) -> Response<Json<ResSignIn>> {
+    let db = db as &DatabaseConnection;
+    let config = config as &AppConfig;
+
+    let u = User::find_by_id(user.id).one(db).await?.unwrap();
+
+    if !verify(&req_change_password.current_password, &u.password).unwrap() {
+        return Err(ErrorResponse((
+            Status::Unauthorized,
+            "Invalid credentials".to_string(),
+        )));
+    }
+
+    let token_version = u.token_version + 1;
+    let mut u: user::ActiveModel = u.into();
+
+    u.password = Set(hash(&req_change_password.new_password, DEFAULT_COST).unwrap());
+    u.token_version = Set(token_version);
+
+    let u = u.update(db).await?;
+
+    audit::record(db, u.id, "password_changed", None).await?;
+
+    sessions::end_all(db, u.id, None).await?;
+
+    let session = sessions::start(db, u.id, &client).await?;
+    let token = issue_token(&u, &session, keys);
+
+    if config.auth_cookie {
+        set_auth_cookies(cookies, token.to_owned(), config);
+    }
+
+    Ok(SuccessResponse((Status::Ok, Json(ResSignIn { token }))))
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ReqResetPassword {
+    token: String,
+    new_password: String,
+}
+
+/// Sets a new password with the token mailed when an admin forced a reset.
+#[post("/reset-password", data = "<req_reset_password>")]
+pub async fn reset_password(
+    db: &State<DatabaseConnection>,
+    req_reset_password: Json<ReqResetPassword>,
+) -> Response<String> {
+    let db = db as &DatabaseConnection;
+
+    let u = match User::find()
+        .filter(user::Column::PasswordResetToken.eq(hash_token(&req_reset_password.token)))
+        .one(db)
+        .await?
+    {
+        Some(u) => u,
+        None => {
+            return Err(ErrorResponse((
+                Status::BadRequest,
+                "Invalid password reset token.".to_string(),
+            )));
+        }
+    };
+
+    let now = DateTimeUtc::from(SystemTime::now());
+
+    if u.password_reset_expires_at
+        .is_none_or(|expires| expires < now)
+    {
+        return Err(ErrorResponse((
+            Status::BadRequest,
+            "The password reset token has expired.".to_string(),
+        )));
+    }
+
+    let mut u: user::ActiveModel = u.into();
+
+    u.password = Set(hash(&req_reset_password.new_password, DEFAULT_COST).unwrap());
+    u.password_reset_token = Set(None);
+    u.password_reset_expires_at = Set(None);
+
+    let u = u.update(db).await?;
+
+    audit::record(db, u.id, "password_reset", None).await?;
+
+    Ok(SuccessResponse((
+        Status::Ok,
+        "Password updated, please sign in again.".to_string(),
+    )))
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ReqDeleteMe {
+    password: String,
+}
+
+/// Deletes the account by anonymizing the user row. Authors and books the
+/// user created stay in the catalogue, attributed to the anonymized row.
+#[delete("/me", data = "<req_delete_me>")]
+pub async fn delete_me(
+    db: &State<DatabaseConnection>,
+    user: AuthenticatedUser,
+    req_delete_me: Json<ReqDeleteMe>,
+) -> Response<String> {
+    let db = db as &DatabaseConnection;
+
+    let u = User::find_by_id(user.id).one(db).await?.unwrap();
+
+    if !verify(&req_delete_me.password, &u.password).unwrap() {
+        return Err(ErrorResponse((
+            Status::Unauthorized,
+            "Invalid credentials".to_string(),
+        )));
+    }
+
+    let now = DateTimeUtc::from(SystemTime::now());
+    let token_version = u.token_version + 1;
+    let mut u: user::ActiveModel = u.into();
+
+    u.email = Set(format!("deleted-user-{}@deleted.invalid", user.id));
+    u.password = Set(hash(random_token(32), DEFAULT_COST).unwrap());
+    u.firstname = Set("Deleted".to_string());
+    u.lastname = Set("User".to_string());
+    u.pending_email = Set(None);
+    u.email_verification_token = Set(None);
+    u.email_verification_expires_at = Set(None);
+    u.token_version = Set(token_version);
+    u.deleted_at = Set(Some(now));
+
+    u.update(db).await?;
+
+    sessions::end_all(db, user.id, None).await?;
+
+    audit::record(db, user.id, "account_deleted", None).await?;
+
     Ok(SuccessResponse((
         Status::Ok,
-        Json(ResMe {
-            id: u.id,
-            email: u.email,
-            firstname: u.firstname,
-            lastname: u.lastname,
-        }),
+        "Account deleted.".to_string(),
     )))
 }
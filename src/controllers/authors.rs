@@ -1,8 +1,8 @@
-use std::time::SystemTime;
-
 use super::books::{ResBook, ResBookList};
 use super::{ErrorResponse, Response, SuccessResponse};
 use crate::auth::AuthenticatedUser;
+use crate::db::replicas::ReadConnection;
+use crate::db::transaction::Transaction;
 use crate::entities::{author, book, prelude::*};
 use rocket::http::Status;
 use rocket::serde::Deserialize;
@@ -12,7 +12,10 @@ use rocket::{
 };
 use sea_orm::ActiveValue::Set;
 use sea_orm::prelude::DateTimeUtc;
-use sea_orm::{ActiveModelTrait, DatabaseConnection, EntityTrait, ModelTrait, QueryOrder};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, ModelTrait, QueryFilter,
+    QueryOrder,
+};
 
 #[derive(Serialize)]
 #[serde(crate = "rocket::serde")]
@@ -21,6 +24,9 @@ pub struct ResAuthor {
     firstname: String,
     lastname: String,
     bio: String,
+    /// RFC 3339, in UTC.
+    created_at: DateTimeUtc,
+    updated_at: DateTimeUtc,
 }
 
 #[derive(Deserialize)]
@@ -34,23 +40,23 @@ pub struct ReqAuthor {
 #[derive(Serialize)]
 #[serde(crate = "rocket::serde")]
 pub struct ResAuthorList {
-    total: usize,
-    authors: Vec<ResAuthor>,
+    pub total: usize,
+    pub authors: Vec<ResAuthor>,
 }
 
 #[get("/")]
 pub async fn index(
-    db: &State<DatabaseConnection>,
+    db: ReadConnection<'_>,
     _user: AuthenticatedUser,
 ) -> Response<Json<ResAuthorList>> {
-    let db = db as &DatabaseConnection;
+    let db = &*db as &DatabaseConnection;
 
     let authors = Author::find()
         .order_by_desc(author::Column::UpdatedAt)
         .all(db)
         .await?
         .iter()
-        .map(|author| ResAuthor::from(author))
+        .map(ResAuthor::from)
         .collect::<Vec<_>>();
 
     Ok(SuccessResponse((
@@ -88,11 +94,11 @@ pub async fn create(
 
 #[get("/<id>")]
 pub async fn show(
-    db: &State<DatabaseConnection>,
+    db: ReadConnection<'_>,
     _user: AuthenticatedUser,
     id: i32,
 ) -> Response<Json<ResAuthor>> {
-    let db = db as &DatabaseConnection;
+    let db = &*db as &DatabaseConnection;
 
     let author = Author::find_by_id(id).one(db).await?;
 
@@ -135,8 +141,6 @@ pub async fn update(
     author.lastname = Set(req_author.lastname.to_owned());
     author.bio = Set(req_author.bio.to_owned());
 
-    author.updated_at = Set(DateTimeUtc::from(SystemTime::now()).naive_local());
-
     let author = author.update(db).await?;
 
     Ok(SuccessResponse((
@@ -146,14 +150,10 @@ pub async fn update(
 }
 
 #[delete("/<id>")]
-pub async fn delete(
-    db: &State<DatabaseConnection>,
-    _user: AuthenticatedUser,
-    id: i32,
-) -> Response<String> {
-    let db = db as &DatabaseConnection;
+pub async fn delete(txn: Transaction<'_>, _user: AuthenticatedUser, id: i32) -> Response<String> {
+    let txn = txn.get().await?;
 
-    let author = match Author::find_by_id(id).one(db).await? {
+    let author = match Author::find_by_id(id).one(&*txn).await? {
         Some(a) => a,
         None => {
             return Err(ErrorResponse((
@@ -163,7 +163,19 @@ pub async fn delete(
         }
     };
 
-    author.delete(db).await?;
+    if Book::find()
+        .filter(book::Column::AuthorId.eq(author.id))
+        .one(&*txn)
+        .await?
+        .is_some()
+    {
+        return Err(ErrorResponse((
+            Status::Conflict,
+            "The author still has books.".to_string(),
+        )));
+    }
+
+    author.delete(&*txn).await?;
 
     Ok(SuccessResponse((Status::Ok, "Author deleted.".to_string())))
 }
@@ -175,17 +187,19 @@ impl From<&author::Model> for ResAuthor {
             firstname: a.firstname.to_owned(),
             lastname: a.lastname.to_owned(),
             bio: a.bio.to_owned(),
+            created_at: a.created_at,
+            updated_at: a.updated_at,
         }
     }
 }
 
 #[get("/<id>/books")]
 pub async fn get_books(
-    db: &State<DatabaseConnection>,
+    db: ReadConnection<'_>,
     _user: AuthenticatedUser,
     id: i32,
 ) -> Response<Json<ResBookList>> {
-    let db = db as &DatabaseConnection;
+    let db = &*db as &DatabaseConnection;
 
     let author = match Author::find_by_id(id).one(db).await? {
         Some(a) => a,
@@ -203,16 +217,7 @@ pub async fn get_books(
         Status::Ok,
         Json(ResBookList {
             total: books.len(),
-            books: books
-                .iter()
-                .map(|b| ResBook {
-                    id: b.id,
-                    author_id: b.author_id,
-                    title: b.title.to_owned(),
-                    year: b.year.to_owned(),
-                    cover: b.cover.to_owned(),
-                })
-                .collect::<Vec<_>>(),
+            books: books.iter().map(ResBook::from).collect::<Vec<_>>(),
         }),
     )))
 }
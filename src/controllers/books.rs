@@ -1,6 +1,5 @@
-use std::time::SystemTime;
-
 use crate::auth::AuthenticatedUser;
+use crate::db::replicas::ReadConnection;
 use crate::entities::{book, prelude::*};
 use rocket::http::Status;
 use rocket::serde::Deserialize;
@@ -23,6 +22,34 @@ pub struct ReqBook {
     cover: String,
 }
 
+/// The same limits as the checks on the `book` table, caught here to answer
+/// with a message rather than a database error.
+fn validate_book(req_book: &ReqBook) -> Result<(), ErrorResponse> {
+    let limits = [
+        ("title", &req_book.title, 255),
+        ("year", &req_book.year, 32),
+        ("cover", &req_book.cover, 2048),
+    ];
+
+    if req_book.title.trim().is_empty() {
+        return Err(ErrorResponse((
+            Status::BadRequest,
+            "The title cannot be empty.".to_string(),
+        )));
+    }
+
+    for (field, value, max) in limits {
+        if value.chars().count() > max {
+            return Err(ErrorResponse((
+                Status::BadRequest,
+                format!("The {} cannot be longer than {} characters.", field, max),
+            )));
+        }
+    }
+
+    Ok(())
+}
+
 #[derive(Serialize)]
 #[serde(crate = "rocket::serde")]
 pub struct ResBook {
@@ -31,6 +58,9 @@ pub struct ResBook {
     pub title: String,
     pub year: String,
     pub cover: String,
+    /// RFC 3339, in UTC.
+    pub created_at: DateTimeUtc,
+    pub updated_at: DateTimeUtc,
 }
 
 #[derive(Serialize)]
@@ -42,23 +72,17 @@ pub struct ResBookList {
 
 #[get("/")]
 pub async fn index(
-    db: &State<DatabaseConnection>,
+    db: ReadConnection<'_>,
     _user: AuthenticatedUser,
 ) -> Response<Json<ResBookList>> {
-    let db = db as &DatabaseConnection;
+    let db = &*db as &DatabaseConnection;
 
     let books = Book::find()
         .order_by_desc(book::Column::UpdatedAt)
         .all(db)
         .await?
         .iter()
-        .map(|book| ResBook {
-            id: book.id,
-            author_id: book.author_id,
-            title: book.title.to_owned(),
-            year: book.year.to_owned(),
-            cover: book.cover.to_owned(),
-        })
+        .map(ResBook::from)
         .collect::<Vec<_>>();
 
     Ok(SuccessResponse((
@@ -78,6 +102,8 @@ pub async fn create(
 ) -> Response<Json<ResBook>> {
     let db = db as &DatabaseConnection;
 
+    validate_book(&req_book)?;
+
     let book = book::ActiveModel {
         user_id: Set(user.id),
         author_id: Set(req_book.author_id.to_owned()),
@@ -91,23 +117,17 @@ pub async fn create(
 
     Ok(SuccessResponse((
         Status::Created,
-        Json(ResBook {
-            id: book.id,
-            author_id: book.author_id,
-            title: book.title,
-            year: book.year,
-            cover: book.cover,
-        }),
+        Json(ResBook::from(&book)),
     )))
 }
 
 #[get("/<id>")]
 pub async fn show(
-    db: &State<DatabaseConnection>,
+    db: ReadConnection<'_>,
     _user: AuthenticatedUser,
     id: i32,
 ) -> Response<Json<ResBook>> {
-    let db = db as &DatabaseConnection;
+    let db = &*db as &DatabaseConnection;
 
     let book = Book::find_by_id(id).one(db).await?;
 
@@ -123,13 +143,7 @@ pub async fn show(
 
     Ok(SuccessResponse((
         Status::Created,
-        Json(ResBook {
-            id: book.id,
-            author_id: book.author_id,
-            title: book.title,
-            year: book.year,
-            cover: book.cover,
-        }),
+        Json(ResBook::from(&book)),
     )))
 }
 
@@ -142,6 +156,8 @@ pub async fn update(
 ) -> Response<Json<ResBook>> {
     let db = db as &DatabaseConnection;
 
+    validate_book(&req_book)?;
+
     let mut book: book::ActiveModel = match Book::find_by_id(id).one(db).await? {
         Some(b) => b.into(),
         None => {
@@ -157,20 +173,9 @@ pub async fn update(
     book.year = Set(req_book.year.to_owned());
     book.cover = Set(req_book.cover.to_owned());
 
-    book.updated_at = Set(DateTimeUtc::from(SystemTime::now()).naive_local());
-
     let book = book.update(db).await?;
 
-    Ok(SuccessResponse((
-        Status::Ok,
-        Json(ResBook {
-            id: book.id,
-            author_id: book.author_id,
-            title: book.title.to_owned(),
-            year: book.year.to_owned(),
-            cover: book.cover.to_owned(),
-        }),
-    )))
+    Ok(SuccessResponse((Status::Ok, Json(ResBook::from(&book)))))
 }
 
 #[delete("/<id>")]
@@ -195,3 +200,17 @@ pub async fn delete(
 
     Ok(SuccessResponse((Status::Ok, "Book deleted".to_string())))
 }
+
+impl From<&book::Model> for ResBook {
+    fn from(b: &book::Model) -> Self {
+        Self {
+            id: b.id,
+            author_id: b.author_id,
+            title: b.title.to_owned(),
+            year: b.year.to_owned(),
+            cover: b.cover.to_owned(),
+            created_at: b.created_at,
+            updated_at: b.updated_at,
+        }
+    }
+}
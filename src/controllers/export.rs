@@ -0,0 +1,187 @@
+use std::path::Path;
+
+use rocket::{
+    State,
+    http::{Header, Status},
+    serde::{Serialize, json::Json},
+};
+use sea_orm::DatabaseConnection;
+
+use super::{ErrorResponse, Response, SuccessResponse};
+use crate::{
+    auth::AuthenticatedUser,
+    config::AppConfig,
+    export::{self, ExportJobs, ExportStatus},
+};
+
+#[derive(Responder)]
+#[response(content_type = "application/zip")]
+pub struct ResExportFile(Vec<u8>, Header<'static>);
+
+impl ResExportFile {
+    fn new(bytes: Vec<u8>) -> Self {
+        Self(
+            bytes,
+            Header::new(
+                "Content-Disposition",
+                "attachment; filename=\"bookstore-export.zip\"",
+            ),
+        )
+    }
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ResExportJob {
+    job_id: String,
+    status: &'static str,
+    download_url: String,
+}
+
+impl ResExportJob {
+    fn new(job_id: &str, status: &'static str) -> Self {
+        Self {
+            job_id: job_id.to_string(),
+            status,
+            download_url: format!("/auth/me/export/{}", job_id),
+        }
+    }
+}
+
+#[derive(Responder)]
+pub enum ResExport {
+    File(ResExportFile),
+    Job(Json<ResExportJob>),
+}
+
+/// Exports everything stored about the user as a ZIP archive. Small exports
+/// are returned directly; larger ones are built in the background and can be
+/// downloaded from the returned `download_url` once ready, until they
+/// expire after `export_ttl_secs`. A user has one background export at a
+/// time: asking again returns the one pending or ready.
+#[get("/me/export")]
+pub async fn create_export(
+    db: &State<DatabaseConnection>,
+    config: &State<AppConfig>,
+    jobs: &State<ExportJobs>,
+    user: AuthenticatedUser,
+) -> Response<ResExport> {
+    let db = db as &DatabaseConnection;
+    let config = config as &AppConfig;
+
+    jobs.prune().await;
+
+    if export::count_rows(db, user.id).await? <= config.export_inline_limit {
+        let bundle = export::build_bundle(db, user.id).await?;
+        let bytes = export::to_zip(&bundle)
+            .map_err(|err| ErrorResponse((Status::InternalServerError, err)))?;
+
+        return Ok(SuccessResponse((
+            Status::Ok,
+            ResExport::File(ResExportFile::new(bytes)),
+        )));
+    }
+
+    let (job_id, new) = jobs.start(user.id);
+
+    if !new {
+        return Ok(match jobs.status(&job_id, user.id) {
+            Some(ExportStatus::Ready(_)) => SuccessResponse((
+                Status::Ok,
+                ResExport::Job(Json(ResExportJob::new(&job_id, "ready"))),
+            )),
+            _ => SuccessResponse((
+                Status::Accepted,
+                ResExport::Job(Json(ResExportJob::new(&job_id, "pending"))),
+            )),
+        });
+    }
+
+    let db = db.clone();
+    let jobs = jobs.inner().clone();
+    let path = jobs.path(&job_id);
+    let id = job_id.to_owned();
+
+    rocket::tokio::spawn(async move {
+        let status = match build_export_file(&db, user.id, &path).await {
+            Ok(()) => ExportStatus::Ready(path),
+            Err(err) => {
+                error!("Export job {} failed: {}", id, err);
+                ExportStatus::Failed(err)
+            }
+        };
+
+        jobs.set_status(&id, status).await;
+    });
+
+    Ok(SuccessResponse((
+        Status::Accepted,
+        ResExport::Job(Json(ResExportJob::new(&job_id, "pending"))),
+    )))
+}
+
+async fn build_export_file(
+    db: &DatabaseConnection,
+    user_id: i32,
+    path: &Path,
+) -> Result<(), String> {
+    let bundle = export::build_bundle(db, user_id)
+        .await
+        .map_err(|err| err.to_string())?;
+    let bytes = export::to_zip(&bundle)?;
+
+    if let Some(dir) = path.parent() {
+        rocket::tokio::fs::create_dir_all(dir)
+            .await
+            .map_err(|err| err.to_string())?;
+    }
+
+    rocket::tokio::fs::write(path, bytes)
+        .await
+        .map_err(|err| err.to_string())
+}
+
+#[get("/me/export/<job_id>")]
+pub async fn download_export(
+    jobs: &State<ExportJobs>,
+    user: AuthenticatedUser,
+    job_id: &str,
+) -> Response<ResExport> {
+    jobs.prune().await;
+
+    let path = match jobs.status(job_id, user.id) {
+        Some(ExportStatus::Pending) => None,
+        Some(ExportStatus::Ready(path)) => Some(path),
+        Some(ExportStatus::Failed(err)) => {
+            return Err(ErrorResponse((
+                Status::InternalServerError,
+                format!("The export failed: {}", err),
+            )));
+        }
+        None => {
+            return Err(ErrorResponse((
+                Status::NotFound,
+                "No export with the specified ID.".to_string(),
+            )));
+        }
+    };
+
+    let path = match path {
+        Some(path) => path,
+        None => {
+            return Ok(SuccessResponse((
+                Status::Accepted,
+                ResExport::Job(Json(ResExportJob::new(job_id, "pending"))),
+            )));
+        }
+    };
+
+    let bytes = rocket::tokio::fs::read(&path)
+        .await
+        .map_err(|err| ErrorResponse((Status::InternalServerError, err.to_string())))?;
+
+    Ok(SuccessResponse((
+        Status::Ok,
+        ResExport::File(ResExportFile::new(bytes)),
+    )))
+}
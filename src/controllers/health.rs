@@ -0,0 +1,59 @@
+use rocket::{
+    State,
+    http::Status,
+    serde::{Serialize, json::Json},
+};
+use sea_orm::DatabaseConnection;
+
+use super::{Response, SuccessResponse};
+use crate::health::{CheckReport, HealthChecks};
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ResHealth {
+    status: &'static str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    checks: Vec<CheckReport>,
+}
+
+/// The process is up and serving requests. Dependencies are not checked, so
+/// an outage elsewhere does not get every instance restarted.
+#[get("/live")]
+pub fn live() -> Response<Json<ResHealth>> {
+    Ok(SuccessResponse((
+        Status::Ok,
+        Json(ResHealth {
+            status: "ok",
+            checks: Vec::new(),
+        }),
+    )))
+}
+
+/// Every required dependency is healthy. Answers 503 otherwise, with the
+/// failing checks and their errors in the report. Failing checks that are
+/// not required report the instance as degraded.
+#[get("/ready")]
+pub async fn ready(
+    db: &State<DatabaseConnection>,
+    checks: &State<HealthChecks>,
+) -> Response<Json<ResHealth>> {
+    let db = db as &DatabaseConnection;
+
+    let checks = checks.run(db).await;
+
+    let (status, report_status) = if checks.iter().all(|check| check.healthy) {
+        (Status::Ok, "ok")
+    } else if checks.iter().all(|check| check.healthy || !check.required) {
+        (Status::Ok, "degraded")
+    } else {
+        (Status::ServiceUnavailable, "unavailable")
+    };
+
+    Ok(SuccessResponse((
+        status,
+        Json(ResHealth {
+            status: report_status,
+            checks,
+        }),
+    )))
+}
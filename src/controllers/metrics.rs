@@ -0,0 +1,28 @@
+use rocket::{
+    State,
+    http::{ContentType, Status},
+};
+use sea_orm::DatabaseConnection;
+
+use super::{Response, SuccessResponse};
+use crate::metrics::{Metrics, MetricsAccess};
+
+/// Exposes the metrics in the Prometheus text format.
+#[get("/metrics")]
+pub async fn index(
+    db: &State<DatabaseConnection>,
+    metrics: &State<Metrics>,
+    _access: MetricsAccess,
+) -> Response<(ContentType, String)> {
+    let db = db as &DatabaseConnection;
+
+    let body = metrics.render(db).await?;
+
+    Ok(SuccessResponse((
+        Status::Ok,
+        (
+            ContentType::new("text", "plain").with_params(("version", "0.0.4")),
+            body,
+        ),
+    )))
+}
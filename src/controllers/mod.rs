@@ -1,20 +1,82 @@
-use rocket::http::Status;
-use sea_orm::DbErr;
+use rocket::{
+    Request,
+    http::Status,
+    response::{self, Responder},
+    serde::{Serialize, json::Json},
+};
+use sea_orm::{DbErr, SqlErr};
 
+use crate::fairings::request_id::RequestId;
+
+pub mod admin;
+pub mod api_keys;
 pub mod auth;
 pub mod authors;
 pub mod books;
+pub mod export;
+pub mod health;
+pub mod metrics;
+pub mod oauth;
+pub mod oidc;
+pub mod sessions;
 
 #[derive(Responder)]
 pub struct SuccessResponse<T>(pub (Status, T));
 
-#[derive(Responder)]
 pub struct ErrorResponse((Status, String));
 
 pub type Response<T> = Result<SuccessResponse<T>, ErrorResponse>;
 
+impl ErrorResponse {
+    pub fn new(status: Status, message: &str) -> Self {
+        ErrorResponse((status, message.to_string()))
+    }
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct ResError {
+    error: String,
+    request_id: Option<String>,
+}
+
+/// Errors are sent as JSON carrying the request id, so a report can be
+/// matched with the server logs.
+impl<'r> Responder<'r, 'static> for ErrorResponse {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
+        let (status, error) = self.0;
+
+        (
+            status,
+            Json(ResError {
+                error,
+                request_id: RequestId::of(req),
+            }),
+        )
+            .respond_to(req)
+    }
+}
+
+/// A unique index rejecting a write is the client's conflict, as when two
+/// requests race past the same existence check.
 impl From<DbErr> for ErrorResponse {
     fn from(err: DbErr) -> Self {
+        if let Some(SqlErr::UniqueConstraintViolation(_)) = err.sql_err() {
+            return ErrorResponse((
+                Status::Conflict,
+                "A record with these values exists already.".to_string(),
+            ));
+        }
+
+        tracing::error!(error = %err, "database error");
+
         ErrorResponse((Status::InternalServerError, err.to_string()))
     }
 }
+
+/// Answers failed guards, unknown routes and other errors raised outside
+/// handlers in the same shape as `ErrorResponse`.
+#[catch(default)]
+pub fn default_catcher(status: Status, _req: &Request<'_>) -> ErrorResponse {
+    ErrorResponse((status, status.reason_lossy().to_string()))
+}
@@ -0,0 +1,850 @@
+use std::time::SystemTime;
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use reqwest::Url;
+use rocket::{
+    State,
+    form::Form,
+    http::Status,
+    request::{FromRequest, Outcome, Request},
+    response::{Redirect, content::RawHtml},
+    serde::{Deserialize, Serialize, json::Json},
+};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::Set, ColumnTrait, DatabaseConnection, DbErr, EntityTrait,
+    ModelTrait, QueryFilter, QueryOrder, prelude::DateTimeUtc,
+};
+
+use super::{ErrorResponse, Response, SuccessResponse};
+use crate::{
+    audit,
+    auth::{
+        AuthenticatedUser, SCOPES, decode_token, hash_token, join_scopes,
+        keys::KeyStore,
+        oauth::{
+            AUTHORIZATION_CODE_TTL, AuthorizationRequest, ConsentRequests, find_active_token,
+            generate_client_credentials, issue_access_token, verify_pkce,
+        },
+        random_token,
+    },
+    entities::{oauth_authorization_code, oauth_client, oauth_token, prelude::*},
+};
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ResOAuthClient {
+    id: i32,
+    client_id: String,
+    name: String,
+    redirect_uris: Vec<String>,
+    scopes: Vec<String>,
+    confidential: bool,
+    created_at: DateTimeUtc,
+}
+
+impl From<&oauth_client::Model> for ResOAuthClient {
+    fn from(c: &oauth_client::Model) -> Self {
+        Self {
+            id: c.id,
+            client_id: c.client_id.to_owned(),
+            name: c.name.to_owned(),
+            redirect_uris: c.redirect_uris.split(' ').map(String::from).collect(),
+            scopes: c.scopes.split(' ').map(String::from).collect(),
+            confidential: c.client_secret_hash.is_some(),
+            created_at: c.created_at,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ResOAuthClientList {
+    total: usize,
+    clients: Vec<ResOAuthClient>,
+}
+
+/// A newly registered client. This is the only time the secret is shown.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ResCreatedOAuthClient {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    client_secret: Option<String>,
+    #[serde(flatten)]
+    client: ResOAuthClient,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ReqOAuthClient {
+    name: String,
+    redirect_uris: Vec<String>,
+    scopes: Vec<String>,
+    confidential: Option<bool>,
+}
+
+#[derive(FromForm)]
+pub struct ReqAuthorize {
+    response_type: Option<String>,
+    client_id: Option<String>,
+    redirect_uri: Option<String>,
+    scope: Option<String>,
+    state: Option<String>,
+    code_challenge: Option<String>,
+    code_challenge_method: Option<String>,
+}
+
+#[derive(FromForm)]
+pub struct ReqConsent {
+    ticket: String,
+    decision: String,
+}
+
+#[derive(FromForm)]
+pub struct ReqToken {
+    grant_type: String,
+    code: Option<String>,
+    redirect_uri: Option<String>,
+    code_verifier: Option<String>,
+    scope: Option<String>,
+    client_id: Option<String>,
+    client_secret: Option<String>,
+}
+
+#[derive(FromForm)]
+pub struct ReqTokenOperation {
+    token: String,
+    client_id: Option<String>,
+    client_secret: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ResToken {
+    access_token: String,
+    token_type: &'static str,
+    expires_in: u64,
+    scope: String,
+}
+
+#[derive(Serialize, Default)]
+#[serde(crate = "rocket::serde")]
+pub struct ResIntrospection {
+    active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    client_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sub: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exp: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token_type: Option<&'static str>,
+}
+
+#[derive(Responder)]
+pub enum ResAuthorize {
+    Consent(RawHtml<String>),
+    Redirect(Box<Redirect>),
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ResOAuthError {
+    error: &'static str,
+    error_description: String,
+}
+
+/// An error in the shape RFC 6749 prescribes for the token endpoint.
+#[derive(Responder)]
+pub struct OAuthError((Status, Json<ResOAuthError>));
+
+pub type OAuthResponse<T> = Result<SuccessResponse<T>, OAuthError>;
+
+impl OAuthError {
+    fn new(status: Status, error: &'static str, description: &str) -> Self {
+        OAuthError((
+            status,
+            Json(ResOAuthError {
+                error,
+                error_description: description.to_string(),
+            }),
+        ))
+    }
+}
+
+impl From<DbErr> for OAuthError {
+    fn from(err: DbErr) -> Self {
+        OAuthError::new(
+            Status::InternalServerError,
+            "server_error",
+            &err.to_string(),
+        )
+    }
+}
+
+/// Client credentials sent with HTTP Basic authentication, the preferred way
+/// for confidential clients to authenticate.
+pub struct BasicAuth(Option<(String, String)>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for BasicAuth {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let credentials = req
+            .headers()
+            .get_one("Authorization")
+            .and_then(|value| value.strip_prefix("Basic "))
+            .and_then(|value| STANDARD.decode(value.trim()).ok())
+            .and_then(|value| String::from_utf8(value).ok())
+            .and_then(|value| {
+                value
+                    .split_once(':')
+                    .map(|(id, secret)| (id.to_string(), secret.to_string()))
+            });
+
+        Outcome::Success(BasicAuth(credentials))
+    }
+}
+
+/// Authenticates the client calling the token, introspection or revocation
+/// endpoint. Confidential clients must present their secret, public clients
+/// only identify themselves.
+async fn authenticate_client(
+    db: &DatabaseConnection,
+    basic: &BasicAuth,
+    client_id: Option<&str>,
+    client_secret: Option<&str>,
+) -> Result<oauth_client::Model, OAuthError> {
+    let (client_id, client_secret) = match &basic.0 {
+        Some((id, secret)) => (Some(id.as_str()), Some(secret.as_str())),
+        None => (client_id, client_secret),
+    };
+
+    let invalid_client = || {
+        OAuthError::new(
+            Status::Unauthorized,
+            "invalid_client",
+            "Client authentication failed.",
+        )
+    };
+
+    let client = match client_id {
+        Some(client_id) => OauthClient::find()
+            .filter(oauth_client::Column::ClientId.eq(client_id))
+            .one(db)
+            .await?
+            .ok_or_else(invalid_client)?,
+        None => return Err(invalid_client()),
+    };
+
+    match (&client.client_secret_hash, client_secret) {
+        (Some(secret_hash), Some(secret)) if *secret_hash == hash_token(secret) => Ok(client),
+        (None, None) => Ok(client),
+        _ => Err(invalid_client()),
+    }
+}
+
+/// Resolves the scopes of a request: the client's registered scopes when
+/// none are requested, otherwise the requested ones if the client may ask
+/// for all of them.
+fn resolve_scopes(client: &oauth_client::Model, requested: Option<&str>) -> Option<String> {
+    let allowed = client.scopes.split(' ').collect::<Vec<_>>();
+
+    match requested {
+        None => Some(client.scopes.to_owned()),
+        Some(requested) => {
+            let scopes = requested.split_whitespace().collect::<Vec<_>>();
+
+            (!scopes.is_empty() && scopes.iter().all(|scope| allowed.contains(scope)))
+                .then(|| scopes.join(" "))
+        }
+    }
+}
+
+fn validate_redirect_uri(uri: &str) -> Result<(), String> {
+    match Url::parse(uri) {
+        Ok(url) if url.fragment().is_none() && !uri.contains(char::is_whitespace) => Ok(()),
+        _ => Err(format!("Invalid redirect URI `{}`", uri)),
+    }
+}
+
+/// Sends the user back to the client with `params` and the client's state.
+fn redirect_to_client(
+    redirect_uri: &str,
+    params: &[(&str, &str)],
+    state: Option<&str>,
+) -> Redirect {
+    // Registered redirect URIs were validated when the client was created.
+    let mut url = Url::parse(redirect_uri).unwrap();
+
+    url.query_pairs_mut().extend_pairs(params);
+
+    if let Some(state) = state {
+        url.query_pairs_mut().append_pair("state", state);
+    }
+
+    Redirect::to(url.to_string())
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+fn consent_page(client: &oauth_client::Model, scopes: &str, ticket: &str) -> String {
+    let scopes = scopes
+        .split(' ')
+        .filter_map(|scope| SCOPES.iter().find(|(s, _)| *s == scope))
+        .map(|(_, description)| format!("<li>{}</li>", escape_html(description)))
+        .collect::<String>();
+
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head><meta charset="utf-8"><title>Authorize {name}</title></head>
+<body>
+<h1>{name} wants to access your bookstore account</h1>
+<p>It will be able to:</p>
+<ul>{scopes}</ul>
+<form method="post" action="/oauth/authorize">
+<input type="hidden" name="ticket" value="{ticket}">
+<button type="submit" name="decision" value="allow">Allow</button>
+<button type="submit" name="decision" value="deny">Deny</button>
+</form>
+</body>
+</html>"#,
+        name = escape_html(&client.name),
+        scopes = scopes,
+        ticket = escape_html(ticket),
+    )
+}
+
+async fn find_own_client(
+    db: &DatabaseConnection,
+    user: &AuthenticatedUser,
+    id: i32,
+) -> Result<oauth_client::Model, ErrorResponse> {
+    match OauthClient::find_by_id(id)
+        .filter(oauth_client::Column::UserId.eq(user.id))
+        .one(db)
+        .await?
+    {
+        Some(c) => Ok(c),
+        None => Err(ErrorResponse((
+            Status::NotFound,
+            "No OAuth client with the specified ID.".to_string(),
+        ))),
+    }
+}
+
+#[get("/clients")]
+pub async fn clients(
+    db: &State<DatabaseConnection>,
+    user: AuthenticatedUser,
+) -> Response<Json<ResOAuthClientList>> {
+    let db = db as &DatabaseConnection;
+
+    let clients = OauthClient::find()
+        .filter(oauth_client::Column::UserId.eq(user.id))
+        .order_by_desc(oauth_client::Column::CreatedAt)
+        .all(db)
+        .await?
+        .iter()
+        .map(ResOAuthClient::from)
+        .collect::<Vec<_>>();
+
+    Ok(SuccessResponse((
+        Status::Ok,
+        Json(ResOAuthClientList {
+            total: clients.len(),
+            clients,
+        }),
+    )))
+}
+
+/// Registers a client app. Confidential clients (the default) get a secret,
+/// public clients have to use PKCE.
+#[post("/clients", data = "<req_client>")]
+pub async fn create_client(
+    db: &State<DatabaseConnection>,
+    user: AuthenticatedUser,
+    req_client: Json<ReqOAuthClient>,
+) -> Response<Json<ResCreatedOAuthClient>> {
+    let db = db as &DatabaseConnection;
+
+    if req_client.redirect_uris.is_empty() {
+        return Err(ErrorResponse((
+            Status::BadRequest,
+            "At least one redirect URI is required.".to_string(),
+        )));
+    }
+
+    for uri in &req_client.redirect_uris {
+        validate_redirect_uri(uri).map_err(|err| ErrorResponse((Status::BadRequest, err)))?;
+    }
+
+    if req_client.scopes.is_empty() {
+        return Err(ErrorResponse((
+            Status::BadRequest,
+            "At least one scope is required.".to_string(),
+        )));
+    }
+
+    let scopes =
+        join_scopes(&req_client.scopes).map_err(|err| ErrorResponse((Status::BadRequest, err)))?;
+
+    let (client_id, client_secret) =
+        generate_client_credentials(req_client.confidential.unwrap_or(true));
+
+    let client = oauth_client::ActiveModel {
+        user_id: Set(user.id),
+        client_id: Set(client_id),
+        client_secret_hash: Set(client_secret.as_deref().map(hash_token)),
+        name: Set(req_client.name.to_owned()),
+        redirect_uris: Set(req_client.redirect_uris.join(" ")),
+        scopes: Set(scopes),
+        ..Default::default()
+    };
+
+    let client = client.insert(db).await?;
+
+    audit::record(
+        db,
+        user.id,
+        "oauth_client_created",
+        Some(client.client_id.to_owned()),
+    )
+    .await?;
+
+    Ok(SuccessResponse((
+        Status::Created,
+        Json(ResCreatedOAuthClient {
+            client_secret,
+            client: ResOAuthClient::from(&client),
+        }),
+    )))
+}
+
+#[get("/clients/<id>")]
+pub async fn show_client(
+    db: &State<DatabaseConnection>,
+    user: AuthenticatedUser,
+    id: i32,
+) -> Response<Json<ResOAuthClient>> {
+    let db = db as &DatabaseConnection;
+
+    let client = find_own_client(db, &user, id).await?;
+
+    Ok(SuccessResponse((
+        Status::Ok,
+        Json(ResOAuthClient::from(&client)),
+    )))
+}
+
+/// Deletes a client, invalidating every code and token issued to it.
+#[delete("/clients/<id>")]
+pub async fn delete_client(
+    db: &State<DatabaseConnection>,
+    user: AuthenticatedUser,
+    id: i32,
+) -> Response<String> {
+    let db = db as &DatabaseConnection;
+
+    let client = find_own_client(db, &user, id).await?;
+    let client_id = client.client_id.to_owned();
+
+    client.delete(db).await?;
+
+    audit::record(db, user.id, "oauth_client_deleted", Some(client_id)).await?;
+
+    Ok(SuccessResponse((
+        Status::Ok,
+        "OAuth client deleted.".to_string(),
+    )))
+}
+
+/// Starts the authorization code flow: validates the client's request and
+/// shows the signed in user the consent screen. Errors the client can handle
+/// are sent back to its redirect URI; an unknown client or redirect URI is
+/// reported to the user instead, since the redirect target cannot be
+/// trusted.
+#[get("/authorize?<req_authorize..>")]
+pub async fn authorize(
+    db: &State<DatabaseConnection>,
+    consent: &State<ConsentRequests>,
+    user: AuthenticatedUser,
+    req_authorize: ReqAuthorize,
+) -> Response<ResAuthorize> {
+    let db = db as &DatabaseConnection;
+
+    let client = match &req_authorize.client_id {
+        Some(client_id) => {
+            OauthClient::find()
+                .filter(oauth_client::Column::ClientId.eq(client_id))
+                .one(db)
+                .await?
+        }
+        None => None,
+    }
+    .ok_or(ErrorResponse((
+        Status::BadRequest,
+        "Unknown OAuth client.".to_string(),
+    )))?;
+
+    let registered = client.redirect_uris.split(' ').collect::<Vec<_>>();
+
+    let redirect_uri = match req_authorize.redirect_uri.as_deref() {
+        Some(uri) if registered.contains(&uri) => uri,
+        None if registered.len() == 1 => registered[0],
+        _ => {
+            return Err(ErrorResponse((
+                Status::BadRequest,
+                "The redirect URI is not registered for this client.".to_string(),
+            )));
+        }
+    };
+
+    let state = req_authorize.state.as_deref();
+    let error = |error: &str, description: &str| {
+        Ok(SuccessResponse((
+            Status::SeeOther,
+            ResAuthorize::Redirect(Box::new(redirect_to_client(
+                redirect_uri,
+                &[("error", error), ("error_description", description)],
+                state,
+            ))),
+        )))
+    };
+
+    if req_authorize.response_type.as_deref() != Some("code") {
+        return error(
+            "unsupported_response_type",
+            "Only the code response type is supported.",
+        );
+    }
+
+    let code_challenge = match (
+        &req_authorize.code_challenge,
+        req_authorize.code_challenge_method.as_deref(),
+    ) {
+        (Some(code_challenge), Some("S256")) => code_challenge,
+        _ => return error("invalid_request", "PKCE with the S256 method is required."),
+    };
+
+    let scopes = match resolve_scopes(&client, req_authorize.scope.as_deref()) {
+        Some(scopes) => scopes,
+        None => return error("invalid_scope", "The client may not request these scopes."),
+    };
+
+    let ticket = consent.insert(AuthorizationRequest::new(
+        client.id,
+        user.id,
+        redirect_uri.to_owned(),
+        req_authorize.redirect_uri.is_some(),
+        scopes.to_owned(),
+        req_authorize.state.to_owned(),
+        code_challenge.to_owned(),
+    ));
+
+    Ok(SuccessResponse((
+        Status::Ok,
+        ResAuthorize::Consent(RawHtml(consent_page(&client, &scopes, &ticket))),
+    )))
+}
+
+/// Receives the user's answer on the consent screen and sends them back to
+/// the client with an authorization code or an `access_denied` error.
+#[post("/authorize", data = "<req_consent>")]
+pub async fn authorize_decision(
+    db: &State<DatabaseConnection>,
+    consent: &State<ConsentRequests>,
+    req_consent: Form<ReqConsent>,
+) -> Response<Redirect> {
+    let db = db as &DatabaseConnection;
+
+    let request = consent.take(&req_consent.ticket).ok_or(ErrorResponse((
+        Status::BadRequest,
+        "The authorization request expired, please start again.".to_string(),
+    )))?;
+
+    let client = OauthClient::find_by_id(request.client_id)
+        .one(db)
+        .await?
+        .ok_or(ErrorResponse((
+            Status::BadRequest,
+            "Unknown OAuth client.".to_string(),
+        )))?;
+
+    if req_consent.decision != "allow" {
+        return Ok(SuccessResponse((
+            Status::SeeOther,
+            redirect_to_client(
+                &request.redirect_uri,
+                &[("error", "access_denied")],
+                request.state.as_deref(),
+            ),
+        )));
+    }
+
+    let code = random_token(32);
+
+    oauth_authorization_code::ActiveModel {
+        code_hash: Set(hash_token(&code)),
+        client_id: Set(client.id),
+        user_id: Set(request.user_id),
+        redirect_uri: Set(request.redirect_uri.to_owned()),
+        redirect_uri_sent: Set(request.redirect_uri_sent),
+        scopes: Set(request.scopes.to_owned()),
+        code_challenge: Set(request.code_challenge.to_owned()),
+        expires_at: Set(DateTimeUtc::from(
+            SystemTime::now() + AUTHORIZATION_CODE_TTL,
+        )),
+        ..Default::default()
+    }
+    .insert(db)
+    .await?;
+
+    audit::record(
+        db,
+        request.user_id,
+        "oauth_consent_granted",
+        Some(format!("{}: {}", client.client_id, request.scopes)),
+    )
+    .await?;
+
+    Ok(SuccessResponse((
+        Status::SeeOther,
+        redirect_to_client(
+            &request.redirect_uri,
+            &[("code", &code)],
+            request.state.as_deref(),
+        ),
+    )))
+}
+
+/// Exchanges an authorization code (with its PKCE verifier) or the client's
+/// own credentials for an access token. Client-credentials tokens act as the
+/// user who registered the client.
+#[post("/token", data = "<req_token>")]
+pub async fn token(
+    db: &State<DatabaseConnection>,
+    keys: &State<KeyStore>,
+    basic: BasicAuth,
+    req_token: Form<ReqToken>,
+) -> OAuthResponse<Json<ResToken>> {
+    let db = db as &DatabaseConnection;
+
+    let client = authenticate_client(
+        db,
+        &basic,
+        req_token.client_id.as_deref(),
+        req_token.client_secret.as_deref(),
+    )
+    .await?;
+
+    let invalid_grant =
+        |description: &str| OAuthError::new(Status::BadRequest, "invalid_grant", description);
+
+    let (u, scopes) = match req_token.grant_type.as_str() {
+        "authorization_code" => {
+            let code = req_token.code.as_deref().ok_or(OAuthError::new(
+                Status::BadRequest,
+                "invalid_request",
+                "The code parameter is required.",
+            ))?;
+
+            let code_hash = hash_token(code);
+
+            let code = match OauthAuthorizationCode::find()
+                .filter(oauth_authorization_code::Column::CodeHash.eq(&code_hash))
+                .one(db)
+                .await?
+            {
+                Some(c) if c.client_id == client.id => c,
+                _ => return Err(invalid_grant("Unknown authorization code.")),
+            };
+
+            // Codes are single use, whether or not the exchange succeeds. Of
+            // concurrent exchanges, only the one deleting the row goes on.
+            let deleted = OauthAuthorizationCode::delete_many()
+                .filter(oauth_authorization_code::Column::CodeHash.eq(&code_hash))
+                .exec(db)
+                .await?;
+
+            if deleted.rows_affected != 1 {
+                return Err(invalid_grant("Unknown authorization code."));
+            }
+
+            if code.expires_at < DateTimeUtc::from(SystemTime::now()) {
+                return Err(invalid_grant("The authorization code expired."));
+            }
+
+            match req_token.redirect_uri.as_deref() {
+                Some(uri) if uri != code.redirect_uri => {
+                    return Err(invalid_grant("The redirect URI does not match."));
+                }
+                None if code.redirect_uri_sent => {
+                    return Err(invalid_grant(
+                        "The redirect URI is required, as it was sent to authorize.",
+                    ));
+                }
+                _ => (),
+            }
+
+            if !req_token
+                .code_verifier
+                .as_deref()
+                .is_some_and(|verifier| verify_pkce(verifier, &code.code_challenge))
+            {
+                return Err(invalid_grant("PKCE verification failed."));
+            }
+
+            (User::find_by_id(code.user_id).one(db).await?, code.scopes)
+        }
+        "client_credentials" => {
+            if client.client_secret_hash.is_none() {
+                return Err(OAuthError::new(
+                    Status::BadRequest,
+                    "unauthorized_client",
+                    "Public clients cannot use the client credentials grant.",
+                ));
+            }
+
+            let scopes =
+                resolve_scopes(&client, req_token.scope.as_deref()).ok_or(OAuthError::new(
+                    Status::BadRequest,
+                    "invalid_scope",
+                    "The client may not request these scopes.",
+                ))?;
+
+            (User::find_by_id(client.user_id).one(db).await?, scopes)
+        }
+        _ => {
+            return Err(OAuthError::new(
+                Status::BadRequest,
+                "unsupported_grant_type",
+                "Supported grant types are authorization_code and client_credentials.",
+            ));
+        }
+    };
+
+    let u = match u {
+        Some(u) if u.deleted_at.is_none() => u,
+        _ => return Err(invalid_grant("The account no longer exists.")),
+    };
+
+    let (access_token, expires_in) = issue_access_token(db, keys, &client, &u, &scopes).await?;
+
+    Ok(SuccessResponse((
+        Status::Ok,
+        Json(ResToken {
+            access_token,
+            token_type: "Bearer",
+            expires_in,
+            scope: scopes,
+        }),
+    )))
+}
+
+/// Finds the row of an access token issued to `client`, if the token is
+/// still valid.
+async fn find_client_token(
+    db: &DatabaseConnection,
+    keys: &KeyStore,
+    client: &oauth_client::Model,
+    token: &str,
+) -> Result<Option<(oauth_token::Model, u64)>, DbErr> {
+    let claims = match decode_token(token, keys) {
+        Some(claims) if claims.client_id.as_ref() == Some(&client.client_id) => claims,
+        _ => return Ok(None),
+    };
+
+    let row = match &claims.jti {
+        Some(jti) => find_active_token(db, jti).await?,
+        None => None,
+    };
+
+    let u = match &row {
+        Some(row) => User::find_by_id(row.user_id).one(db).await?,
+        None => None,
+    };
+
+    Ok(match (row, u) {
+        (Some(row), Some(u))
+            if u.deleted_at.is_none()
+                && u.disabled_at.is_none()
+                && u.token_version == claims.ver =>
+        {
+            Some((row, claims.exp))
+        }
+        _ => None,
+    })
+}
+
+/// Token introspection (RFC 7662). Clients can only introspect their own
+/// tokens; anything else is reported as inactive.
+#[post("/introspect", data = "<req_introspect>")]
+pub async fn introspect(
+    db: &State<DatabaseConnection>,
+    keys: &State<KeyStore>,
+    basic: BasicAuth,
+    req_introspect: Form<ReqTokenOperation>,
+) -> OAuthResponse<Json<ResIntrospection>> {
+    let db = db as &DatabaseConnection;
+
+    let client = authenticate_client(
+        db,
+        &basic,
+        req_introspect.client_id.as_deref(),
+        req_introspect.client_secret.as_deref(),
+    )
+    .await?;
+
+    let introspection = match find_client_token(db, keys, &client, &req_introspect.token).await? {
+        Some((row, exp)) => ResIntrospection {
+            active: true,
+            scope: Some(row.scopes),
+            client_id: Some(client.client_id),
+            sub: Some(row.user_id.to_string()),
+            exp: Some(exp),
+            token_type: Some("Bearer"),
+        },
+        None => ResIntrospection::default(),
+    };
+
+    Ok(SuccessResponse((Status::Ok, Json(introspection))))
+}
+
+/// Token revocation (RFC 7009). Unknown or foreign tokens are ignored, as the
+/// RFC requires.
+#[post("/revoke", data = "<req_revoke>")]
+pub async fn revoke(
+    db: &State<DatabaseConnection>,
+    keys: &State<KeyStore>,
+    basic: BasicAuth,
+    req_revoke: Form<ReqTokenOperation>,
+) -> OAuthResponse<String> {
+    let db = db as &DatabaseConnection;
+
+    let client = authenticate_client(
+        db,
+        &basic,
+        req_revoke.client_id.as_deref(),
+        req_revoke.client_secret.as_deref(),
+    )
+    .await?;
+
+    if let Some((row, _)) = find_client_token(db, keys, &client, &req_revoke.token).await? {
+        let mut token: oauth_token::ActiveModel = row.into();
+        token.revoked_at = Set(Some(DateTimeUtc::from(SystemTime::now())));
+        token.update(db).await?;
+    }
+
+    Ok(SuccessResponse((Status::Ok, String::new())))
+}
@@ -0,0 +1,268 @@
+use bcrypt::{DEFAULT_COST, hash};
+use rocket::{
+    State,
+    http::{CookieJar, Status},
+    response::Redirect,
+    serde::json::Json,
+};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::Set, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter,
+};
+
+use super::{ErrorResponse, Response, SuccessResponse, auth::ResSignIn};
+use crate::{
+    audit,
+    auth::{
+        AuthenticatedUser, issue_token,
+        keys::KeyStore,
+        oidc::{self, IdTokenClaims, OidcClient, OidcProviderConfig},
+        random_token,
+        sessions::{self, ClientInfo},
+        set_auth_cookies,
+    },
+    config::AppConfig,
+    entities::{prelude::*, user, user_identity},
+};
+
+fn provider<'a>(oidc: &'a OidcClient, name: &str) -> Result<&'a OidcProviderConfig, ErrorResponse> {
+    oidc.provider(name).ok_or(ErrorResponse((
+        Status::NotFound,
+        "Unknown identity provider.".to_string(),
+    )))
+}
+
+/// Sends the user to the provider's login page, remembering the login in a
+/// cookie so only this browser can complete it.
+async fn start_login(
+    oidc: &OidcClient,
+    keys: &KeyStore,
+    config: &AppConfig,
+    cookies: &CookieJar<'_>,
+    name: &str,
+    link_user_id: Option<i32>,
+) -> Response<Redirect> {
+    let provider = provider(oidc, name)?;
+
+    let (url, state) = oidc
+        .authorization_url(provider, link_user_id)
+        .await
+        .map_err(|err| ErrorResponse((Status::BadGateway, err)))?;
+
+    cookies.add(oidc::state_cookie(&state, keys, config));
+
+    Ok(SuccessResponse((Status::SeeOther, Redirect::to(url))))
+}
+
+/// Sends the user to the provider's login page.
+#[get("/<name>/login")]
+pub async fn login(
+    oidc: &State<OidcClient>,
+    keys: &State<KeyStore>,
+    config: &State<AppConfig>,
+    cookies: &CookieJar<'_>,
+    name: &str,
+) -> Response<Redirect> {
+    start_login(oidc, keys, config, cookies, name, None).await
+}
+
+/// Sends a signed in user to the provider's login page, to link the identity
+/// they sign in with to their account.
+#[get("/<name>/link")]
+pub async fn link(
+    oidc: &State<OidcClient>,
+    keys: &State<KeyStore>,
+    config: &State<AppConfig>,
+    cookies: &CookieJar<'_>,
+    user: AuthenticatedUser,
+    name: &str,
+) -> Response<Redirect> {
+    start_login(oidc, keys, config, cookies, name, Some(user.id)).await
+}
+
+/// Handles the provider redirecting back, signing the user in to the account
+/// linked to the external identity, or linking it first when the login was
+/// started to.
+#[get("/<name>/callback?<code>&<state>&<error>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn callback(
+    db: &State<DatabaseConnection>,
+    config: &State<AppConfig>,
+    keys: &State<KeyStore>,
+    oidc: &State<OidcClient>,
+    cookies: &CookieJar<'_>,
+    client: ClientInfo,
+    name: &str,
+    code: Option<&str>,
+    state: Option<&str>,
+    error: Option<&str>,
+) -> Response<Json<ResSignIn>> {
+    let db = db as &DatabaseConnection;
+    let config = config as &AppConfig;
+    let provider = provider(oidc, name)?;
+
+    let (code, state) = match (code, state, error) {
+        (Some(code), Some(state), None) => (code, state),
+        (_, _, error) => {
+            return Err(ErrorResponse((
+                Status::Unauthorized,
+                format!("Sign in failed: {}", error.unwrap_or("missing code")),
+            )));
+        }
+    };
+
+    if !oidc::take_state_cookie(cookies, state, keys) {
+        return Err(ErrorResponse((
+            Status::Unauthorized,
+            "Sign in failed: the login was started in another browser".to_string(),
+        )));
+    }
+
+    let login = oidc
+        .exchange(provider, code, state)
+        .await
+        .map_err(|err| ErrorResponse((Status::Unauthorized, err)))?;
+
+    let u = match login.link_user_id {
+        Some(user_id) => link_user(db, provider, &login.claims, user_id).await?,
+        None => find_or_create_user(db, provider, &login.claims).await?,
+    };
+
+    if u.deleted_at.is_some() {
+        return Err(ErrorResponse((
+            Status::Unauthorized,
+            "Invalid credentials".to_string(),
+        )));
+    }
+
+    if u.disabled_at.is_some() {
+        return Err(ErrorResponse((
+            Status::Forbidden,
+            "This account has been disabled.".to_string(),
+        )));
+    }
+
+    audit::record(db, u.id, "sign_in", Some(format!("oidc:{}", provider.name))).await?;
+
+    let session = sessions::start(db, u.id, &client).await?;
+    let token = issue_token(&u, &session, keys);
+
+    if config.auth_cookie {
+        set_auth_cookies(cookies, token.to_owned(), config);
+    }
+
+    Ok(SuccessResponse((Status::Ok, Json(ResSignIn { token }))))
+}
+
+/// Links an external identity to the signed in user who asked to.
+async fn link_user(
+    db: &DatabaseConnection,
+    provider: &OidcProviderConfig,
+    claims: &IdTokenClaims,
+    user_id: i32,
+) -> Result<user::Model, ErrorResponse> {
+    match find_identity(db, provider, claims).await? {
+        Some(identity) if identity.user_id != user_id => {
+            return Err(ErrorResponse((
+                Status::Conflict,
+                "This identity is linked to another account.".to_string(),
+            )));
+        }
+        Some(_) => (),
+        None => insert_identity(db, provider, claims, user_id).await?,
+    }
+
+    Ok(User::find_by_id(user_id).one(db).await?.unwrap())
+}
+
+async fn find_identity(
+    db: &DatabaseConnection,
+    provider: &OidcProviderConfig,
+    claims: &IdTokenClaims,
+) -> Result<Option<user_identity::Model>, ErrorResponse> {
+    Ok(UserIdentity::find()
+        .filter(user_identity::Column::Provider.eq(&provider.name))
+        .filter(user_identity::Column::Subject.eq(&claims.sub))
+        .one(db)
+        .await?)
+}
+
+async fn insert_identity(
+    db: &DatabaseConnection,
+    provider: &OidcProviderConfig,
+    claims: &IdTokenClaims,
+    user_id: i32,
+) -> Result<(), ErrorResponse> {
+    user_identity::ActiveModel {
+        user_id: Set(user_id),
+        provider: Set(provider.name.to_owned()),
+        subject: Set(claims.sub.to_owned()),
+        email: Set(claims.email.to_owned()),
+        ..Default::default()
+    }
+    .insert(db)
+    .await?;
+
+    audit::record(
+        db,
+        user_id,
+        "identity_linked",
+        Some(provider.name.to_owned()),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Resolves the account for an external identity: an existing link, else an
+/// account with the same verified email when the provider is trusted to
+/// link by email, else a new account.
+async fn find_or_create_user(
+    db: &DatabaseConnection,
+    provider: &OidcProviderConfig,
+    claims: &IdTokenClaims,
+) -> Result<user::Model, ErrorResponse> {
+    if let Some(identity) = find_identity(db, provider, claims).await? {
+        return Ok(User::find_by_id(identity.user_id).one(db).await?.unwrap());
+    }
+
+    let email = match (&claims.email, claims.email_verified) {
+        (Some(email), true) => email,
+        _ => {
+            return Err(ErrorResponse((
+                Status::Unauthorized,
+                "The identity provider did not share a verified email address.".to_string(),
+            )));
+        }
+    };
+
+    let u = match User::find_by_email(email).one(db).await? {
+        Some(u) if provider.link_by_email => u,
+        Some(_) => {
+            return Err(ErrorResponse((
+                Status::Conflict,
+                "An account exists with that email address. Sign in to it and link the provider from there.".to_string(),
+            )));
+        }
+        None => {
+            let u = user::ActiveModel {
+                email: Set(email.to_owned()),
+                // Nobody knows this password, the account signs in through
+                // the provider.
+                password: Set(hash(random_token(32), DEFAULT_COST).unwrap()),
+                firstname: Set(claims.given_name.to_owned().unwrap_or_default()),
+                lastname: Set(claims.family_name.to_owned().unwrap_or_default()),
+                ..Default::default()
+            }
+            .insert(db)
+            .await?;
+
+            audit::record(db, u.id, "sign_up", Some(format!("oidc:{}", provider.name))).await?;
+
+            u
+        }
+    };
+
+    insert_identity(db, provider, claims, u.id).await?;
+
+    Ok(u)
+}
@@ -0,0 +1,102 @@
+use std::time::SystemTime;
+
+use rocket::{
+    State,
+    http::Status,
+    serde::{Serialize, json::Json},
+};
+use sea_orm::{
+    ColumnTrait, DatabaseConnection, EntityTrait, ModelTrait, QueryFilter, QueryOrder,
+    prelude::DateTimeUtc,
+};
+
+use super::{ErrorResponse, Response, SuccessResponse};
+use crate::{
+    audit,
+    auth::AuthenticatedUser,
+    entities::{prelude::*, session},
+};
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ResSession {
+    id: i32,
+    user_agent: Option<String>,
+    ip: Option<String>,
+    /// Whether this is the session of the request's token.
+    current: bool,
+    last_seen_at: DateTimeUtc,
+    created_at: DateTimeUtc,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ResSessionList {
+    total: usize,
+    sessions: Vec<ResSession>,
+}
+
+/// Lists the sessions of the user that are still active, most recently used
+/// first.
+#[get("/")]
+pub async fn index(
+    db: &State<DatabaseConnection>,
+    user: AuthenticatedUser,
+) -> Response<Json<ResSessionList>> {
+    let db = db as &DatabaseConnection;
+
+    let sessions = Session::find()
+        .filter(session::Column::UserId.eq(user.id))
+        .filter(session::Column::ExpiresAt.gt(DateTimeUtc::from(SystemTime::now())))
+        .order_by_desc(session::Column::LastSeenAt)
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|s| ResSession {
+            id: s.id,
+            user_agent: s.user_agent,
+            ip: s.ip,
+            current: Some(s.id) == user.session_id,
+            last_seen_at: s.last_seen_at,
+            created_at: s.created_at,
+        })
+        .collect::<Vec<_>>();
+
+    Ok(SuccessResponse((
+        Status::Ok,
+        Json(ResSessionList {
+            total: sessions.len(),
+            sessions,
+        }),
+    )))
+}
+
+/// Ends a session, signing out the device it belongs to.
+#[delete("/<id>")]
+pub async fn delete(
+    db: &State<DatabaseConnection>,
+    user: AuthenticatedUser,
+    id: i32,
+) -> Response<String> {
+    let db = db as &DatabaseConnection;
+
+    let session = match Session::find_by_id(id)
+        .filter(session::Column::UserId.eq(user.id))
+        .one(db)
+        .await?
+    {
+        Some(s) => s,
+        None => {
+            return Err(ErrorResponse((
+                Status::NotFound,
+                "No session with the specified ID.".to_string(),
+            )));
+        }
+    };
+
+    session.delete(db).await?;
+
+    audit::record(db, user.id, "session_ended", Some(id.to_string())).await?;
+
+    Ok(SuccessResponse((Status::Ok, "Session ended.".to_string())))
+}
@@ -1,14 +1,339 @@
+use std::time::Duration;
+
+use rocket::serde::Deserialize;
 use sea_orm::*;
 
-use crate::AppConfig;
+use crate::config::{AppConfig, Secret};
+
+pub mod replicas;
+pub mod transaction;
+
+/// A database the bookstore can run on, chosen by the scheme of the database
+/// URL. Each needs its cargo feature.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Backend {
+    Postgres,
+    Sqlite,
+    MySql,
+}
+
+impl Backend {
+    pub fn from_url(url: &str) -> Result<Self, String> {
+        match url.split_once(':').map(|(scheme, _)| scheme) {
+            Some("postgres" | "postgresql") => Ok(Backend::Postgres),
+            Some("sqlite") => Ok(Backend::Sqlite),
+            Some("mysql" | "mariadb") => Ok(Backend::MySql),
+            _ => Err("database_url must start with postgres://, sqlite: or mysql://".to_string()),
+        }
+    }
+
+    /// The cargo feature the backend is compiled in with.
+    pub fn feature(&self) -> &'static str {
+        match self {
+            Backend::Postgres => "postgres",
+            Backend::Sqlite => "sqlite",
+            Backend::MySql => "mysql",
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        match self {
+            Backend::Postgres => cfg!(feature = "postgres"),
+            Backend::Sqlite => cfg!(feature = "sqlite"),
+            Backend::MySql => cfg!(feature = "mysql"),
+        }
+    }
+}
+
+/// How the connection to Postgres is encrypted, passed on as `sslmode`.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(crate = "rocket::serde", rename_all = "kebab-case")]
+pub enum SslMode {
+    Disable,
+    Allow,
+    Prefer,
+    Require,
+    VerifyCa,
+    VerifyFull,
+}
+
+impl SslMode {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SslMode::Disable => "disable",
+            SslMode::Allow => "allow",
+            SslMode::Prefer => "prefer",
+            SslMode::Require => "require",
+            SslMode::VerifyCa => "verify-ca",
+            SslMode::VerifyFull => "verify-full",
+        }
+    }
+}
+
+/// The connection pool, configured under `db_pool`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(crate = "rocket::serde", default)]
+pub struct PoolConfig {
+    pub max_connections: u32,
+    /// Connections kept open even when idle.
+    pub min_connections: u32,
+    pub connect_timeout_secs: u64,
+    /// How long a query waits for a free connection before failing.
+    pub acquire_timeout_secs: u64,
+    pub idle_timeout_secs: u64,
+    pub max_lifetime_secs: u64,
+    /// Logs every statement at debug level. Failed and slow statements are
+    /// always logged.
+    pub log_statements: bool,
+    /// Overrides the `sslmode` of the database URL.
+    pub ssl_mode: Option<SslMode>,
+    /// Attempts at connecting on startup, for when the database comes up
+    /// after the application.
+    pub connect_attempts: u32,
+    /// The wait before the second attempt, doubled after every failed one.
+    pub retry_initial_delay_ms: u64,
+    pub retry_max_delay_ms: u64,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            max_connections: 10,
+            min_connections: 1,
+            connect_timeout_secs: 8,
+            acquire_timeout_secs: 8,
+            idle_timeout_secs: 600,
+            max_lifetime_secs: 1800,
+            log_statements: true,
+            ssl_mode: None,
+            connect_attempts: 10,
+            retry_initial_delay_ms: 500,
+            retry_max_delay_ms: 10_000,
+        }
+    }
+}
+
+impl PoolConfig {
+    pub(crate) fn validate(&self, backend: Option<Backend>, errors: &mut Vec<String>) {
+        if self.max_connections == 0 {
+            errors.push("db_pool.max_connections must be at least 1".to_string());
+        }
+
+        if self.min_connections > self.max_connections {
+            errors
+                .push("db_pool.min_connections cannot exceed db_pool.max_connections".to_string());
+        }
+
+        if self.connect_attempts == 0 {
+            errors.push("db_pool.connect_attempts must be at least 1".to_string());
+        }
+
+        if self.ssl_mode.is_some() && backend.is_some_and(|b| b != Backend::Postgres) {
+            errors.push("db_pool.ssl_mode only applies to postgres".to_string());
+        }
+    }
+}
+
+/// Read replicas, configured under `db_replicas`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(crate = "rocket::serde", default)]
+pub struct ReplicaConfig {
+    /// Connection URLs of the replicas, on the same backend as the primary.
+    /// Each gets a pool configured by `db_pool`.
+    pub urls: Vec<Secret>,
+    /// How long the reads of a client that wrote go to the primary, so it
+    /// sees its own changes while the replicas catch up. Tracked with a
+    /// signed `last_write` cookie, so it holds across instances.
+    pub read_your_writes_ms: u64,
+}
+
+impl Default for ReplicaConfig {
+    fn default() -> Self {
+        Self {
+            urls: Vec::new(),
+            read_your_writes_ms: 5000,
+        }
+    }
+}
+
+impl ReplicaConfig {
+    pub(crate) fn validate(&self, backend: Option<Backend>, errors: &mut Vec<String>) {
+        for (i, url) in self.urls.iter().enumerate() {
+            match (Backend::from_url(url.expose()), backend) {
+                (Ok(replica), Some(primary)) if replica != primary => errors.push(format!(
+                    "db_replicas.urls[{}] must be a {} URL like database_url",
+                    i,
+                    primary.feature()
+                )),
+                (Err(_), _) => errors.push(format!(
+                    "db_replicas.urls[{}] must start with postgres://, sqlite: or mysql://",
+                    i
+                )),
+                _ => (),
+            }
+        }
+    }
+}
+
+fn connect_options(config: &AppConfig, mut url: String) -> ConnectOptions {
+    let pool = &config.db_pool;
+
+    if let Some(ssl_mode) = pool.ssl_mode
+        && Backend::from_url(&url) == Ok(Backend::Postgres)
+    {
+        let separator = match url.contains('?') {
+            true => '&',
+            false => '?',
+        };
+
+        url = format!("{}{}sslmode={}", url, separator, ssl_mode.as_str());
+    }
+
+    let mut opts = ConnectOptions::new(url);
+
+    opts.max_connections(pool.max_connections)
+        .min_connections(pool.min_connections)
+        .connect_timeout(Duration::from_secs(pool.connect_timeout_secs))
+        .acquire_timeout(Duration::from_secs(pool.acquire_timeout_secs))
+        .idle_timeout(Duration::from_secs(pool.idle_timeout_secs))
+        .max_lifetime(Duration::from_secs(pool.max_lifetime_secs))
+        // Queries are logged by the metric callback below instead, inside
+        // the span of the request that ran them.
+        .sqlx_logging(false);
+
+    opts
+}
+
+/// Connects to the database, retrying with exponential backoff until
+/// `db_pool.connect_attempts` have failed.
+pub async fn connect(config: &AppConfig) -> Result<DatabaseConnection, DbErr> {
+    connect_to(config, config.database_url()).await
+}
+
+/// Opens a pool for every read replica. They connect lazily, so a replica
+/// that is down does not keep the application from starting; reads go to
+/// the primary until it answers.
+pub async fn connect_replicas(config: &AppConfig) -> Result<Vec<DatabaseConnection>, DbErr> {
+    let mut replicas = Vec::new();
+
+    for url in &config.db_replicas.urls {
+        let mut opts = connect_options(config, url.expose().to_string());
+        opts.connect_lazy(true);
+
+        let mut db = Database::connect(opts).await?;
+        log_queries(&mut db, config);
+
+        replicas.push(db);
+    }
+
+    Ok(replicas)
+}
+
+async fn connect_to(config: &AppConfig, url: String) -> Result<DatabaseConnection, DbErr> {
+    let pool = &config.db_pool;
+    let opts = connect_options(config, url);
+
+    let mut attempt = 1;
+    let mut delay = Duration::from_millis(pool.retry_initial_delay_ms);
+
+    let mut db = loop {
+        match Database::connect(opts.clone()).await {
+            Ok(db) => break db,
+            Err(err) if attempt < pool.connect_attempts => {
+                tracing::warn!(
+                    attempt,
+                    max_attempts = pool.connect_attempts,
+                    retry_in_ms = delay.as_millis() as u64,
+                    error = %err,
+                    "could not connect to the database, retrying"
+                );
+
+                rocket::tokio::time::sleep(delay).await;
+
+                attempt += 1;
+                delay = (delay * 2).min(Duration::from_millis(pool.retry_max_delay_ms));
+            }
+            Err(err) => {
+                tracing::error!(
+                    attempt,
+                    error = %err,
+                    "could not connect to the database, giving up"
+                );
+
+                return Err(err);
+            }
+        }
+    };
+
+    tracing::info!(
+        attempt,
+        max_connections = pool.max_connections,
+        min_connections = pool.min_connections,
+        "connected to the database"
+    );
+
+    log_queries(&mut db, config);
+
+    Ok(db)
+}
+
+fn log_queries(db: &mut DatabaseConnection, config: &AppConfig) {
+    let slow_query = Duration::from_millis(config.slow_query_ms);
+    let log_statements = config.db_pool.log_statements;
+
+    db.set_metric_callback(move |info| {
+        let duration_ms = info.elapsed.as_secs_f64() * 1000.0;
+
+        // Only the SQL is logged, the values may be credentials.
+        if info.failed {
+            tracing::warn!(duration_ms, sql = %info.statement.sql, "query failed");
+        } else if info.elapsed >= slow_query {
+            tracing::warn!(duration_ms, sql = %info.statement.sql, "slow query");
+        } else if log_statements {
+            tracing::debug!(duration_ms, sql = %info.statement.sql, "query");
+        }
+    });
+}
+
+/// A snapshot of the connection pool.
+pub struct PoolStats {
+    pub size: u32,
+    pub idle: usize,
+    pub max_connections: u32,
+}
+
+pub fn pool_stats(db: &DatabaseConnection) -> Option<PoolStats> {
+    match db {
+        #[cfg(feature = "postgres")]
+        DatabaseConnection::SqlxPostgresPoolConnection(_) => {
+            let pool = db.get_postgres_connection_pool();
 
-pub(super) async fn connect(config: &AppConfig) -> Result<DatabaseConnection, DbErr> {
-    let mut opts = ConnectOptions::new(format!(
-        "postgres://{}:{}@{}:{}/{}",
-        config.db_username, config.db_password, config.db_host, config.db_port, config.db_database
-    ));
+            Some(PoolStats {
+                size: pool.size(),
+                idle: pool.num_idle(),
+                max_connections: pool.options().get_max_connections(),
+            })
+        }
+        #[cfg(feature = "sqlite")]
+        DatabaseConnection::SqlxSqlitePoolConnection(_) => {
+            let pool = db.get_sqlite_connection_pool();
 
-    opts.sqlx_logging(false);
+            Some(PoolStats {
+                size: pool.size(),
+                idle: pool.num_idle(),
+                max_connections: pool.options().get_max_connections(),
+            })
+        }
+        #[cfg(feature = "mysql")]
+        DatabaseConnection::SqlxMySqlPoolConnection(_) => {
+            let pool = db.get_mysql_connection_pool();
 
-    Database::connect(opts).await
+            Some(PoolStats {
+                size: pool.size(),
+                idle: pool.num_idle(),
+                max_connections: pool.options().get_max_connections(),
+            })
+        }
+        _ => None,
+    }
 }
@@ -0,0 +1,253 @@
+use std::{
+    collections::HashMap,
+    ops::Deref,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant, SystemTime},
+};
+
+use rocket::{
+    Request,
+    http::{Cookie, SameSite},
+    request::{FromRequest, Outcome},
+    serde::{Deserialize, Serialize},
+};
+use sea_orm::DatabaseConnection;
+
+use crate::{
+    auth::{self, AuthenticatedUser, keys::KeyStore},
+    config::AppConfig,
+};
+
+/// The cookie telling any instance that the user wrote recently.
+pub const LAST_WRITE_COOKIE: &str = "last_write";
+
+/// Tells the last write cookie apart from other tokens signed with our keys.
+const LAST_WRITE_TYP: &str = "last_write";
+
+/// How long a replica's last ping is trusted, either way.
+const RECHECK_AFTER: Duration = Duration::from_secs(5);
+
+/// How long a ping may take before the replica counts as down.
+const PING_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The claims of the last write cookie.
+#[derive(Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+struct LastWrite {
+    typ: String,
+    /// The user who wrote.
+    sub: i32,
+    /// Milliseconds since the Unix epoch.
+    wrote_at: u64,
+    exp: u64,
+}
+
+struct Replica {
+    db: DatabaseConnection,
+    /// When the replica was last pinged, and whether it answered.
+    checked: Mutex<Option<(Instant, bool)>>,
+}
+
+impl Replica {
+    /// Whether the replica answered its last ping, pinging it again once
+    /// that is [`RECHECK_AFTER`] old.
+    async fn available(&self) -> bool {
+        if let Some((at, up)) = *self.checked.lock().unwrap()
+            && at.elapsed() < RECHECK_AFTER
+        {
+            return up;
+        }
+
+        let up = self.ping().await.is_ok();
+
+        if !up {
+            tracing::warn!("read replica is down, reading from the primary");
+        }
+
+        *self.checked.lock().unwrap() = Some((Instant::now(), up));
+
+        up
+    }
+
+    async fn ping(&self) -> Result<(), String> {
+        match rocket::tokio::time::timeout(PING_TIMEOUT, self.db.ping()).await {
+            Ok(result) => result.map_err(|err| err.to_string()),
+            Err(_) => Err(format!("Timed out after {}s", PING_TIMEOUT.as_secs())),
+        }
+    }
+}
+
+/// The read replicas, taken in turn while they answer, and when each user
+/// last wrote through this instance. Cloning shares them, so the readiness
+/// check can ping them too.
+#[derive(Clone)]
+pub struct Replicas {
+    replicas: Arc<Vec<Replica>>,
+    next: Arc<AtomicUsize>,
+    writes: Arc<Mutex<HashMap<i32, Instant>>>,
+    read_your_writes: Duration,
+}
+
+impl Replicas {
+    pub fn new(connections: Vec<DatabaseConnection>, read_your_writes: Duration) -> Self {
+        Self {
+            replicas: Arc::new(
+                connections
+                    .into_iter()
+                    .map(|db| Replica {
+                        db,
+                        checked: Mutex::new(None),
+                    })
+                    .collect(),
+            ),
+            next: Arc::new(AtomicUsize::new(0)),
+            writes: Arc::new(Mutex::new(HashMap::new())),
+            read_your_writes,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.replicas.is_empty()
+    }
+
+    /// Pings every replica, for the readiness check.
+    pub async fn ping(&self) -> Vec<Result<(), String>> {
+        let pings = self.replicas.iter().map(|replica| async move {
+            let result = replica.ping().await;
+
+            *replica.checked.lock().unwrap() = Some((Instant::now(), result.is_ok()));
+
+            result
+        });
+
+        rocket::futures::future::join_all(pings).await
+    }
+
+    /// Sends the reads of `user_id` to the primary for a while. Returns the
+    /// signed cookie holding the time of the write, so that clients keeping
+    /// cookies get the same on whichever instance they land.
+    pub fn record_write(
+        &self,
+        user_id: i32,
+        keys: &KeyStore,
+        config: &AppConfig,
+    ) -> Option<Cookie<'static>> {
+        if self.replicas.is_empty() {
+            return None;
+        }
+
+        let mut writes = self.writes.lock().unwrap();
+
+        writes.retain(|_, at| at.elapsed() < self.read_your_writes);
+        writes.insert(user_id, Instant::now());
+
+        let claims = LastWrite {
+            typ: LAST_WRITE_TYP.to_string(),
+            sub: user_id,
+            wrote_at: unix_time_ms(),
+            exp: auth::unix_time() + self.read_your_writes.as_secs() + 1,
+        };
+
+        Some(
+            Cookie::build((LAST_WRITE_COOKIE, auth::sign(&claims, keys)))
+                .path("/")
+                .http_only(true)
+                .secure(config.auth_cookie_secure)
+                .same_site(SameSite::Lax)
+                .max_age(rocket::time::Duration::milliseconds(
+                    self.read_your_writes.as_millis() as i64,
+                ))
+                .build(),
+        )
+    }
+
+    /// Whether `user_id` wrote through this instance, or sent the cookie of
+    /// a write elsewhere, that the replicas may not have caught up with.
+    fn wrote_recently(&self, req: &Request<'_>, keys: &KeyStore, user_id: i32) -> bool {
+        if self
+            .writes
+            .lock()
+            .unwrap()
+            .get(&user_id)
+            .is_some_and(|at| at.elapsed() < self.read_your_writes)
+        {
+            return true;
+        }
+
+        let Some(cookie) = req.cookies().get(LAST_WRITE_COOKIE) else {
+            return false;
+        };
+
+        auth::verify::<LastWrite>(cookie.value(), keys).is_some_and(|write| {
+            write.typ == LAST_WRITE_TYP
+                && write.sub == user_id
+                && unix_time_ms().saturating_sub(write.wrote_at)
+                    < self.read_your_writes.as_millis() as u64
+        })
+    }
+
+    /// The next replica that answers, or `None` when all are down.
+    async fn replica(&self) -> Option<&DatabaseConnection> {
+        let next = self.next.fetch_add(1, Ordering::Relaxed);
+
+        for i in 0..self.replicas.len() {
+            let replica = &self.replicas[(next + i) % self.replicas.len()];
+
+            if replica.available().await {
+                return Some(&replica.db);
+            }
+        }
+
+        None
+    }
+}
+
+fn unix_time_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+/// A connection for handlers that only read: a replica when any are
+/// configured and up, unless the user wrote recently, and the primary
+/// otherwise.
+pub struct ReadConnection<'r>(&'r DatabaseConnection);
+
+impl Deref for ReadConnection<'_> {
+    type Target = DatabaseConnection;
+
+    fn deref(&self) -> &DatabaseConnection {
+        self.0
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ReadConnection<'r> {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let primary = req.rocket().state::<DatabaseConnection>().unwrap();
+        let keys = req.rocket().state::<KeyStore>().unwrap();
+
+        let replicas = match req.rocket().state::<Replicas>() {
+            Some(replicas) if !replicas.is_empty() => replicas,
+            _ => return Outcome::Success(ReadConnection(primary)),
+        };
+
+        let user_id = req
+            .guard::<AuthenticatedUser>()
+            .await
+            .succeeded()
+            .map(|user| user.id);
+
+        if user_id.is_some_and(|user_id| replicas.wrote_recently(req, keys, user_id)) {
+            return Outcome::Success(ReadConnection(primary));
+        }
+
+        Outcome::Success(ReadConnection(replicas.replica().await.unwrap_or(primary)))
+    }
+}
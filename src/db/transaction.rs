@@ -0,0 +1,57 @@
+use rocket::{
+    Request,
+    request::{FromRequest, Outcome},
+    tokio::sync::{MappedMutexGuard, Mutex, MutexGuard},
+};
+use sea_orm::{DatabaseConnection, DatabaseTransaction, DbErr, TransactionTrait};
+
+/// The transaction of a request, once begun, kept in the request's local
+/// cache until the response settles it.
+#[derive(Default)]
+pub(crate) struct RequestTransaction(Mutex<Option<DatabaseTransaction>>);
+
+impl RequestTransaction {
+    pub(crate) fn of<'r>(req: &'r Request<'_>) -> &'r Self {
+        req.local_cache(RequestTransaction::default)
+    }
+
+    /// Takes the transaction out, when the request began one.
+    pub(crate) async fn take(&self) -> Option<DatabaseTransaction> {
+        self.0.lock().await.take()
+    }
+}
+
+/// A unit of work spanning a request. The transaction begins on the first
+/// call to [`Transaction::get`] and is committed by the
+/// [`RequestTransactions`](crate::fairings::transaction::RequestTransactions)
+/// fairing when the response is a success, or rolled back when it is an
+/// error.
+pub struct Transaction<'r> {
+    db: &'r DatabaseConnection,
+    slot: &'r RequestTransaction,
+}
+
+impl Transaction<'_> {
+    /// The request's transaction, begun on the first call.
+    pub async fn get(&self) -> Result<MappedMutexGuard<'_, DatabaseTransaction>, DbErr> {
+        let mut slot = self.slot.0.lock().await;
+
+        if slot.is_none() {
+            *slot = Some(self.db.begin().await?);
+        }
+
+        Ok(MutexGuard::map(slot, |txn| txn.as_mut().unwrap()))
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Transaction<'r> {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(Transaction {
+            db: req.rocket().state::<DatabaseConnection>().unwrap(),
+            slot: RequestTransaction::of(req),
+        })
+    }
+}
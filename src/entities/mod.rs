@@ -1,7 +1,41 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.11
 
+use std::time::SystemTime;
+
+use sea_orm::{
+    ActiveValue::{self, Set},
+    prelude::DateTimeUtc,
+};
+
 pub mod prelude;
 
+pub mod api_key;
+pub mod audit_event;
 pub mod author;
 pub mod book;
+pub mod oauth_authorization_code;
+pub mod oauth_client;
+pub mod oauth_token;
+pub mod session;
 pub mod user;
+pub mod user_identity;
+
+/// Stamps a record on save: `created_at` when it is inserted, unless given,
+/// and `updated_at` every time. Bulk inserts and updates skip
+/// `ActiveModelBehavior`, and are covered by column defaults and triggers
+/// instead.
+fn stamp(
+    created_at: &mut ActiveValue<DateTimeUtc>,
+    updated_at: Option<&mut ActiveValue<DateTimeUtc>>,
+    insert: bool,
+) {
+    let now = DateTimeUtc::from(SystemTime::now());
+
+    if insert && created_at.is_not_set() {
+        *created_at = Set(now);
+    }
+
+    if let Some(updated_at) = updated_at {
+        *updated_at = Set(now);
+    }
+}
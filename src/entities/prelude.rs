@@ -1,5 +1,12 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.11
 
+pub use super::api_key::Entity as ApiKey;
+pub use super::audit_event::Entity as AuditEvent;
 pub use super::author::Entity as Author;
 pub use super::book::Entity as Book;
+pub use super::oauth_authorization_code::Entity as OauthAuthorizationCode;
+pub use super::oauth_client::Entity as OauthClient;
+pub use super::oauth_token::Entity as OauthToken;
+pub use super::session::Entity as Session;
 pub use super::user::Entity as User;
+pub use super::user_identity::Entity as UserIdentity;
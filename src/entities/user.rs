@@ -1,6 +1,6 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.11
 
-use sea_orm::entity::prelude::*;
+use sea_orm::{entity::prelude::*, sea_query::Func};
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
 #[sea_orm(table_name = "user")]
@@ -12,16 +12,51 @@ pub struct Model {
     pub password: String,
     pub firstname: String,
     pub lastname: String,
-    pub created_at: DateTime,
-    pub updated_at: DateTime,
+    pub created_at: DateTimeUtc,
+    pub updated_at: DateTimeUtc,
+    pub pending_email: Option<String>,
+    pub email_verification_token: Option<String>,
+    pub email_verification_expires_at: Option<DateTimeUtc>,
+    pub token_version: i32,
+    pub deleted_at: Option<DateTimeUtc>,
+    pub role: String,
+    pub disabled_at: Option<DateTimeUtc>,
+    pub password_reset_token: Option<String>,
+    pub password_reset_expires_at: Option<DateTimeUtc>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::api_key::Entity")]
+    ApiKey,
+    #[sea_orm(has_many = "super::audit_event::Entity")]
+    AuditEvent,
     #[sea_orm(has_many = "super::author::Entity")]
     Author,
     #[sea_orm(has_many = "super::book::Entity")]
     Book,
+    #[sea_orm(has_many = "super::oauth_authorization_code::Entity")]
+    OauthAuthorizationCode,
+    #[sea_orm(has_many = "super::oauth_client::Entity")]
+    OauthClient,
+    #[sea_orm(has_many = "super::oauth_token::Entity")]
+    OauthToken,
+    #[sea_orm(has_many = "super::session::Entity")]
+    Session,
+    #[sea_orm(has_many = "super::user_identity::Entity")]
+    UserIdentity,
+}
+
+impl Related<super::api_key::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ApiKey.def()
+    }
+}
+
+impl Related<super::audit_event::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::AuditEvent.def()
+    }
 }
 
 impl Related<super::author::Entity> for Entity {
@@ -36,4 +71,53 @@ impl Related<super::book::Entity> for Entity {
     }
 }
 
-impl ActiveModelBehavior for ActiveModel {}
+impl Related<super::oauth_authorization_code::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::OauthAuthorizationCode.def()
+    }
+}
+
+impl Related<super::oauth_client::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::OauthClient.def()
+    }
+}
+
+impl Related<super::oauth_token::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::OauthToken.def()
+    }
+}
+
+impl Related<super::session::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Session.def()
+    }
+}
+
+impl Related<super::user_identity::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::UserIdentity.def()
+    }
+}
+
+impl Entity {
+    /// Finds users by email address ignoring case, like the unique index on
+    /// `lower(email)` does.
+    pub fn find_by_email(email: &str) -> Select<Entity> {
+        Self::find()
+            .filter(Expr::expr(Func::lower(Expr::col(Column::Email))).eq(email.to_lowercase()))
+    }
+}
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C>(mut self, _db: &C, insert: bool) -> Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        super::stamp(&mut self.created_at, Some(&mut self.updated_at), insert);
+
+        Ok(self)
+    }
+}
@@ -0,0 +1,356 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::{Cursor, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use rocket::{
+    serde::{Serialize, json},
+    tokio::fs,
+};
+use sea_orm::{
+    ColumnTrait, DatabaseConnection, DbErr, EntityTrait, ModelTrait, PaginatorTrait, QueryFilter,
+    prelude::DateTimeUtc,
+};
+use zip::{ZipWriter, write::SimpleFileOptions};
+
+use crate::{
+    auth::random_token,
+    entities::{audit_event, author, book, prelude::*, session},
+};
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ExportProfile {
+    id: i32,
+    email: String,
+    pending_email: Option<String>,
+    firstname: String,
+    lastname: String,
+    created_at: DateTimeUtc,
+    updated_at: DateTimeUtc,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ExportAuthor {
+    id: i32,
+    firstname: String,
+    lastname: String,
+    bio: String,
+    created_at: DateTimeUtc,
+    updated_at: DateTimeUtc,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ExportBook {
+    id: i32,
+    author_id: i32,
+    title: String,
+    year: String,
+    cover: String,
+    created_at: DateTimeUtc,
+    updated_at: DateTimeUtc,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ExportAuditEvent {
+    action: String,
+    detail: Option<String>,
+    created_at: DateTimeUtc,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ExportSession {
+    user_agent: Option<String>,
+    ip: Option<String>,
+    expires_at: DateTimeUtc,
+    last_seen_at: DateTimeUtc,
+    created_at: DateTimeUtc,
+}
+
+/// Everything we store about a user, as handed out by `GET /auth/me/export`.
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ExportBundle {
+    profile: ExportProfile,
+    authors: Vec<ExportAuthor>,
+    books: Vec<ExportBook>,
+    audit_events: Vec<ExportAuditEvent>,
+    sessions: Vec<ExportSession>,
+}
+
+/// Counts the rows an export of `user_id` would contain, used to decide
+/// whether to build it inline or in a background job.
+pub async fn count_rows(db: &DatabaseConnection, user_id: i32) -> Result<u64, DbErr> {
+    let authors = Author::find()
+        .filter(author::Column::UserId.eq(user_id))
+        .count(db)
+        .await?;
+    let books = Book::find()
+        .filter(book::Column::UserId.eq(user_id))
+        .count(db)
+        .await?;
+    let audit_events = AuditEvent::find()
+        .filter(audit_event::Column::UserId.eq(user_id))
+        .count(db)
+        .await?;
+    let sessions = Session::find()
+        .filter(session::Column::UserId.eq(user_id))
+        .count(db)
+        .await?;
+
+    Ok(authors + books + audit_events + sessions)
+}
+
+pub async fn build_bundle(db: &DatabaseConnection, user_id: i32) -> Result<ExportBundle, DbErr> {
+    let u = match User::find_by_id(user_id).one(db).await? {
+        Some(u) => u,
+        None => return Err(DbErr::RecordNotFound(format!("user {}", user_id))),
+    };
+
+    let authors = u.find_related(Author).all(db).await?;
+    let books = u.find_related(Book).all(db).await?;
+    let audit_events = u.find_related(AuditEvent).all(db).await?;
+    let sessions = u.find_related(Session).all(db).await?;
+
+    Ok(ExportBundle {
+        profile: ExportProfile {
+            id: u.id,
+            email: u.email,
+            pending_email: u.pending_email,
+            firstname: u.firstname,
+            lastname: u.lastname,
+            created_at: u.created_at,
+            updated_at: u.updated_at,
+        },
+        authors: authors
+            .into_iter()
+            .map(|a| ExportAuthor {
+                id: a.id,
+                firstname: a.firstname,
+                lastname: a.lastname,
+                bio: a.bio,
+                created_at: a.created_at,
+                updated_at: a.updated_at,
+            })
+            .collect(),
+        books: books
+            .into_iter()
+            .map(|b| ExportBook {
+                id: b.id,
+                author_id: b.author_id,
+                title: b.title,
+                year: b.year,
+                cover: b.cover,
+                created_at: b.created_at,
+                updated_at: b.updated_at,
+            })
+            .collect(),
+        audit_events: audit_events
+            .into_iter()
+            .map(|e| ExportAuditEvent {
+                action: e.action,
+                detail: e.detail,
+                created_at: e.created_at,
+            })
+            .collect(),
+        sessions: sessions
+            .into_iter()
+            .map(|s| ExportSession {
+                user_agent: s.user_agent,
+                ip: s.ip,
+                expires_at: s.expires_at,
+                last_seen_at: s.last_seen_at,
+                created_at: s.created_at,
+            })
+            .collect(),
+    })
+}
+
+/// Packs the bundle into a ZIP archive with one JSON document per section.
+pub fn to_zip(bundle: &ExportBundle) -> Result<Vec<u8>, String> {
+    let sections = [
+        ("profile.json", json::to_pretty_string(&bundle.profile)),
+        ("authors.json", json::to_pretty_string(&bundle.authors)),
+        ("books.json", json::to_pretty_string(&bundle.books)),
+        (
+            "audit_events.json",
+            json::to_pretty_string(&bundle.audit_events),
+        ),
+        ("sessions.json", json::to_pretty_string(&bundle.sessions)),
+    ];
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+
+    for (name, contents) in sections {
+        let contents = contents.map_err(|err| err.to_string())?;
+
+        zip.start_file(name, SimpleFileOptions::default())
+            .map_err(|err| err.to_string())?;
+        zip.write_all(contents.as_bytes())
+            .map_err(|err| err.to_string())?;
+    }
+
+    Ok(zip.finish().map_err(|err| err.to_string())?.into_inner())
+}
+
+#[derive(Clone)]
+pub enum ExportStatus {
+    Pending,
+    Ready(PathBuf),
+    Failed(String),
+}
+
+pub struct ExportJob {
+    pub user_id: i32,
+    pub status: ExportStatus,
+    pub created_at: Instant,
+}
+
+/// In-memory registry of background export jobs, keyed by job id. Cloning
+/// shares the registry so spawned jobs can report back.
+///
+/// Jobs expire after `ttl`, and their files are deleted with them. Files in
+/// `dir` that no job knows of, left over from before a restart, are deleted
+/// once they are as old.
+#[derive(Clone)]
+pub struct ExportJobs {
+    jobs: Arc<Mutex<HashMap<String, ExportJob>>>,
+    dir: PathBuf,
+    ttl: Duration,
+}
+
+impl ExportJobs {
+    pub fn new(dir: PathBuf, ttl: Duration) -> Self {
+        Self {
+            jobs: Arc::new(Mutex::new(HashMap::new())),
+            dir,
+            ttl,
+        }
+    }
+
+    /// Where the export of `job_id` is written.
+    pub fn path(&self, job_id: &str) -> PathBuf {
+        self.dir.join(format!("{}.zip", job_id))
+    }
+
+    /// Registers a background export for `user_id`, unless one of theirs is
+    /// pending or ready already. Returns the job id, and whether the export
+    /// still has to be built.
+    pub fn start(&self, user_id: i32) -> (String, bool) {
+        let mut jobs = self.jobs.lock().unwrap();
+
+        if let Some((job_id, _)) = jobs.iter().find(|(_, job)| {
+            job.user_id == user_id
+                && job.created_at.elapsed() < self.ttl
+                && !matches!(job.status, ExportStatus::Failed(_))
+        }) {
+            return (job_id.to_owned(), false);
+        }
+
+        let job_id = random_token(32);
+
+        jobs.insert(
+            job_id.to_owned(),
+            ExportJob {
+                user_id,
+                status: ExportStatus::Pending,
+                created_at: Instant::now(),
+            },
+        );
+
+        (job_id, true)
+    }
+
+    /// The status of a job of `user_id`, until it expires.
+    pub fn status(&self, job_id: &str, user_id: i32) -> Option<ExportStatus> {
+        self.jobs
+            .lock()
+            .unwrap()
+            .get(job_id)
+            .filter(|job| job.user_id == user_id)
+            .map(|job| job.status.clone())
+    }
+
+    /// Reports the outcome of a job. A file finished after its job expired
+    /// is deleted straight away.
+    pub async fn set_status(&self, job_id: &str, status: ExportStatus) {
+        let orphan = match self.jobs.lock().unwrap().get_mut(job_id) {
+            Some(job) => {
+                job.status = status;
+                None
+            }
+            None => match status {
+                ExportStatus::Ready(path) => Some(path),
+                _ => None,
+            },
+        };
+
+        if let Some(path) = orphan {
+            remove_file(&path).await;
+        }
+    }
+
+    /// Forgets expired jobs and deletes their files. The files are deleted
+    /// once the registry is unlocked.
+    pub async fn prune(&self) {
+        let (expired, known) = {
+            let mut jobs = self.jobs.lock().unwrap();
+            let mut expired = Vec::new();
+
+            jobs.retain(|_, job| {
+                let keep = job.created_at.elapsed() < self.ttl;
+
+                if !keep && let ExportStatus::Ready(path) = &job.status {
+                    expired.push(path.to_owned());
+                }
+
+                keep
+            });
+
+            (expired, jobs.keys().cloned().collect::<HashSet<_>>())
+        };
+
+        for path in expired {
+            remove_file(&path).await;
+        }
+
+        let mut entries = match fs::read_dir(&self.dir).await {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            let known = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .is_some_and(|job_id| known.contains(job_id));
+            let age = entry
+                .metadata()
+                .await
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|modified| modified.elapsed().ok());
+
+            if path.extension().is_some_and(|ext| ext == "zip")
+                && !known
+                && age.is_some_and(|age| age >= self.ttl)
+            {
+                remove_file(&path).await;
+            }
+        }
+    }
+}
+
+async fn remove_file(path: &Path) {
+    if let Err(err) = fs::remove_file(path).await {
+        tracing::warn!(path = %path.display(), error = %err, "cannot delete an export");
+    }
+}
@@ -1,13 +1,101 @@
+use regex::Regex;
 use rocket::{
     Request, Response,
     fairing::{Fairing, Info, Kind},
-    http::Header,
+    http::{Header, Method, Status},
+    serde::Deserialize,
 };
 
-pub struct CORS;
+use crate::config::{list, regex};
+
+/// The cross-origin policy, configured under `cors`. Without allowed origins
+/// no CORS headers are sent and browsers only allow same-origin requests.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(crate = "rocket::serde", default)]
+pub struct CorsConfig {
+    /// Exact origins, e.g. `https://app.example.com`. `*` allows any origin
+    /// but then credentials are never allowed.
+    #[serde(deserialize_with = "list")]
+    pub origins: Vec<String>,
+    /// Origins matching this are allowed too, e.g. `^https://.*\.example\.com$`.
+    #[serde(deserialize_with = "regex")]
+    pub origin_regex: Option<Regex>,
+    #[serde(deserialize_with = "list")]
+    pub methods: Vec<String>,
+    #[serde(deserialize_with = "list")]
+    pub headers: Vec<String>,
+    #[serde(deserialize_with = "list")]
+    pub expose_headers: Vec<String>,
+    pub max_age: u64,
+    pub credentials: bool,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        let list = |items: &[&str]| items.iter().map(|i| i.to_string()).collect();
+
+        Self {
+            origins: Vec::new(),
+            origin_regex: None,
+            methods: list(&["GET", "POST", "PUT", "PATCH", "DELETE"]),
+            headers: list(&["Authorization", "Content-Type", "X-CSRF-Token"]),
+            expose_headers: list(&[
+                "RateLimit-Limit",
+                "RateLimit-Remaining",
+                "RateLimit-Reset",
+                "Retry-After",
+            ]),
+            max_age: 3600,
+            credentials: false,
+        }
+    }
+}
+
+impl CorsConfig {
+    fn any_origin(&self) -> bool {
+        self.origins.iter().any(|o| o == "*")
+    }
+
+    fn allows_origin(&self, origin: &str) -> bool {
+        self.any_origin()
+            || self.origins.iter().any(|o| o == origin)
+            || self
+                .origin_regex
+                .as_ref()
+                .is_some_and(|re| re.is_match(origin))
+    }
+
+    fn allows_method(&self, method: &str) -> bool {
+        self.methods.iter().any(|m| m.eq_ignore_ascii_case(method))
+    }
+
+    fn allows_headers(&self, headers: &str) -> bool {
+        headers
+            .split(',')
+            .map(str::trim)
+            .filter(|h| !h.is_empty())
+            .all(|h| {
+                self.headers
+                    .iter()
+                    .any(|allowed| allowed.eq_ignore_ascii_case(h))
+            })
+    }
+}
+
+/// Applies the CORS policy: echoes allowed origins and answers preflight
+/// requests.
+pub struct Cors {
+    config: CorsConfig,
+}
+
+impl Cors {
+    pub fn new(config: CorsConfig) -> Self {
+        Self { config }
+    }
+}
 
 #[rocket::async_trait]
-impl Fairing for CORS {
+impl Fairing for Cors {
     fn info(&self) -> Info {
         Info {
             name: "Add CORS headers",
@@ -15,18 +103,70 @@ impl Fairing for CORS {
         }
     }
 
-    async fn on_response<'r>(&self, _request: &'r Request<'_>, response: &mut Response<'r>) {
-        response.set_header(Header::new("Access-Control-Allow-Origin", "*"));
-        response.set_header(Header::new(
-            "Access-Control-Allow-Methods",
-            "GET, POST, PUT, PATCH, DELETE, OPTIONS",
-        ));
-        response.set_header(Header::new("Access-Control-Allow-Headers", "*"));
-        response.set_header(Header::new("Access-Control-Allow-Credentials", "true"));
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let origin = match request.headers().get_one("Origin") {
+            Some(origin) => origin,
+            None => return,
+        };
+
+        // Responses differ per origin, caches must not mix them up.
+        response.adjoin_header(Header::new("Vary", "Origin"));
+
+        if !self.config.allows_origin(origin) {
+            return;
+        }
+
+        let preflight = request.method() == Method::Options;
+
+        if preflight {
+            let method = request.headers().get_one("Access-Control-Request-Method");
+            let headers = request
+                .headers()
+                .get_one("Access-Control-Request-Headers")
+                .unwrap_or("");
+
+            if !method.is_some_and(|m| self.config.allows_method(m))
+                || !self.config.allows_headers(headers)
+            {
+                return;
+            }
+
+            response.set_header(Header::new(
+                "Access-Control-Allow-Methods",
+                self.config.methods.join(", "),
+            ));
+            response.set_header(Header::new(
+                "Access-Control-Allow-Headers",
+                self.config.headers.join(", "),
+            ));
+            response.set_header(Header::new(
+                "Access-Control-Max-Age",
+                self.config.max_age.to_string(),
+            ));
+        } else if !self.config.expose_headers.is_empty() {
+            response.set_header(Header::new(
+                "Access-Control-Expose-Headers",
+                self.config.expose_headers.join(", "),
+            ));
+        }
+
+        if self.config.any_origin() {
+            response.set_header(Header::new("Access-Control-Allow-Origin", "*"));
+        } else {
+            response.set_header(Header::new(
+                "Access-Control-Allow-Origin",
+                origin.to_string(),
+            ));
+
+            if self.config.credentials {
+                response.set_header(Header::new("Access-Control-Allow-Credentials", "true"));
+            }
+        }
     }
 }
 
+/// Answers preflight requests for every path; the fairing adds the headers.
 #[options("/<_..>")]
-pub fn options() -> &'static str {
-    ""
+pub fn options() -> Status {
+    Status::NoContent
 }
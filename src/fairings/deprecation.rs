@@ -0,0 +1,33 @@
+use rocket::{
+    Request, Response,
+    fairing::{Fairing, Info, Kind},
+    http::Header,
+};
+
+use crate::auth::LEGACY_TOKEN_HEADER;
+
+/// When the `token` header was deprecated in favour of `Authorization:
+/// Bearer`, as an RFC 9745 date (2026-10-19).
+const LEGACY_TOKEN_HEADER_DEPRECATED: &str = "@1792368000";
+
+/// Tells clients still authenticating with the `token` header that it is
+/// deprecated, with a `Deprecation` header on every response to them.
+pub struct Deprecations;
+
+#[rocket::async_trait]
+impl Fairing for Deprecations {
+    fn info(&self) -> Info {
+        Info {
+            name: "Flag deprecated request headers",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let headers = req.headers();
+
+        if headers.contains(LEGACY_TOKEN_HEADER) && !headers.contains("Authorization") {
+            res.set_header(Header::new("Deprecation", LEGACY_TOKEN_HEADER_DEPRECATED));
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use std::time::Instant;
+
+use rocket::{
+    Data, Request, Response,
+    fairing::{Fairing, Info, Kind},
+};
+
+use crate::metrics::Metrics;
+
+struct RequestStart(Instant);
+
+/// Counts requests and their latency per route and status.
+pub struct RequestMetrics;
+
+#[rocket::async_trait]
+impl Fairing for RequestMetrics {
+    fn info(&self) -> Info {
+        Info {
+            name: "Record request metrics",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _data: &mut Data<'_>) {
+        req.local_cache(|| RequestStart(Instant::now()));
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let metrics = match req.rocket().state::<Metrics>() {
+            Some(metrics) => metrics,
+            None => return,
+        };
+
+        let seconds = req
+            .local_cache(|| RequestStart(Instant::now()))
+            .0
+            .elapsed()
+            .as_secs_f64();
+
+        // Requests no route matched are counted together, rather than
+        // creating a series for every path probed.
+        let route = match req.route() {
+            Some(route) => route.uri.path().to_string(),
+            None => "unmatched".to_string(),
+        };
+
+        metrics.observe_request(req.method().as_str(), &route, res.status(), seconds);
+    }
+}
@@ -1 +1,8 @@
 pub mod cors;
+pub mod deprecation;
+pub mod metrics;
+pub mod rate_limit;
+pub mod replicas;
+pub mod request_id;
+pub mod security_headers;
+pub mod transaction;
@@ -0,0 +1,317 @@
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use rocket::{
+    Data, Request, Response,
+    fairing::{Fairing, Info, Kind},
+    http::{Header, Method, Status, uri::Origin},
+};
+
+use crate::{
+    auth::{
+        ACCESS_TOKEN_COOKIE, LEGACY_TOKEN_HEADER, api_keys::API_KEY_PREFIX, bearer_token,
+        decode_token, hash_token, keys::KeyStore,
+    },
+    config::AppConfig,
+    controllers::ErrorResponse,
+};
+
+/// Where over-quota requests are rerouted, so their handler never runs.
+const RATE_LIMITED_PATH: &str = "/__rate_limited";
+
+/// Buckets kept by `MemoryStore` before idle ones are pruned.
+const MEMORY_STORE_PRUNE_AT: usize = 10_000;
+
+/// What a request is counted against.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RateLimitKey {
+    /// Always the client IP, for routes used before signing in.
+    Ip,
+    /// The API key or user of the request's credential, else the client IP.
+    Client,
+}
+
+/// A token bucket holding `capacity` requests that refills completely over
+/// `window`.
+#[derive(Clone, Debug)]
+pub struct RouteLimit {
+    pub method: Option<Method>,
+    pub path: String,
+    pub capacity: u32,
+    pub window: Duration,
+    pub key: RateLimitKey,
+}
+
+impl RouteLimit {
+    /// Parses `[METHOD ]/path=capacity/seconds[:ip]`, e.g.
+    /// `POST /auth/sign-in=5/60:ip`. Paths match by prefix.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let invalid = || {
+            format!(
+                "Invalid rate limit `{}`, expected [METHOD ]/path=capacity/seconds[:ip]",
+                value
+            )
+        };
+
+        let (route, limit) = value.split_once('=').ok_or_else(invalid)?;
+
+        let (method, path) = match route.trim().split_once(' ') {
+            Some((method, path)) => (Some(method.parse().map_err(|_| invalid())?), path.trim()),
+            None => (None, route.trim()),
+        };
+
+        let (limit, key) = match limit.trim().split_once(':') {
+            Some((limit, "ip")) => (limit, RateLimitKey::Ip),
+            Some(_) => return Err(invalid()),
+            None => (limit.trim(), RateLimitKey::Client),
+        };
+
+        let (capacity, seconds) = limit.split_once('/').ok_or_else(invalid)?;
+
+        Ok(Self {
+            method,
+            path: path.to_string(),
+            capacity: capacity.parse().map_err(|_| invalid())?,
+            window: Duration::from_secs(seconds.parse().map_err(|_| invalid())?),
+            key,
+        })
+    }
+
+    /// Tight limits on the unauthenticated auth routes and a generous one
+    /// for everything else.
+    pub fn defaults() -> Vec<Self> {
+        [
+            "POST /auth/sign-in=5/60:ip",
+            "POST /auth/sign-up=3/600:ip",
+            "POST /auth/reset-password=5/600:ip",
+            "POST /oauth/token=30/60",
+            "/=300/60",
+        ]
+        .into_iter()
+        .map(|rule| Self::parse(rule).unwrap())
+        .collect()
+    }
+
+    fn matches(&self, req: &Request<'_>) -> bool {
+        self.method.is_none_or(|method| method == req.method())
+            && req.uri().path().as_str().starts_with(&self.path)
+    }
+}
+
+impl FromStr for RouteLimit {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Self::parse(value)
+    }
+}
+
+/// The outcome of taking a token from a bucket.
+#[derive(Clone, Copy)]
+pub struct RateLimitDecision {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    /// Seconds until the bucket is full again.
+    pub reset: u64,
+    /// Seconds until the next request is allowed, when this one was not.
+    pub retry_after: u64,
+}
+
+/// Storage for the token buckets. The in-memory store only limits a single
+/// instance; a shared backend (Redis, the database) can implement this to
+/// limit across instances.
+#[rocket::async_trait]
+pub trait RateLimitStore: Send + Sync {
+    async fn take(&self, key: &str, limit: &RouteLimit) -> Result<RateLimitDecision, String>;
+}
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+    /// The window of the bucket's limit, which may differ from the one of
+    /// the limit being checked when buckets are pruned.
+    window: Duration,
+}
+
+#[derive(Default)]
+pub struct MemoryStore {
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+#[rocket::async_trait]
+impl RateLimitStore for MemoryStore {
+    async fn take(&self, key: &str, limit: &RouteLimit) -> Result<RateLimitDecision, String> {
+        let capacity = limit.capacity as f64;
+        let rate = capacity / limit.window.as_secs_f64().max(1.0);
+        let now = Instant::now();
+
+        let mut buckets = self.buckets.lock().unwrap();
+
+        if buckets.len() >= MEMORY_STORE_PRUNE_AT {
+            // A bucket idle for a whole window is full again, which is the
+            // same as having no bucket at all.
+            buckets.retain(|_, bucket| now.duration_since(bucket.updated_at) < bucket.window);
+        }
+
+        let bucket = buckets.entry(key.to_string()).or_insert(Bucket {
+            tokens: capacity,
+            updated_at: now,
+            window: limit.window,
+        });
+
+        bucket.tokens = (bucket.tokens
+            + now.duration_since(bucket.updated_at).as_secs_f64() * rate)
+            .min(capacity);
+        bucket.updated_at = now;
+
+        let allowed = bucket.tokens >= 1.0;
+
+        if allowed {
+            bucket.tokens -= 1.0;
+        }
+
+        Ok(RateLimitDecision {
+            allowed,
+            limit: limit.capacity,
+            remaining: bucket.tokens.floor() as u32,
+            reset: ((capacity - bucket.tokens) / rate).ceil() as u64,
+            retry_after: match allowed {
+                true => 0,
+                false => ((1.0 - bucket.tokens) / rate).ceil() as u64,
+            },
+        })
+    }
+}
+
+/// Limits request rates per route with token buckets, adding `RateLimit-*`
+/// headers to responses and answering over-quota requests with 429.
+pub struct RateLimiter {
+    limits: Vec<RouteLimit>,
+    store: Box<dyn RateLimitStore>,
+}
+
+impl RateLimiter {
+    /// Limits are tried in order, the first matching one applies.
+    pub fn new(limits: Vec<RouteLimit>, store: impl RateLimitStore + 'static) -> Self {
+        Self {
+            limits,
+            store: Box::new(store),
+        }
+    }
+}
+
+/// Identifies the client a request is counted against: the API key or user
+/// of a valid credential, else the client IP.
+fn client_key(req: &Request<'_>, key: RateLimitKey) -> String {
+    let config = req.rocket().state::<AppConfig>();
+
+    let ip = || match config.and_then(|config| config.client_ip(req)) {
+        Some(ip) => format!("ip:{}", ip),
+        None => "ip:unknown".to_string(),
+    };
+
+    if key == RateLimitKey::Ip {
+        return ip();
+    }
+
+    let keys = req.rocket().state::<KeyStore>();
+
+    let token = match req.headers().get_one("Authorization") {
+        Some(authorization) => bearer_token(authorization).map(str::to_string),
+        None => req
+            .headers()
+            .get_one(LEGACY_TOKEN_HEADER)
+            .map(str::to_string)
+            .or_else(|| match config.is_some_and(|c| c.auth_cookie) {
+                true => req
+                    .cookies()
+                    .get(ACCESS_TOKEN_COOKIE)
+                    .map(|c| c.value().to_string()),
+                false => None,
+            }),
+    };
+
+    match token {
+        // Checking the key needs the database, counting by its hash keeps
+        // one caller from draining another key's bucket.
+        Some(token) if token.starts_with(API_KEY_PREFIX) => format!("key:{}", hash_token(&token)),
+        Some(token) => keys
+            .and_then(|keys| decode_token(&token, keys))
+            .map(|claims| format!("user:{}", claims.sub))
+            .unwrap_or_else(ip),
+        None => ip(),
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for RateLimiter {
+    fn info(&self) -> Info {
+        Info {
+            name: "Rate limit requests",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _data: &mut Data<'_>) {
+        // Browsers send preflights on their own, ahead of the requests that
+        // are counted.
+        if req.method() == Method::Options {
+            return;
+        }
+
+        let (index, limit) = match self.limits.iter().enumerate().find(|(_, l)| l.matches(req)) {
+            Some(found) => found,
+            None => return,
+        };
+
+        let key = format!("{}:{}", index, client_key(req, limit.key));
+
+        let decision = match self.store.take(&key, limit).await {
+            Ok(decision) => decision,
+            Err(err) => {
+                // Failing open: an unavailable store must not take the API
+                // down with it.
+                error!("Rate limit store failed: {}", err);
+                return;
+            }
+        };
+
+        req.local_cache(|| Some(decision));
+
+        if !decision.allowed {
+            req.set_method(Method::Get);
+            req.set_uri(Origin::parse(RATE_LIMITED_PATH).unwrap());
+        }
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let decision = match req.local_cache(|| None::<RateLimitDecision>) {
+            Some(decision) => decision,
+            None => return,
+        };
+
+        res.set_header(Header::new("RateLimit-Limit", decision.limit.to_string()));
+        res.set_header(Header::new(
+            "RateLimit-Remaining",
+            decision.remaining.to_string(),
+        ));
+        res.set_header(Header::new("RateLimit-Reset", decision.reset.to_string()));
+
+        if !decision.allowed {
+            res.set_header(Header::new("Retry-After", decision.retry_after.to_string()));
+        }
+    }
+}
+
+#[get("/__rate_limited")]
+pub fn rate_limited() -> ErrorResponse {
+    ErrorResponse::new(
+        Status::TooManyRequests,
+        "Too many requests, please try again later.",
+    )
+}
@@ -0,0 +1,51 @@
+use rocket::{
+    Request, Response,
+    fairing::{Fairing, Info, Kind},
+    http::Method,
+};
+
+use crate::{
+    auth::{AuthenticatedUser, keys::KeyStore},
+    config::AppConfig,
+    db::replicas::Replicas,
+};
+
+/// Keeps the reads of a user who just wrote on the primary, until the
+/// replicas have caught up. Any successful request other than a read counts
+/// as a write. It is remembered by this instance, and in a cookie so that it
+/// holds across instances for clients keeping cookies.
+pub struct ReadYourWrites;
+
+#[rocket::async_trait]
+impl Fairing for ReadYourWrites {
+    fn info(&self) -> Info {
+        Info {
+            name: "Route reads after writes to the primary",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let replicas = match req.rocket().state::<Replicas>() {
+            Some(replicas) => replicas,
+            None => return,
+        };
+
+        if matches!(req.method(), Method::Get | Method::Head | Method::Options)
+            || res.status().code >= 400
+        {
+            return;
+        }
+
+        let keys = req.rocket().state::<KeyStore>().unwrap();
+        let config = req.rocket().state::<AppConfig>().unwrap();
+
+        // Only requests a handler authenticated count, without
+        // authenticating the others here.
+        if let Some(user) = AuthenticatedUser::cached(req)
+            && let Some(cookie) = replicas.record_write(user.id, keys, config)
+        {
+            res.adjoin_header(cookie);
+        }
+    }
+}
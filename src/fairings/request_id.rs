@@ -0,0 +1,127 @@
+use std::time::Instant;
+
+use rocket::{
+    Data, Request, Response, Route,
+    fairing::{Fairing, Info, Kind},
+    http::{Header, Status},
+    route::{self, Handler},
+};
+use tracing::Instrument;
+
+use crate::auth::random_token;
+
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+/// The id of the request, taken from a well-formed incoming `X-Request-Id`
+/// header so it can be followed across services, or generated.
+#[derive(Clone)]
+pub struct RequestId(pub String);
+
+impl RequestId {
+    pub fn of(req: &Request<'_>) -> Option<String> {
+        req.local_cache(|| None::<RequestId>)
+            .as_ref()
+            .map(|id| id.0.to_owned())
+    }
+}
+
+struct RequestStart(Instant);
+
+fn valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= 128
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+/// Assigns every request an id, echoes it in the `X-Request-Id` response
+/// header and logs each request once it completes.
+pub struct RequestTracing;
+
+#[rocket::async_trait]
+impl Fairing for RequestTracing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Assign request ids and log requests",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _data: &mut Data<'_>) {
+        let id = match req.headers().get_one(REQUEST_ID_HEADER) {
+            Some(id) if valid_request_id(id) => id.to_string(),
+            _ => random_token(20),
+        };
+
+        req.local_cache(|| Some(RequestId(id)));
+        req.local_cache(|| RequestStart(Instant::now()));
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let request_id = RequestId::of(req).unwrap_or_default();
+        let duration_ms = req
+            .local_cache(|| RequestStart(Instant::now()))
+            .0
+            .elapsed()
+            .as_secs_f64()
+            * 1000.0;
+        let status = res.status();
+        let route = req.route().map(|r| r.uri.to_string()).unwrap_or_default();
+
+        if status.code >= Status::InternalServerError.code {
+            tracing::error!(
+                request_id,
+                method = %req.method(),
+                path = %req.uri().path(),
+                route,
+                status = status.code,
+                duration_ms,
+                "request failed"
+            );
+        } else {
+            tracing::info!(
+                request_id,
+                method = %req.method(),
+                path = %req.uri().path(),
+                route,
+                status = status.code,
+                duration_ms,
+                "request completed"
+            );
+        }
+
+        res.set_header(Header::new(REQUEST_ID_HEADER, request_id));
+    }
+}
+
+/// Runs a route's handler, guards included, inside a span carrying the
+/// request id, so everything it logs (database queries in particular) can be
+/// traced back to the request.
+#[derive(Clone)]
+struct Traced(Box<dyn Handler>);
+
+#[rocket::async_trait]
+impl Handler for Traced {
+    async fn handle<'r>(&self, req: &'r Request<'_>, data: Data<'r>) -> route::Outcome<'r> {
+        let span = tracing::info_span!(
+            "route",
+            request_id = RequestId::of(req).unwrap_or_default(),
+            method = %req.method(),
+            route = req.route().map(|r| r.uri.to_string()).unwrap_or_default(),
+        );
+
+        self.0.handle(req, data).instrument(span).await
+    }
+}
+
+/// Wraps the handlers of `routes` in a request span.
+pub fn traced(routes: Vec<Route>) -> Vec<Route> {
+    routes
+        .into_iter()
+        .map(|mut route| {
+            route.handler = Box::new(Traced(route.handler));
+            route
+        })
+        .collect()
+}
@@ -0,0 +1,175 @@
+use std::collections::BTreeMap;
+
+use rocket::{
+    Request, Response,
+    fairing::{Fairing, Info, Kind},
+    http::Header,
+    serde::Deserialize,
+};
+
+/// The header values of one route group. `None` leaves a header out.
+#[derive(Clone, Debug)]
+pub struct SecurityHeaderValues {
+    pub hsts: Option<String>,
+    pub content_security_policy: Option<String>,
+    pub content_type_options: Option<String>,
+    pub referrer_policy: Option<String>,
+    pub permissions_policy: Option<String>,
+    pub frame_options: Option<String>,
+}
+
+impl Default for SecurityHeaderValues {
+    /// Strict values for a JSON API that is never framed or rendered.
+    fn default() -> Self {
+        Self {
+            hsts: Some("max-age=31536000; includeSubDomains".to_string()),
+            content_security_policy: Some("default-src 'none'; frame-ancestors 'none'".to_string()),
+            content_type_options: Some("nosniff".to_string()),
+            referrer_policy: Some("no-referrer".to_string()),
+            permissions_policy: Some("camera=(), microphone=(), geolocation=()".to_string()),
+            frame_options: Some("DENY".to_string()),
+        }
+    }
+}
+
+/// The header values set in the configuration. An empty value removes the
+/// header.
+#[derive(Default, Deserialize)]
+#[serde(crate = "rocket::serde", default)]
+struct SecurityHeaderOverrides {
+    hsts: Option<String>,
+    csp: Option<String>,
+    content_type_options: Option<String>,
+    referrer_policy: Option<String>,
+    permissions_policy: Option<String>,
+    frame_options: Option<String>,
+}
+
+impl SecurityHeaderValues {
+    fn with(&self, overrides: &SecurityHeaderOverrides) -> Self {
+        let value = |value: &Option<String>, base: &Option<String>| match value {
+            Some(value) if value.is_empty() => None,
+            Some(value) => Some(value.to_owned()),
+            None => base.clone(),
+        };
+
+        Self {
+            hsts: value(&overrides.hsts, &self.hsts),
+            content_security_policy: value(&overrides.csp, &self.content_security_policy),
+            content_type_options: value(
+                &overrides.content_type_options,
+                &self.content_type_options,
+            ),
+            referrer_policy: value(&overrides.referrer_policy, &self.referrer_policy),
+            permissions_policy: value(&overrides.permissions_policy, &self.permissions_policy),
+            frame_options: value(&overrides.frame_options, &self.frame_options),
+        }
+    }
+
+    fn headers(&self) -> [(&'static str, &Option<String>); 6] {
+        [
+            ("Strict-Transport-Security", &self.hsts),
+            ("Content-Security-Policy", &self.content_security_policy),
+            ("X-Content-Type-Options", &self.content_type_options),
+            ("Referrer-Policy", &self.referrer_policy),
+            ("Permissions-Policy", &self.permissions_policy),
+            ("X-Frame-Options", &self.frame_options),
+        ]
+    }
+}
+
+/// A set of routes, matched by path prefix, with its own header values.
+#[derive(Clone, Debug)]
+pub struct SecurityHeaderGroup {
+    pub path: String,
+    pub values: SecurityHeaderValues,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct SecurityHeaderGroupSettings {
+    path: String,
+    #[serde(flatten)]
+    overrides: SecurityHeaderOverrides,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(crate = "rocket::serde", default)]
+struct SecurityHeadersSettings {
+    default: SecurityHeaderOverrides,
+    groups: BTreeMap<String, SecurityHeaderGroupSettings>,
+}
+
+/// Security headers for every response, configured under
+/// `security_headers.default` and, for route groups, under
+/// `security_headers.groups.<name>` with the group's `path`. Groups start
+/// from the default values.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(crate = "rocket::serde", from = "SecurityHeadersSettings")]
+pub struct SecurityHeadersConfig {
+    pub default: SecurityHeaderValues,
+    pub groups: Vec<SecurityHeaderGroup>,
+}
+
+impl From<SecurityHeadersSettings> for SecurityHeadersConfig {
+    fn from(settings: SecurityHeadersSettings) -> Self {
+        let default = SecurityHeaderValues::default().with(&settings.default);
+
+        let groups = settings
+            .groups
+            .into_values()
+            .map(|group| SecurityHeaderGroup {
+                values: default.with(&group.overrides),
+                path: group.path,
+            })
+            .collect();
+
+        Self { default, groups }
+    }
+}
+
+impl SecurityHeadersConfig {
+    /// The values of the group with the longest matching path.
+    fn values_for(&self, path: &str) -> &SecurityHeaderValues {
+        self.groups
+            .iter()
+            .filter(|group| path.starts_with(&group.path))
+            .max_by_key(|group| group.path.len())
+            .map_or(&self.default, |group| &group.values)
+    }
+}
+
+/// Replaces Rocket's default `Shield`, whose fixed headers would otherwise
+/// take precedence over the configured ones.
+pub struct SecurityHeaders {
+    config: SecurityHeadersConfig,
+}
+
+impl SecurityHeaders {
+    pub fn new(config: SecurityHeadersConfig) -> Self {
+        Self { config }
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for SecurityHeaders {
+    fn info(&self) -> Info {
+        Info {
+            name: "Add security headers",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let values = self.config.values_for(request.uri().path().as_str());
+
+        for (name, value) in values.headers() {
+            // Handlers that set a header themselves know better.
+            if let Some(value) = value
+                && !response.headers().contains(name)
+            {
+                response.set_header(Header::new(name, value.to_owned()));
+            }
+        }
+    }
+}
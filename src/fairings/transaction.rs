@@ -0,0 +1,46 @@
+use rocket::{
+    Request, Response,
+    fairing::{Fairing, Info, Kind},
+    response::Responder,
+};
+
+use crate::{controllers::ErrorResponse, db::transaction::RequestTransaction};
+
+/// Settles the transaction a request began through the
+/// [`Transaction`](crate::db::transaction::Transaction) guard: committed
+/// when the response is a success, rolled back otherwise. A failed commit
+/// turns the response into an error, as nothing was saved.
+///
+/// Attached before the other fairings, so they see the final response.
+pub struct RequestTransactions;
+
+#[rocket::async_trait]
+impl Fairing for RequestTransactions {
+    fn info(&self) -> Info {
+        Info {
+            name: "Commit or roll back request transactions",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let txn = match RequestTransaction::of(req).take().await {
+            Some(txn) => txn,
+            None => return,
+        };
+
+        if res.status().code >= 400 {
+            if let Err(err) = txn.rollback().await {
+                tracing::error!(error = %err, "cannot roll back the request's transaction");
+            }
+
+            return;
+        }
+
+        if let Err(err) = txn.commit().await
+            && let Ok(error) = ErrorResponse::from(err).respond_to(req)
+        {
+            res.merge(error);
+        }
+    }
+}
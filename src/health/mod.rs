@@ -0,0 +1,176 @@
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use rocket::serde::Serialize;
+use sea_orm::DatabaseConnection;
+use sea_orm_migration::MigratorTrait;
+
+use crate::{db::replicas::Replicas, migrator::Migrator};
+
+/// How long a check may take before it counts as failed.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A dependency the application needs to serve requests. Checks are run by
+/// `GET /health/ready`, and any required one failing takes the instance out
+/// of rotation.
+#[rocket::async_trait]
+pub trait HealthCheck: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Whether the instance cannot serve requests while the check fails.
+    /// Others only degrade it.
+    fn required(&self) -> bool {
+        true
+    }
+
+    async fn check(&self, db: &DatabaseConnection) -> Result<(), String>;
+}
+
+/// The database answers a ping.
+pub struct DatabaseCheck;
+
+#[rocket::async_trait]
+impl HealthCheck for DatabaseCheck {
+    fn name(&self) -> &str {
+        "database"
+    }
+
+    async fn check(&self, db: &DatabaseConnection) -> Result<(), String> {
+        db.ping().await.map_err(|err| err.to_string())
+    }
+}
+
+/// Every migration has been applied, so the schema is the one the code
+/// expects.
+pub struct MigrationsCheck;
+
+#[rocket::async_trait]
+impl HealthCheck for MigrationsCheck {
+    fn name(&self) -> &str {
+        "migrations"
+    }
+
+    async fn check(&self, db: &DatabaseConnection) -> Result<(), String> {
+        let pending = Migrator::get_pending_migrations(db)
+            .await
+            .map_err(|err| err.to_string())?;
+
+        match pending.is_empty() {
+            true => Ok(()),
+            false => Err(format!(
+                "Pending migrations: {}",
+                pending
+                    .iter()
+                    .map(|m| m.name())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+        }
+    }
+}
+
+/// Export archives can be written to the export directory.
+pub struct ExportDirCheck(pub PathBuf);
+
+#[rocket::async_trait]
+impl HealthCheck for ExportDirCheck {
+    fn name(&self) -> &str {
+        "export_dir"
+    }
+
+    async fn check(&self, _db: &DatabaseConnection) -> Result<(), String> {
+        let probe = self.0.join(".health");
+
+        rocket::tokio::fs::create_dir_all(&self.0)
+            .await
+            .map_err(|err| err.to_string())?;
+        rocket::tokio::fs::write(&probe, b"")
+            .await
+            .map_err(|err| err.to_string())?;
+        rocket::tokio::fs::remove_file(&probe)
+            .await
+            .map_err(|err| err.to_string())
+    }
+}
+
+/// The read replicas answer a ping. Reads fall back to the primary while
+/// they do not, so the instance keeps serving.
+pub struct ReplicasCheck(pub Replicas);
+
+#[rocket::async_trait]
+impl HealthCheck for ReplicasCheck {
+    fn name(&self) -> &str {
+        "replicas"
+    }
+
+    fn required(&self) -> bool {
+        false
+    }
+
+    async fn check(&self, _db: &DatabaseConnection) -> Result<(), String> {
+        let errors = self
+            .0
+            .ping()
+            .await
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, result)| {
+                result
+                    .err()
+                    .map(|err| format!("db_replicas.urls[{}]: {}", i, err))
+            })
+            .collect::<Vec<_>>();
+
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(errors.join("; ")),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct CheckReport {
+    pub name: String,
+    pub healthy: bool,
+    pub required: bool,
+    pub duration_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// The registered health checks.
+#[derive(Default)]
+pub struct HealthChecks(Vec<Box<dyn HealthCheck>>);
+
+impl HealthChecks {
+    pub fn register(mut self, check: impl HealthCheck + 'static) -> Self {
+        self.0.push(Box::new(check));
+        self
+    }
+
+    /// Runs every check concurrently, each with a timeout, reporting them in
+    /// the order they were added.
+    pub async fn run(&self, db: &DatabaseConnection) -> Vec<CheckReport> {
+        let runs = self.0.iter().map(|check| async move {
+            let start = Instant::now();
+
+            let result = match rocket::tokio::time::timeout(CHECK_TIMEOUT, check.check(db)).await {
+                Ok(result) => result,
+                Err(_) => Err(format!("Timed out after {}s", CHECK_TIMEOUT.as_secs())),
+            };
+
+            CheckReport {
+                name: check.name().to_string(),
+                healthy: result.is_ok(),
+                required: check.required(),
+                duration_ms: start.elapsed().as_secs_f64() * 1000.0,
+                error: result.err(),
+            }
+        });
+
+        rocket::futures::future::join_all(runs).await
+    }
+}
@@ -0,0 +1,216 @@
+//! The bookstore API. The `bookstore` binary wraps it in a command line
+//! interface, and integration tests build it through [`build_rocket`].
+
+use std::time::Duration;
+
+use auth::{keys::KeyStore, oauth::ConsentRequests, oidc::OidcClient};
+use config::AppConfig;
+use controllers::{Response, SuccessResponse};
+use db::replicas::Replicas;
+use export::ExportJobs;
+use fairings::{
+    cors::Cors,
+    deprecation::Deprecations,
+    metrics::RequestMetrics,
+    rate_limit::{MemoryStore, RateLimiter},
+    replicas::ReadYourWrites,
+    request_id::{RequestTracing, traced},
+    security_headers::SecurityHeaders,
+    transaction::RequestTransactions,
+};
+use health::{DatabaseCheck, ExportDirCheck, HealthChecks, MigrationsCheck, ReplicasCheck};
+use metrics::Metrics;
+use rocket::{Build, Rocket, fairing::AdHoc, http::Status, shield::Shield};
+use sea_orm::DatabaseConnection;
+
+#[macro_use]
+extern crate rocket;
+
+pub mod audit;
+pub mod auth;
+pub mod cli;
+pub mod config;
+pub mod controllers;
+pub mod db;
+pub mod entities;
+pub mod export;
+pub mod fairings;
+pub mod health;
+pub mod mail;
+pub mod metrics;
+pub mod migrator;
+pub mod seed;
+
+#[get("/")]
+fn index() -> Response<String> {
+    Ok(SuccessResponse((Status::Ok, "Hello Wolrd".to_string())))
+}
+
+/// Mounts every route and catcher.
+pub fn mount(rocket: Rocket<Build>) -> Rocket<Build> {
+    rocket
+        .mount(
+            "/",
+            traced(routes![
+                index,
+                controllers::metrics::index,
+                fairings::cors::options,
+                fairings::rate_limit::rate_limited
+            ]),
+        )
+        .mount(
+            "/health",
+            traced(routes![
+                controllers::health::live,
+                controllers::health::ready
+            ]),
+        )
+        .mount(
+            "/auth",
+            traced(routes![
+                controllers::auth::sign_in,
+                controllers::auth::sign_up,
+                controllers::auth::sign_out,
+                controllers::auth::me,
+                controllers::auth::update_me,
+                controllers::auth::verify_email,
+                controllers::auth::change_password,
+                controllers::auth::reset_password,
+                controllers::auth::delete_me,
+                controllers::export::create_export,
+                controllers::export::download_export
+            ]),
+        )
+        .mount(
+            "/auth/oidc",
+            traced(routes![
+                controllers::oidc::login,
+                controllers::oidc::link,
+                controllers::oidc::callback
+            ]),
+        )
+        .mount(
+            "/auth/api-keys",
+            traced(routes![
+                controllers::api_keys::index,
+                controllers::api_keys::create,
+                controllers::api_keys::show,
+                controllers::api_keys::update,
+                controllers::api_keys::delete
+            ]),
+        )
+        .mount(
+            "/auth/sessions",
+            traced(routes![
+                controllers::sessions::index,
+                controllers::sessions::delete
+            ]),
+        )
+        .mount(
+            "/oauth",
+            traced(routes![
+                controllers::oauth::clients,
+                controllers::oauth::create_client,
+                controllers::oauth::show_client,
+                controllers::oauth::delete_client,
+                controllers::oauth::authorize,
+                controllers::oauth::authorize_decision,
+                controllers::oauth::token,
+                controllers::oauth::introspect,
+                controllers::oauth::revoke
+            ]),
+        )
+        .mount(
+            "/admin/users",
+            traced(routes![
+                controllers::admin::index,
+                controllers::admin::show,
+                controllers::admin::authors,
+                controllers::admin::books,
+                controllers::admin::update_role,
+                controllers::admin::disable,
+                controllers::admin::enable,
+                controllers::admin::force_password_reset,
+                controllers::admin::impersonate
+            ]),
+        )
+        .mount("/.well-known", traced(routes![controllers::auth::jwks]))
+        .mount(
+            "/authors",
+            traced(routes![
+                controllers::authors::index,
+                controllers::authors::create,
+                controllers::authors::show,
+                controllers::authors::update,
+                controllers::authors::delete,
+                controllers::authors::get_books
+            ]),
+        )
+        .mount(
+            "/books",
+            traced(routes![
+                controllers::books::index,
+                controllers::books::create,
+                controllers::books::show,
+                controllers::books::update,
+                controllers::books::delete,
+            ]),
+        )
+        .register("/", catchers![controllers::default_catcher])
+}
+
+/// Builds the application around open connections to the primary database
+/// and to its read replicas, if any.
+pub fn build_rocket(
+    config: AppConfig,
+    db: DatabaseConnection,
+    replicas: Vec<DatabaseConnection>,
+) -> Result<Rocket<Build>, String> {
+    let keys = KeyStore::load(&config)?;
+    let replicas = Replicas::new(
+        replicas,
+        Duration::from_millis(config.db_replicas.read_your_writes_ms),
+    );
+    let oidc = OidcClient::new(&config.oidc);
+    let rate_limiter = RateLimiter::new(config.rate_limits.clone(), MemoryStore::default());
+    let export_jobs = ExportJobs::new(
+        config.export_dir.clone().into(),
+        Duration::from_secs(config.export_ttl_secs),
+    );
+
+    let mut health_checks = HealthChecks::default()
+        .register(DatabaseCheck)
+        .register(MigrationsCheck)
+        .register(ExportDirCheck(config.export_dir.clone().into()));
+
+    if !replicas.is_empty() {
+        health_checks = health_checks.register(ReplicasCheck(replicas.clone()));
+    }
+
+    let rocket = rocket::custom(AppConfig::figment())
+        .attach(RequestTransactions)
+        .attach(ReadYourWrites)
+        .attach(RequestTracing)
+        .attach(RequestMetrics)
+        .attach(Cors::new(config.cors.clone()))
+        .attach(Shield::new())
+        .attach(SecurityHeaders::new(config.security_headers.clone()))
+        .attach(Deprecations)
+        .attach(rate_limiter)
+        // Exports left over from before a restart can no longer be downloaded.
+        .attach(AdHoc::on_ignite("Prune exports", |rocket| async move {
+            rocket.state::<ExportJobs>().unwrap().prune().await;
+            rocket
+        }))
+        .manage(db)
+        .manage(replicas)
+        .manage(config)
+        .manage(keys)
+        .manage(oidc)
+        .manage(export_jobs)
+        .manage(ConsentRequests::default())
+        .manage(Metrics::default())
+        .manage(health_checks);
+
+    Ok(mount(rocket))
+}
@@ -0,0 +1,7 @@
+/// Delivers an email to `to`.
+///
+/// No mail transport is configured yet, so only the recipient and subject
+/// are logged. Bodies carry one-time tokens and stay out of the logs.
+pub fn send(to: &str, subject: &str, _body: &str) {
+    tracing::debug!(to, subject, "mail not sent, no transport is configured");
+}
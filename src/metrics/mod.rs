@@ -0,0 +1,244 @@
+use std::{net::IpAddr, str::FromStr};
+
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
+use rocket::{
+    http::Status,
+    request::{FromRequest, Outcome, Request},
+    serde::Deserialize,
+};
+use sea_orm::{DatabaseConnection, DbErr, EntityTrait, PaginatorTrait};
+
+use crate::{
+    auth::{bearer_token, hash_token},
+    config::{AppConfig, Secret, list},
+    db,
+    entities::prelude::*,
+};
+
+/// A single address, or a network in CIDR notation.
+#[derive(Clone, Debug)]
+pub struct AllowedNetwork {
+    addr: IpAddr,
+    prefix: u32,
+}
+
+impl AllowedNetwork {
+    /// Parses `127.0.0.1`, `10.0.0.0/8`, `::1` or `fd00::/8`.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let invalid = || format!("Invalid address or network `{}`", value);
+
+        let (addr, prefix) = match value.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (value, None),
+        };
+
+        let addr: IpAddr = addr.parse().map_err(|_| invalid())?;
+        let bits = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+
+        let prefix = match prefix {
+            Some(prefix) => prefix.parse().map_err(|_| invalid())?,
+            None => bits,
+        };
+
+        if prefix > bits {
+            return Err(invalid());
+        }
+
+        Ok(Self { addr, prefix })
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for AllowedNetwork {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Self::parse(value)
+    }
+}
+
+/// Who may scrape `/metrics`, configured under `metrics`. Clients from an
+/// allowed network are let in, as is anyone presenting the token as a bearer
+/// token. Nobody is by default: behind a reverse proxy on the same host every
+/// request comes from loopback, so even that is opt-in.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(crate = "rocket::serde", default)]
+pub struct MetricsConfig {
+    #[serde(deserialize_with = "list")]
+    pub allow: Vec<AllowedNetwork>,
+    pub token: Option<Secret>,
+}
+
+/// A request allowed to read the metrics.
+pub struct MetricsAccess;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for MetricsAccess {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let app_config = req.rocket().state::<AppConfig>().unwrap();
+        let config = &app_config.metrics;
+
+        let allowed_ip = app_config
+            .client_ip(req)
+            .is_some_and(|ip| config.allow.iter().any(|network| network.contains(ip)));
+
+        // Comparing hashes keeps the comparison from leaking the token.
+        let valid_token = config.token.as_ref().is_some_and(|token| {
+            req.headers()
+                .get_one("Authorization")
+                .and_then(bearer_token)
+                .is_some_and(|bearer| hash_token(bearer) == hash_token(token.expose()))
+        });
+
+        match allowed_ip || valid_token {
+            true => Outcome::Success(MetricsAccess),
+            false => Outcome::Error((Status::Forbidden, ())),
+        }
+    }
+}
+
+/// The application's Prometheus metrics. Request and sign in metrics are
+/// recorded as they happen, the database and catalogue ones are read when
+/// the metrics are scraped.
+pub struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    request_duration: HistogramVec,
+    sign_ins: IntCounterVec,
+    db_connections: IntGaugeVec,
+    db_max_connections: IntGauge,
+    books: IntGauge,
+    authors: IntGauge,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        let requests = IntCounterVec::new(
+            Opts::new("bookstore_http_requests_total", "HTTP requests handled."),
+            &["method", "route", "status"],
+        )
+        .unwrap();
+        let request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "bookstore_http_request_duration_seconds",
+                "Time taken to handle HTTP requests.",
+            ),
+            &["method", "route", "status"],
+        )
+        .unwrap();
+        let sign_ins = IntCounterVec::new(
+            Opts::new("bookstore_sign_ins_total", "Password sign in attempts."),
+            &["result"],
+        )
+        .unwrap();
+        let db_connections = IntGaugeVec::new(
+            Opts::new(
+                "bookstore_db_pool_connections",
+                "Open database connections.",
+            ),
+            &["state"],
+        )
+        .unwrap();
+        let db_max_connections = IntGauge::new(
+            "bookstore_db_pool_max_connections",
+            "Maximum database connections in the pool.",
+        )
+        .unwrap();
+        let books = IntGauge::new("bookstore_books", "Books in the catalogue.").unwrap();
+        let authors = IntGauge::new("bookstore_authors", "Authors in the catalogue.").unwrap();
+
+        let registry = Registry::new();
+
+        registry.register(Box::new(requests.clone())).unwrap();
+        registry
+            .register(Box::new(request_duration.clone()))
+            .unwrap();
+        registry.register(Box::new(sign_ins.clone())).unwrap();
+        registry.register(Box::new(db_connections.clone())).unwrap();
+        registry
+            .register(Box::new(db_max_connections.clone()))
+            .unwrap();
+        registry.register(Box::new(books.clone())).unwrap();
+        registry.register(Box::new(authors.clone())).unwrap();
+
+        Self {
+            registry,
+            requests,
+            request_duration,
+            sign_ins,
+            db_connections,
+            db_max_connections,
+            books,
+            authors,
+        }
+    }
+}
+
+impl Metrics {
+    /// Records a handled request. `route` is the matched route's path, not
+    /// the requested one, to keep the number of series bounded.
+    pub fn observe_request(&self, method: &str, route: &str, status: Status, seconds: f64) {
+        let status = status.code.to_string();
+        let labels = [method, route, status.as_str()];
+
+        self.requests.with_label_values(&labels).inc();
+        self.request_duration
+            .with_label_values(&labels)
+            .observe(seconds);
+    }
+
+    pub fn sign_in(&self, success: bool) {
+        let result = match success {
+            true => "success",
+            false => "failure",
+        };
+
+        self.sign_ins.with_label_values(&[result]).inc();
+    }
+
+    /// Refreshes the gauges and renders every metric in the Prometheus text
+    /// format.
+    pub async fn render(&self, db: &DatabaseConnection) -> Result<String, DbErr> {
+        if let Some(pool) = db::pool_stats(db) {
+            let idle = pool.idle as i64;
+
+            self.db_connections.with_label_values(&["idle"]).set(idle);
+            self.db_connections
+                .with_label_values(&["in_use"])
+                .set(pool.size as i64 - idle);
+            self.db_max_connections.set(pool.max_connections as i64);
+        }
+
+        self.books.set(Book::find().count(db).await? as i64);
+        self.authors.set(Author::find().count(db).await? as i64);
+
+        let mut buffer = Vec::new();
+
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .unwrap();
+
+        Ok(String::from_utf8(buffer).unwrap())
+    }
+}
@@ -16,8 +16,8 @@ impl MigrationTrait for Migration {
                     .col(string(User::Password).not_null())
                     .col(string(User::Firstname).not_null())
                     .col(string(User::Lastname).not_null())
-                    .col(timestamp(User::CreatedAt).extra("DEFAULT CURRENT_TIMESTAMP".to_owned()))
-                    .col(timestamp(User::UpdatedAt).extra("DEFAULT CURRENT_TIMESTAMP".to_owned()))
+                    .col(timestamp(User::CreatedAt).default(Expr::current_timestamp()))
+                    .col(timestamp(User::UpdatedAt).default(Expr::current_timestamp()))
                     .to_owned(),
             )
             .await
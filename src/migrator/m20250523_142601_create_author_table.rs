@@ -24,8 +24,8 @@ impl MigrationTrait for Migration {
                     .col(string(Author::Firstname).not_null())
                     .col(string(Author::Lastname).not_null())
                     .col(string(Author::Bio).not_null())
-                    .col(timestamp(Author::CreatedAt).extra("DEFAULT CURRENT_TIMESTAMP".to_owned()))
-                    .col(timestamp(Author::UpdatedAt).extra("DEFAULT CURRENT_TIMESTAMP".to_owned()))
+                    .col(timestamp(Author::CreatedAt).default(Expr::current_timestamp()))
+                    .col(timestamp(Author::UpdatedAt).default(Expr::current_timestamp()))
                     .to_owned(),
             )
             .await
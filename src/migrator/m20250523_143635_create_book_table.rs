@@ -33,8 +33,8 @@ impl MigrationTrait for Migration {
                     .col(string(Book::Title))
                     .col(string(Book::Year))
                     .col(string(Book::Cover))
-                    .col(timestamp(Book::CreatedAt).extra("DEFAULT CURRENT_TIMESTAMP"))
-                    .col(timestamp(Book::UpdatedAt).extra("DEFAULT CURRENT_TIMESTAMP"))
+                    .col(timestamp(Book::CreatedAt).default(Expr::current_timestamp()))
+                    .col(timestamp(Book::UpdatedAt).default(Expr::current_timestamp()))
                     .to_owned(),
             )
             .await
@@ -0,0 +1,54 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for col in [
+            string_null(User::PendingEmail),
+            string_null(User::EmailVerificationToken),
+            timestamp_null(User::EmailVerificationExpiresAt),
+            integer(User::TokenVersion).default(0).to_owned(),
+            timestamp_null(User::DeletedAt),
+        ] {
+            manager
+                .alter_table(Table::alter().table(User::Table).add_column(col).to_owned())
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for col in [
+            User::PendingEmail,
+            User::EmailVerificationToken,
+            User::EmailVerificationExpiresAt,
+            User::TokenVersion,
+            User::DeletedAt,
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(User::Table)
+                        .drop_column(col)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    PendingEmail,
+    EmailVerificationToken,
+    EmailVerificationExpiresAt,
+    TokenVersion,
+    DeletedAt,
+}
@@ -0,0 +1,47 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use super::m20220101_000001_create_user_table::User;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(AuditEvent::Table)
+                    .if_not_exists()
+                    .col(pk_auto(AuditEvent::Id))
+                    .col(integer(AuditEvent::UserId))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-audit_event-user_id")
+                            .from(AuditEvent::Table, AuditEvent::UserId)
+                            .to(User::Table, User::Id),
+                    )
+                    .col(string(AuditEvent::Action))
+                    .col(text_null(AuditEvent::Detail))
+                    .col(timestamp(AuditEvent::CreatedAt).default(Expr::current_timestamp()))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(AuditEvent::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum AuditEvent {
+    Table,
+    Id,
+    UserId,
+    Action,
+    Detail,
+    CreatedAt,
+}
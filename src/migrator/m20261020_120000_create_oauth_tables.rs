@@ -0,0 +1,162 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use super::m20220101_000001_create_user_table::User;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(OauthClient::Table)
+                    .if_not_exists()
+                    .col(pk_auto(OauthClient::Id))
+                    .col(integer(OauthClient::UserId))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-oauth_client-user_id")
+                            .from(OauthClient::Table, OauthClient::UserId)
+                            .to(User::Table, User::Id),
+                    )
+                    .col(string(OauthClient::ClientId).unique_key())
+                    .col(string_null(OauthClient::ClientSecretHash))
+                    .col(string(OauthClient::Name))
+                    .col(text(OauthClient::RedirectUris))
+                    .col(string(OauthClient::Scopes))
+                    .col(timestamp(OauthClient::CreatedAt).default(Expr::current_timestamp()))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(OauthAuthorizationCode::Table)
+                    .if_not_exists()
+                    .col(pk_auto(OauthAuthorizationCode::Id))
+                    .col(string(OauthAuthorizationCode::CodeHash).unique_key())
+                    .col(integer(OauthAuthorizationCode::ClientId))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-oauth_authorization_code-client_id")
+                            .from(
+                                OauthAuthorizationCode::Table,
+                                OauthAuthorizationCode::ClientId,
+                            )
+                            .to(OauthClient::Table, OauthClient::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .col(integer(OauthAuthorizationCode::UserId))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-oauth_authorization_code-user_id")
+                            .from(
+                                OauthAuthorizationCode::Table,
+                                OauthAuthorizationCode::UserId,
+                            )
+                            .to(User::Table, User::Id),
+                    )
+                    .col(text(OauthAuthorizationCode::RedirectUri))
+                    .col(string(OauthAuthorizationCode::Scopes))
+                    .col(string(OauthAuthorizationCode::CodeChallenge))
+                    .col(timestamp(OauthAuthorizationCode::ExpiresAt))
+                    .col(
+                        timestamp(OauthAuthorizationCode::CreatedAt)
+                            .default(Expr::current_timestamp()),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(OauthToken::Table)
+                    .if_not_exists()
+                    .col(pk_auto(OauthToken::Id))
+                    .col(string(OauthToken::Jti).unique_key())
+                    .col(integer(OauthToken::ClientId))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-oauth_token-client_id")
+                            .from(OauthToken::Table, OauthToken::ClientId)
+                            .to(OauthClient::Table, OauthClient::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .col(integer(OauthToken::UserId))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-oauth_token-user_id")
+                            .from(OauthToken::Table, OauthToken::UserId)
+                            .to(User::Table, User::Id),
+                    )
+                    .col(string(OauthToken::Scopes))
+                    .col(timestamp(OauthToken::ExpiresAt))
+                    .col(timestamp_null(OauthToken::RevokedAt))
+                    .col(timestamp(OauthToken::CreatedAt).default(Expr::current_timestamp()))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(OauthToken::Table).to_owned())
+            .await?;
+
+        manager
+            .drop_table(
+                Table::drop()
+                    .table(OauthAuthorizationCode::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(OauthClient::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum OauthClient {
+    Table,
+    Id,
+    UserId,
+    ClientId,
+    ClientSecretHash,
+    Name,
+    RedirectUris,
+    Scopes,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum OauthAuthorizationCode {
+    Table,
+    Id,
+    CodeHash,
+    ClientId,
+    UserId,
+    RedirectUri,
+    Scopes,
+    CodeChallenge,
+    ExpiresAt,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum OauthToken {
+    Table,
+    Id,
+    Jti,
+    ClientId,
+    UserId,
+    Scopes,
+    ExpiresAt,
+    RevokedAt,
+    CreatedAt,
+}
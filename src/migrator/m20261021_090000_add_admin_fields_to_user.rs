@@ -0,0 +1,51 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for col in [
+            string(User::Role).default("user").to_owned(),
+            timestamp_null(User::DisabledAt),
+            string_null(User::PasswordResetToken),
+            timestamp_null(User::PasswordResetExpiresAt),
+        ] {
+            manager
+                .alter_table(Table::alter().table(User::Table).add_column(col).to_owned())
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for col in [
+            User::Role,
+            User::DisabledAt,
+            User::PasswordResetToken,
+            User::PasswordResetExpiresAt,
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(User::Table)
+                        .drop_column(col)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    Role,
+    DisabledAt,
+    PasswordResetToken,
+    PasswordResetExpiresAt,
+}
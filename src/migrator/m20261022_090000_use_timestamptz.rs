@@ -0,0 +1,259 @@
+use sea_orm_migration::{prelude::*, sea_orm::DatabaseBackend};
+
+/// Moves every timestamp column to a time zone aware type, storing UTC.
+///
+/// Rows written before this migration hold UTC already: they were stamped
+/// with `DateTime<Utc>::naive_local()`, which is the UTC wall time, over
+/// connections running in UTC. They are converted as UTC, unshifted.
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// Timestamp columns, with whether each is nullable and whether it defaults
+/// to the current time.
+type Columns = &'static [(&'static str, bool, bool)];
+
+/// Every timestamp column by table.
+const COLUMNS: [(&str, Columns); 10] = [
+    (
+        "user",
+        &[
+            ("created_at", false, true),
+            ("updated_at", false, true),
+            ("email_verification_expires_at", true, false),
+            ("deleted_at", true, false),
+            ("disabled_at", true, false),
+            ("password_reset_expires_at", true, false),
+        ],
+    ),
+    (
+        "author",
+        &[("created_at", false, true), ("updated_at", false, true)],
+    ),
+    (
+        "book",
+        &[("created_at", false, true), ("updated_at", false, true)],
+    ),
+    ("audit_event", &[("created_at", false, true)]),
+    (
+        "api_key",
+        &[
+            ("expires_at", true, false),
+            ("last_used_at", true, false),
+            ("created_at", false, true),
+        ],
+    ),
+    ("user_identity", &[("created_at", false, true)]),
+    ("oauth_client", &[("created_at", false, true)]),
+    (
+        "oauth_authorization_code",
+        &[("expires_at", false, false), ("created_at", false, true)],
+    ),
+    (
+        "oauth_token",
+        &[
+            ("expires_at", false, false),
+            ("revoked_at", true, false),
+            ("created_at", false, true),
+        ],
+    ),
+    (
+        "session",
+        &[
+            ("expires_at", false, false),
+            ("last_seen_at", false, true),
+            ("created_at", false, true),
+        ],
+    ),
+];
+
+/// The tables whose `updated_at` is kept current by a trigger, for writes
+/// that bypass the entities, such as bulk updates and manual SQL.
+const TOUCHED_TABLES: [&str; 3] = ["user", "author", "book"];
+
+/// How SQLite stores a `DateTime<Utc>`, so values written by triggers and
+/// by the application sort and compare alike.
+const SQLITE_FORMAT: &str = "%Y-%m-%dT%H:%M:%f+00:00";
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        match manager.get_database_backend() {
+            // The stored values are UTC, whatever the server's time zone.
+            DatabaseBackend::Postgres => {
+                for (table, columns) in COLUMNS {
+                    let changes = columns
+                        .iter()
+                        .map(|(column, _, _)| {
+                            format!(
+                                r#"ALTER COLUMN "{0}" TYPE timestamptz USING "{0}" AT TIME ZONE 'UTC'"#,
+                                column
+                            )
+                        })
+                        .collect::<Vec<_>>()
+                        .join(", ");
+
+                    db.execute_unprepared(&format!(r#"ALTER TABLE "{}" {}"#, table, changes))
+                        .await?;
+                }
+
+                db.execute_unprepared(
+                    "CREATE OR REPLACE FUNCTION set_updated_at() RETURNS trigger AS $$
+                    BEGIN
+                        IF NEW.updated_at IS NOT DISTINCT FROM OLD.updated_at THEN
+                            NEW.updated_at = CURRENT_TIMESTAMP;
+                        END IF;
+                        RETURN NEW;
+                    END;
+                    $$ LANGUAGE plpgsql",
+                )
+                .await?;
+
+                for table in TOUCHED_TABLES {
+                    db.execute_unprepared(&format!(
+                        r#"CREATE TRIGGER "{0}_updated_at" BEFORE UPDATE ON "{0}"
+                        FOR EACH ROW EXECUTE FUNCTION set_updated_at()"#,
+                        table
+                    ))
+                    .await?;
+                }
+            }
+            // `timestamp` columns are stored in UTC, converted from the
+            // connection's time zone, which is UTC for the application.
+            DatabaseBackend::MySql => {
+                for (table, columns) in COLUMNS {
+                    let mut alter = Table::alter();
+                    alter.table(Alias::new(table));
+
+                    for (column, nullable, current) in columns.iter() {
+                        let mut def = ColumnDef::new(Alias::new(*column));
+                        def.timestamp_with_time_zone();
+                        column_options(&mut def, *nullable, *current);
+                        alter.modify_column(def);
+                    }
+
+                    manager.alter_table(alter).await?;
+                }
+
+                for table in TOUCHED_TABLES {
+                    db.execute_unprepared(&format!(
+                        "CREATE TRIGGER `{0}_updated_at` BEFORE UPDATE ON `{0}` FOR EACH ROW
+                        SET NEW.updated_at = IF(NEW.updated_at <=> OLD.updated_at, CURRENT_TIMESTAMP, NEW.updated_at)",
+                        table
+                    ))
+                    .await?;
+                }
+            }
+            // Column types mean little to SQLite. The naive values are
+            // rewritten in the format the application now writes.
+            DatabaseBackend::Sqlite => {
+                for (table, columns) in COLUMNS {
+                    for (column, _, _) in columns.iter() {
+                        db.execute_unprepared(&format!(
+                            r#"UPDATE "{0}" SET "{1}" = strftime('{2}', "{1}") WHERE "{1}" IS NOT NULL"#,
+                            table, column, SQLITE_FORMAT
+                        ))
+                        .await?;
+                    }
+                }
+
+                for table in TOUCHED_TABLES {
+                    db.execute_unprepared(&format!(
+                        r#"CREATE TRIGGER "{0}_updated_at" AFTER UPDATE ON "{0}"
+                        FOR EACH ROW WHEN NEW.updated_at IS OLD.updated_at
+                        BEGIN
+                            UPDATE "{0}" SET updated_at = strftime('{1}', 'now') WHERE id = NEW.id;
+                        END"#,
+                        table, SQLITE_FORMAT
+                    ))
+                    .await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        let backend = manager.get_database_backend();
+
+        for table in TOUCHED_TABLES {
+            let drop = match backend {
+                DatabaseBackend::Postgres => {
+                    format!(r#"DROP TRIGGER IF EXISTS "{0}_updated_at" ON "{0}""#, table)
+                }
+                DatabaseBackend::MySql => format!("DROP TRIGGER IF EXISTS `{}_updated_at`", table),
+                DatabaseBackend::Sqlite => {
+                    format!(r#"DROP TRIGGER IF EXISTS "{}_updated_at""#, table)
+                }
+            };
+
+            db.execute_unprepared(&drop).await?;
+        }
+
+        match backend {
+            DatabaseBackend::Postgres => {
+                db.execute_unprepared("DROP FUNCTION IF EXISTS set_updated_at()")
+                    .await?;
+
+                for (table, columns) in COLUMNS {
+                    let changes = columns
+                        .iter()
+                        .map(|(column, _, _)| {
+                            format!(
+                                r#"ALTER COLUMN "{0}" TYPE timestamp USING "{0}" AT TIME ZONE 'UTC'"#,
+                                column
+                            )
+                        })
+                        .collect::<Vec<_>>()
+                        .join(", ");
+
+                    db.execute_unprepared(&format!(r#"ALTER TABLE "{}" {}"#, table, changes))
+                        .await?;
+                }
+            }
+            DatabaseBackend::MySql => {
+                for (table, columns) in COLUMNS {
+                    let mut alter = Table::alter();
+                    alter.table(Alias::new(table));
+
+                    for (column, nullable, current) in columns.iter() {
+                        let mut def = ColumnDef::new(Alias::new(*column));
+                        def.timestamp();
+                        column_options(&mut def, *nullable, *current);
+                        alter.modify_column(def);
+                    }
+
+                    manager.alter_table(alter).await?;
+                }
+            }
+            DatabaseBackend::Sqlite => {
+                for (table, columns) in COLUMNS {
+                    for (column, _, _) in columns.iter() {
+                        db.execute_unprepared(&format!(
+                            r#"UPDATE "{0}" SET "{1}" = strftime('%Y-%m-%d %H:%M:%f', "{1}") WHERE "{1}" IS NOT NULL"#,
+                            table, column
+                        ))
+                        .await?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// MySQL drops whatever a modified column definition leaves out.
+fn column_options(def: &mut ColumnDef, nullable: bool, current: bool) {
+    match nullable {
+        true => def.null(),
+        false => def.not_null(),
+    };
+
+    if current {
+        def.default(Expr::current_timestamp());
+    }
+}
@@ -0,0 +1,231 @@
+use sea_orm_migration::{
+    prelude::*,
+    sea_orm::{ConnectionTrait, DatabaseBackend, Statement},
+};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// Indexes by name, table and columns. The unique index on the author and
+/// title of a book doubles as the index for looking books up by author.
+const INDEXES: [(&str, &str, &[&str], bool); 5] = [
+    ("idx-author-user_id", "author", &["user_id"], false),
+    ("idx-author-updated_at", "author", &["updated_at"], false),
+    ("idx-book-user_id", "book", &["user_id"], false),
+    ("idx-book-updated_at", "book", &["updated_at"], false),
+    (
+        "idx-book-author_id-title",
+        "book",
+        &["author_id", "title"],
+        true,
+    ),
+];
+
+/// Check constraints on `book` by name, with `{len}` standing for the
+/// backend's function counting characters and `{row}` for the qualifier of
+/// the row's columns.
+const BOOK_CHECKS: [(&str, &str); 3] = [
+    (
+        "ck-book-title",
+        "{len}(trim({row}title)) > 0 AND {len}({row}title) <= 255",
+    ),
+    ("ck-book-year", "{len}({row}year) <= 32"),
+    ("ck-book-cover", "{len}({row}cover) <= 2048"),
+];
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        let backend = manager.get_database_backend();
+
+        check_existing_rows(db, backend).await?;
+
+        for (name, table, columns, unique) in INDEXES {
+            let mut index = Index::create();
+            index.name(name).table(Alias::new(table));
+
+            for column in columns {
+                index.col(Alias::new(*column));
+            }
+
+            if unique {
+                index.unique();
+            }
+
+            manager.create_index(index).await?;
+        }
+
+        let email_index = match backend {
+            DatabaseBackend::MySql => {
+                "CREATE UNIQUE INDEX `idx-user-email-lower` ON `user` ((lower(email)))"
+            }
+            _ => r#"CREATE UNIQUE INDEX "idx-user-email-lower" ON "user" (lower(email))"#,
+        };
+
+        db.execute_unprepared(email_index).await?;
+
+        match backend {
+            DatabaseBackend::Sqlite => {
+                // SQLite cannot add constraints to an existing table, so
+                // triggers reject the rows the checks would.
+                let condition = book_checks(backend, "NEW.");
+
+                for event in ["INSERT", "UPDATE"] {
+                    db.execute_unprepared(&format!(
+                        r#"CREATE TRIGGER "book_check_{0}" BEFORE {1} ON "book"
+                        FOR EACH ROW WHEN NOT ({2})
+                        BEGIN
+                            SELECT RAISE(ABORT, 'CHECK constraint failed: book');
+                        END"#,
+                        event.to_lowercase(),
+                        event,
+                        condition
+                    ))
+                    .await?;
+                }
+            }
+            _ => {
+                for (name, check) in BOOK_CHECKS {
+                    db.execute_unprepared(&format!(
+                        "ALTER TABLE {} ADD CONSTRAINT {} CHECK ({})",
+                        quote(backend, "book"),
+                        quote(backend, name),
+                        check.replace("{len}", "char_length").replace("{row}", "")
+                    ))
+                    .await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        let backend = manager.get_database_backend();
+
+        match backend {
+            DatabaseBackend::Sqlite => {
+                for event in ["insert", "update"] {
+                    db.execute_unprepared(&format!(
+                        r#"DROP TRIGGER IF EXISTS "book_check_{}""#,
+                        event
+                    ))
+                    .await?;
+                }
+            }
+            _ => {
+                let drop = match backend {
+                    DatabaseBackend::MySql => "DROP CHECK",
+                    _ => "DROP CONSTRAINT",
+                };
+
+                for (name, _) in BOOK_CHECKS {
+                    db.execute_unprepared(&format!(
+                        "ALTER TABLE {} {} {}",
+                        quote(backend, "book"),
+                        drop,
+                        quote(backend, name)
+                    ))
+                    .await?;
+                }
+            }
+        }
+
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx-user-email-lower")
+                    .table(Alias::new("user"))
+                    .to_owned(),
+            )
+            .await?;
+
+        for (name, table, _, _) in INDEXES.iter().rev() {
+            manager
+                .drop_index(
+                    Index::drop()
+                        .name(*name)
+                        .table(Alias::new(*table))
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+fn quote(backend: DatabaseBackend, name: &str) -> String {
+    match backend {
+        DatabaseBackend::MySql => format!("`{}`", name),
+        _ => format!("\"{}\"", name),
+    }
+}
+
+/// Every check on `book`, as one condition on the columns qualified by
+/// `row`.
+fn book_checks(backend: DatabaseBackend, row: &str) -> String {
+    let len = match backend {
+        DatabaseBackend::Sqlite => "length",
+        _ => "char_length",
+    };
+
+    BOOK_CHECKS
+        .iter()
+        .map(|(_, check)| format!("({})", check.replace("{len}", len).replace("{row}", row)))
+        .collect::<Vec<_>>()
+        .join(" AND ")
+}
+
+/// Fails with a description of the rows that would break the new indexes
+/// and checks, which have to be fixed by hand first, rather than with the
+/// database's error on the first of them.
+async fn check_existing_rows(
+    db: &SchemaManagerConnection<'_>,
+    backend: DatabaseBackend,
+) -> Result<(), DbErr> {
+    let queries = [
+        (
+            "email addresses used by several users, ignoring case",
+            format!(
+                "SELECT lower(email) FROM {} GROUP BY lower(email) HAVING count(*) > 1",
+                quote(backend, "user")
+            ),
+        ),
+        (
+            "titles used by several books of one author",
+            "SELECT author_id, title FROM book GROUP BY author_id, title HAVING count(*) > 1"
+                .to_string(),
+        ),
+        (
+            "books with an empty or overlong title, year or cover",
+            format!(
+                "SELECT id FROM book WHERE NOT ({})",
+                book_checks(backend, "")
+            ),
+        ),
+    ];
+
+    let mut problems = Vec::new();
+
+    for (problem, sql) in queries {
+        let rows = db
+            .query_all(Statement::from_string(backend, sql))
+            .await?
+            .len();
+
+        if rows > 0 {
+            problems.push(format!("{} ({})", problem, rows));
+        }
+    }
+
+    match problems.is_empty() {
+        true => Ok(()),
+        false => Err(DbErr::Migration(format!(
+            "Fix these rows before migrating: {}.",
+            problems.join(", ")
+        ))),
+    }
+}
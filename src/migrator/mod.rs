@@ -3,6 +3,16 @@ pub use sea_orm_migration::prelude::*;
 mod m20220101_000001_create_user_table;
 mod m20250523_142601_create_author_table;
 mod m20250523_143635_create_book_table;
+mod m20261019_090000_add_account_fields_to_user;
+mod m20261019_120000_create_audit_event_table;
+mod m20261019_150000_create_api_key_table;
+mod m20261020_090000_create_user_identity_table;
+mod m20261020_120000_create_oauth_tables;
+mod m20261020_150000_create_session_table;
+mod m20261021_090000_add_admin_fields_to_user;
+mod m20261022_090000_use_timestamptz;
+mod m20261022_120000_add_indexes_and_constraints;
+mod m20261023_090000_add_redirect_uri_sent_to_oauth_authorization_code;
 
 pub struct Migrator;
 
@@ -13,6 +23,16 @@ impl MigratorTrait for Migrator {
             Box::new(m20220101_000001_create_user_table::Migration),
             Box::new(m20250523_142601_create_author_table::Migration),
             Box::new(m20250523_143635_create_book_table::Migration),
+            Box::new(m20261019_090000_add_account_fields_to_user::Migration),
+            Box::new(m20261019_120000_create_audit_event_table::Migration),
+            Box::new(m20261019_150000_create_api_key_table::Migration),
+            Box::new(m20261020_090000_create_user_identity_table::Migration),
+            Box::new(m20261020_120000_create_oauth_tables::Migration),
+            Box::new(m20261020_150000_create_session_table::Migration),
+            Box::new(m20261021_090000_add_admin_fields_to_user::Migration),
+            Box::new(m20261022_090000_use_timestamptz::Migration),
+            Box::new(m20261022_120000_add_indexes_and_constraints::Migration),
+            Box::new(m20261023_090000_add_redirect_uri_sent_to_oauth_authorization_code::Migration),
         ]
     }
 }
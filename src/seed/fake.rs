@@ -0,0 +1,304 @@
+use std::{collections::HashSet, time::SystemTime};
+
+use rand::{Rng, SeedableRng, rngs::StdRng, seq::SliceRandom};
+use sea_orm::{
+    ActiveValue::Set, ColumnTrait, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter,
+    QueryOrder, QuerySelect, QueryTrait, TransactionTrait, prelude::DateTimeUtc,
+};
+
+use super::{UserFixture, upsert_user};
+use crate::{
+    audit,
+    entities::{audit_event, author, book, prelude::*},
+};
+
+/// The audit event marking an account as created by the generator, whose
+/// data it may replace.
+const OWNER_CREATED: &str = "fake_data_owner_created";
+
+/// Rows per insert statement, well below the bind parameter limit of every
+/// backend.
+const BATCH_SIZE: usize = 500;
+
+const FIRSTNAMES: [&str; 24] = [
+    "Ada",
+    "Alice",
+    "Amos",
+    "Ursula",
+    "Italo",
+    "Jorge",
+    "Clarice",
+    "Doris",
+    "Elena",
+    "Fernando",
+    "Gabriel",
+    "Hilda",
+    "Isabel",
+    "Jun",
+    "Kazuo",
+    "Leo",
+    "Marguerite",
+    "Naguib",
+    "Olga",
+    "Primo",
+    "Rosa",
+    "Selma",
+    "Toni",
+    "Virginia",
+];
+
+const LASTNAMES: [&str; 24] = [
+    "Achebe",
+    "Borges",
+    "Calvino",
+    "Duras",
+    "Eco",
+    "Ferrante",
+    "Grass",
+    "Hesse",
+    "Ishiguro",
+    "Jansson",
+    "Kafka",
+    "Lispector",
+    "Mahfouz",
+    "Nabokov",
+    "Oz",
+    "Pessoa",
+    "Queneau",
+    "Rulfo",
+    "Saramago",
+    "Tokarczuk",
+    "Undset",
+    "Vonnegut",
+    "Woolf",
+    "Yourcenar",
+];
+
+const ADJECTIVES: [&str; 16] = [
+    "Invisible",
+    "Silent",
+    "Distant",
+    "Broken",
+    "Golden",
+    "Hidden",
+    "Last",
+    "Lost",
+    "Northern",
+    "Quiet",
+    "Red",
+    "Secret",
+    "Shifting",
+    "Sleeping",
+    "Wandering",
+    "Winter",
+];
+
+const NOUNS: [&str; 16] = [
+    "Cities",
+    "Garden",
+    "Harbour",
+    "Island",
+    "Library",
+    "Lighthouse",
+    "Map",
+    "Mirror",
+    "Orchard",
+    "River",
+    "Season",
+    "Shadow",
+    "Station",
+    "Tower",
+    "Voyage",
+    "Years",
+];
+
+pub struct FakeOptions {
+    /// The owner of the generated data, created when missing. Accounts the
+    /// generator did not create are refused.
+    pub email: String,
+    pub authors: u32,
+    pub books_per_author: u32,
+    /// The same seed always generates the same data.
+    pub seed: u64,
+}
+
+#[derive(Debug)]
+pub struct FakeReport {
+    pub authors: usize,
+    pub books: usize,
+    /// The password of the owner, when it was created.
+    pub generated_password: Option<String>,
+}
+
+/// Generates authors and books for performance testing the list endpoints.
+/// What was generated before is deleted first, so generating again gives a
+/// dataset of the same size instead of growing it. Authors that other users
+/// filed books under are kept, with those books.
+pub async fn generate(
+    db: &DatabaseConnection,
+    options: &FakeOptions,
+) -> Result<FakeReport, String> {
+    let mut rng = StdRng::seed_from_u64(options.seed);
+    let mut generated_passwords = Vec::new();
+
+    let txn = db.begin().await.map_err(|err| err.to_string())?;
+
+    let existing = User::find_by_email(&options.email)
+        .one(&txn)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    let user_id = match existing {
+        Some(u) => {
+            let generated = AuditEvent::find()
+                .filter(audit_event::Column::UserId.eq(u.id))
+                .filter(audit_event::Column::Action.eq(OWNER_CREATED))
+                .count(&txn)
+                .await
+                .map_err(|err| err.to_string())?;
+
+            if generated == 0 {
+                return Err(format!(
+                    "{} was not created by the generator, pick another user.",
+                    options.email
+                ));
+            }
+
+            u.id
+        }
+        None => {
+            let owner = UserFixture {
+                email: options.email.to_owned(),
+                password: None,
+                firstname: "Fake".to_string(),
+                lastname: "Data".to_string(),
+                role: None,
+            };
+
+            let (user_id, _) = upsert_user(&txn, &owner, &mut generated_passwords).await?;
+
+            audit::record(&txn, user_id, OWNER_CREATED, None)
+                .await
+                .map_err(|err| err.to_string())?;
+
+            user_id
+        }
+    };
+
+    Book::delete_many()
+        .filter(book::Column::UserId.eq(user_id))
+        .exec(&txn)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    let kept_authors = Book::find()
+        .select_only()
+        .column(book::Column::AuthorId)
+        .into_query();
+
+    Author::delete_many()
+        .filter(author::Column::UserId.eq(user_id))
+        .filter(author::Column::Id.not_in_subquery(kept_authors))
+        .exec(&txn)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    // Authors still there are kept for the books of other users.
+    let kept_authors: Vec<i32> = Author::find()
+        .select_only()
+        .column(author::Column::Id)
+        .filter(author::Column::UserId.eq(user_id))
+        .into_tuple()
+        .all(&txn)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    // Bulk inserts skip `ActiveModelBehavior`, which stamps other records.
+    let now = DateTimeUtc::from(SystemTime::now());
+
+    let authors = (0..options.authors)
+        .map(|_| author::ActiveModel {
+            user_id: Set(user_id),
+            firstname: Set(pick(&mut rng, &FIRSTNAMES)),
+            lastname: Set(pick(&mut rng, &LASTNAMES)),
+            bio: Set(format!(
+                "Wrote about {} {}.",
+                pick(&mut rng, &ADJECTIVES).to_lowercase(),
+                pick(&mut rng, &NOUNS).to_lowercase()
+            )),
+            created_at: Set(now),
+            updated_at: Set(now),
+            ..Default::default()
+        })
+        .collect::<Vec<_>>();
+
+    for batch in authors.chunks(BATCH_SIZE) {
+        Author::insert_many(batch.to_vec())
+            .exec(&txn)
+            .await
+            .map_err(|err| err.to_string())?;
+    }
+
+    let author_ids: Vec<i32> = Author::find()
+        .select_only()
+        .column(author::Column::Id)
+        .filter(author::Column::UserId.eq(user_id))
+        .filter(author::Column::Id.is_not_in(kept_authors))
+        .order_by_asc(author::Column::Id)
+        .into_tuple()
+        .all(&txn)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    let mut books = Vec::new();
+
+    for &author_id in &author_ids {
+        // An author's titles are unique, repeats get a volume number.
+        let mut titles = HashSet::new();
+
+        for _ in 0..options.books_per_author {
+            let base = format!(
+                "The {} {}",
+                pick(&mut rng, &ADJECTIVES),
+                pick(&mut rng, &NOUNS)
+            );
+            let mut title = base.clone();
+            let mut volume = 2;
+
+            while !titles.insert(title.clone()) {
+                title = format!("{} {}", base, volume);
+                volume += 1;
+            }
+
+            books.push(book::ActiveModel {
+                user_id: Set(user_id),
+                author_id: Set(author_id),
+                title: Set(title),
+                year: Set(rng.gen_range(1850..=2025).to_string()),
+                cover: Set(String::new()),
+                created_at: Set(now),
+                updated_at: Set(now),
+                ..Default::default()
+            });
+        }
+    }
+
+    for batch in books.chunks(BATCH_SIZE) {
+        Book::insert_many(batch.to_vec())
+            .exec(&txn)
+            .await
+            .map_err(|err| err.to_string())?;
+    }
+
+    txn.commit().await.map_err(|err| err.to_string())?;
+
+    Ok(FakeReport {
+        authors: author_ids.len(),
+        books: books.len(),
+        generated_password: generated_passwords.pop().map(|(_, password)| password),
+    })
+}
+
+fn pick(rng: &mut StdRng, words: &[&str]) -> String {
+    words.choose(rng).unwrap().to_string()
+}
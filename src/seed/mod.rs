@@ -0,0 +1,444 @@
+use std::{collections::BTreeMap, fmt, path::PathBuf};
+
+use bcrypt::{DEFAULT_COST, hash};
+use rocket::{
+    figment::{
+        Figment,
+        providers::{Format, Json, Yaml},
+    },
+    serde::{Deserialize, Deserializer},
+};
+use sea_orm::{
+    ActiveModelTrait,
+    ActiveValue::{self, Set},
+    ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, QueryFilter, TransactionTrait,
+};
+
+use crate::{
+    auth::{ROLES, random_token},
+    entities::{author, book, prelude::*, user},
+};
+
+pub mod fake;
+
+/// The demo data loaded by `seed` when it is given no fixture files.
+const DEMO: &str = include_str!("demo.yaml");
+
+/// Users, authors and books to load, each under a key of its own. Records
+/// point at each other by key, so fixtures never depend on database ids:
+///
+/// ```yaml
+/// users:
+///   demo: { email: demo@example.com, firstname: Demo, lastname: User }
+/// authors:
+///   calvino: { user: demo, firstname: Italo, lastname: Calvino }
+/// books:
+///   invisible_cities: { author: calvino, title: Invisible Cities, year: 1972 }
+/// ```
+#[derive(Default, Deserialize)]
+#[serde(crate = "rocket::serde", default)]
+pub struct Fixtures {
+    pub users: BTreeMap<String, UserFixture>,
+    pub authors: BTreeMap<String, AuthorFixture>,
+    pub books: BTreeMap<String, BookFixture>,
+}
+
+/// A user, matched with an existing one by email address.
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct UserFixture {
+    pub email: String,
+    /// Only used when the user is created. A random password is generated
+    /// and reported when left out.
+    #[serde(default)]
+    pub password: Option<String>,
+    pub firstname: String,
+    pub lastname: String,
+    /// Left alone when not given.
+    #[serde(default)]
+    pub role: Option<String>,
+}
+
+/// An author, matched with an existing one by owner and name.
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct AuthorFixture {
+    /// The key of the owning user.
+    pub user: String,
+    pub firstname: String,
+    pub lastname: String,
+    #[serde(default)]
+    pub bio: String,
+}
+
+/// A book, matched with an existing one by author and title.
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct BookFixture {
+    /// The key of the author.
+    pub author: String,
+    /// The key of the owning user, the author's owner when left out.
+    #[serde(default)]
+    pub user: Option<String>,
+    pub title: String,
+    #[serde(default, deserialize_with = "text")]
+    pub year: String,
+    #[serde(default)]
+    pub cover: String,
+}
+
+/// Deserializes a string, also taking the number a bare `1972` in YAML
+/// turns into.
+fn text<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(crate = "rocket::serde", untagged)]
+    enum Text {
+        String(String),
+        Number(i64),
+    }
+
+    Ok(match Text::deserialize(deserializer)? {
+        Text::String(text) => text,
+        Text::Number(number) => number.to_string(),
+    })
+}
+
+impl Fixtures {
+    pub fn demo() -> Result<Self, String> {
+        Self::from_yaml(DEMO)
+    }
+
+    pub fn from_yaml(yaml: &str) -> Result<Self, String> {
+        Self::extract(Figment::from(Yaml::string(yaml)))
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        Self::extract(Figment::from(Json::string(json)))
+    }
+
+    /// Reads fixture files, YAML or JSON by extension. They are merged in
+    /// order, so a later file can add to or override the records of an
+    /// earlier one under the same key.
+    pub fn read(paths: &[PathBuf]) -> Result<Self, String> {
+        let mut figment = Figment::new();
+
+        for path in paths {
+            if !path.is_file() {
+                return Err(format!("No fixture file `{}`.", path.display()));
+            }
+
+            figment = match path.extension().and_then(|ext| ext.to_str()) {
+                Some("yaml" | "yml") => figment.merge(Yaml::file_exact(path)),
+                Some("json") => figment.merge(Json::file_exact(path)),
+                _ => {
+                    return Err(format!(
+                        "`{}` is not a .yaml, .yml or .json file.",
+                        path.display()
+                    ));
+                }
+            };
+        }
+
+        Self::extract(figment)
+    }
+
+    fn extract(figment: Figment) -> Result<Self, String> {
+        let fixtures: Self = figment.extract().map_err(|errors| {
+            errors
+                .into_iter()
+                .map(|err| err.to_string())
+                .collect::<Vec<_>>()
+                .join("\n")
+        })?;
+
+        fixtures.validate()?;
+
+        Ok(fixtures)
+    }
+
+    /// Checks every link and role up front, describing every problem found,
+    /// so a broken fixture writes nothing.
+    fn validate(&self) -> Result<(), String> {
+        let mut errors = Vec::new();
+
+        for (key, u) in &self.users {
+            if let Some(role) = &u.role
+                && !ROLES.contains(&role.as_str())
+            {
+                errors.push(format!(
+                    "users.{}: role must be one of {}",
+                    key,
+                    ROLES.join(", ")
+                ));
+            }
+        }
+
+        for (key, a) in &self.authors {
+            if !self.users.contains_key(&a.user) {
+                errors.push(format!("authors.{}: no user `{}`", key, a.user));
+            }
+        }
+
+        for (key, b) in &self.books {
+            if !self.authors.contains_key(&b.author) {
+                errors.push(format!("books.{}: no author `{}`", key, b.author));
+            }
+
+            if let Some(u) = &b.user
+                && !self.users.contains_key(u)
+            {
+                errors.push(format!("books.{}: no user `{}`", key, u));
+            }
+        }
+
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(errors.join("\n")),
+        }
+    }
+}
+
+/// What happened to the records of one kind.
+#[derive(Debug, Default, PartialEq)]
+pub struct Counts {
+    pub created: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+}
+
+impl Counts {
+    fn record(&mut self, outcome: Outcome) {
+        match outcome {
+            Outcome::Created => self.created += 1,
+            Outcome::Updated => self.updated += 1,
+            Outcome::Unchanged => self.unchanged += 1,
+        }
+    }
+}
+
+impl fmt::Display for Counts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} created, {} updated, {} unchanged",
+            self.created, self.updated, self.unchanged
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SeedReport {
+    pub users: Counts,
+    pub authors: Counts,
+    pub books: Counts,
+    /// The email address and password of every user created with a
+    /// generated password.
+    pub generated_passwords: Vec<(String, String)>,
+}
+
+enum Outcome {
+    Created,
+    Updated,
+    Unchanged,
+}
+
+/// Sets a field of a loaded model, leaving it unchanged when it already
+/// holds the value, so untouched records are not written.
+fn assign<V>(field: &mut ActiveValue<V>, value: V)
+where
+    V: Into<sea_orm::Value> + PartialEq,
+{
+    if !matches!(field, ActiveValue::Unchanged(current) if *current == value) {
+        *field = Set(value);
+    }
+}
+
+/// Creates the records of the fixtures, or updates those that exist, in a
+/// single transaction. Loading the same fixtures again changes nothing.
+pub async fn load(db: &DatabaseConnection, fixtures: &Fixtures) -> Result<SeedReport, String> {
+    let txn = db.begin().await.map_err(|err| err.to_string())?;
+
+    let mut report = SeedReport::default();
+    let mut user_ids = BTreeMap::new();
+    let mut authors = BTreeMap::new();
+
+    for (key, fixture) in &fixtures.users {
+        let (id, outcome) = upsert_user(&txn, fixture, &mut report.generated_passwords)
+            .await
+            .map_err(|err| format!("users.{}: {}", key, err))?;
+
+        user_ids.insert(key.as_str(), id);
+        report.users.record(outcome);
+    }
+
+    for (key, fixture) in &fixtures.authors {
+        let user_id = user_ids[fixture.user.as_str()];
+
+        let (id, outcome) = upsert_author(&txn, user_id, fixture)
+            .await
+            .map_err(|err| format!("authors.{}: {}", key, err))?;
+
+        authors.insert(key.as_str(), (id, user_id));
+        report.authors.record(outcome);
+    }
+
+    for (key, fixture) in &fixtures.books {
+        let (author_id, owner_id) = authors[fixture.author.as_str()];
+        let user_id = match &fixture.user {
+            Some(u) => user_ids[u.as_str()],
+            None => owner_id,
+        };
+
+        let outcome = upsert_book(&txn, user_id, author_id, fixture)
+            .await
+            .map_err(|err| format!("books.{}: {}", key, err))?;
+
+        report.books.record(outcome);
+    }
+
+    txn.commit().await.map_err(|err| err.to_string())?;
+
+    Ok(report)
+}
+
+async fn upsert_user(
+    db: &impl ConnectionTrait,
+    fixture: &UserFixture,
+    generated_passwords: &mut Vec<(String, String)>,
+) -> Result<(i32, Outcome), String> {
+    let existing = User::find_by_email(&fixture.email)
+        .one(db)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    let Some(u) = existing else {
+        let password = match &fixture.password {
+            Some(password) => password.to_owned(),
+            None => {
+                let password = random_token(16);
+                generated_passwords.push((fixture.email.to_owned(), password.to_owned()));
+                password
+            }
+        };
+
+        let mut u = user::ActiveModel {
+            email: Set(fixture.email.to_owned()),
+            password: Set(hash(&password, DEFAULT_COST).map_err(|err| err.to_string())?),
+            firstname: Set(fixture.firstname.to_owned()),
+            lastname: Set(fixture.lastname.to_owned()),
+            ..Default::default()
+        };
+
+        if let Some(role) = &fixture.role {
+            u.role = Set(role.to_owned());
+        }
+
+        let u = u.insert(db).await.map_err(|err| err.to_string())?;
+
+        return Ok((u.id, Outcome::Created));
+    };
+
+    let id = u.id;
+    let mut u: user::ActiveModel = u.into();
+
+    assign(&mut u.firstname, fixture.firstname.to_owned());
+    assign(&mut u.lastname, fixture.lastname.to_owned());
+
+    if let Some(role) = &fixture.role {
+        assign(&mut u.role, role.to_owned());
+    }
+
+    if !u.is_changed() {
+        return Ok((id, Outcome::Unchanged));
+    }
+
+    u.update(db).await.map_err(|err| err.to_string())?;
+
+    Ok((id, Outcome::Updated))
+}
+
+async fn upsert_author(
+    db: &impl ConnectionTrait,
+    user_id: i32,
+    fixture: &AuthorFixture,
+) -> Result<(i32, Outcome), String> {
+    let existing = Author::find()
+        .filter(author::Column::UserId.eq(user_id))
+        .filter(author::Column::Firstname.eq(&fixture.firstname))
+        .filter(author::Column::Lastname.eq(&fixture.lastname))
+        .one(db)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    let Some(a) = existing else {
+        let a = author::ActiveModel {
+            user_id: Set(user_id),
+            firstname: Set(fixture.firstname.to_owned()),
+            lastname: Set(fixture.lastname.to_owned()),
+            bio: Set(fixture.bio.to_owned()),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+        .map_err(|err| err.to_string())?;
+
+        return Ok((a.id, Outcome::Created));
+    };
+
+    let id = a.id;
+    let mut a: author::ActiveModel = a.into();
+
+    assign(&mut a.bio, fixture.bio.to_owned());
+
+    if !a.is_changed() {
+        return Ok((id, Outcome::Unchanged));
+    }
+
+    a.update(db).await.map_err(|err| err.to_string())?;
+
+    Ok((id, Outcome::Updated))
+}
+
+async fn upsert_book(
+    db: &impl ConnectionTrait,
+    user_id: i32,
+    author_id: i32,
+    fixture: &BookFixture,
+) -> Result<Outcome, String> {
+    let existing = Book::find()
+        .filter(book::Column::AuthorId.eq(author_id))
+        .filter(book::Column::Title.eq(&fixture.title))
+        .one(db)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    let Some(b) = existing else {
+        book::ActiveModel {
+            user_id: Set(user_id),
+            author_id: Set(author_id),
+            title: Set(fixture.title.to_owned()),
+            year: Set(fixture.year.to_owned()),
+            cover: Set(fixture.cover.to_owned()),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+        .map_err(|err| err.to_string())?;
+
+        return Ok(Outcome::Created);
+    };
+
+    let mut b: book::ActiveModel = b.into();
+
+    assign(&mut b.user_id, user_id);
+    assign(&mut b.year, fixture.year.to_owned());
+    assign(&mut b.cover, fixture.cover.to_owned());
+
+    if !b.is_changed() {
+        return Ok(Outcome::Unchanged);
+    }
+
+    b.update(db).await.map_err(|err| err.to_string())?;
+
+    Ok(Outcome::Updated)
+}
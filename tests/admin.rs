@@ -0,0 +1,78 @@
+mod common;
+
+use common::TestApp;
+use rocket::{http::Status, serde::json::json};
+
+async fn user_id(app: &TestApp, token: &str) -> i64 {
+    app.get("/auth/me", token).await.body["id"]
+        .as_i64()
+        .unwrap()
+}
+
+#[rocket::async_test]
+async fn only_admins_manage_users() {
+    let app = TestApp::spawn().await;
+    let token = app.user("reader@example.com").await;
+
+    let res = app.get("/admin/users", &token).await;
+
+    assert_eq!(res.status, Status::Forbidden);
+}
+
+#[rocket::async_test]
+async fn impersonation_acts_as_the_user() {
+    let app = TestApp::spawn().await;
+    let admin = app.admin("admin@example.com").await;
+    let reader = app.user("reader@example.com").await;
+    let reader_id = user_id(&app, &reader).await;
+
+    let res = app
+        .post(
+            &format!("/admin/users/{}/impersonate", reader_id),
+            &admin,
+            json!({}),
+        )
+        .await;
+    assert_eq!(res.status, Status::Ok, "{}", res.body);
+    let token = res.body["token"].as_str().unwrap();
+
+    let res = app.get("/auth/me", token).await;
+    assert_eq!(res.body["email"], "reader@example.com");
+
+    // The admin's powers do not come along.
+    let res = app.get("/admin/users", token).await;
+    assert_eq!(res.status, Status::Forbidden);
+}
+
+#[rocket::async_test]
+async fn impersonation_ends_when_the_admin_is_demoted() {
+    let app = TestApp::spawn().await;
+    let admin = app.admin("admin@example.com").await;
+    let other_admin = app.admin("other-admin@example.com").await;
+    let reader = app.user("reader@example.com").await;
+    let admin_id = user_id(&app, &admin).await;
+    let reader_id = user_id(&app, &reader).await;
+
+    let res = app
+        .post(
+            &format!("/admin/users/{}/impersonate", reader_id),
+            &admin,
+            json!({}),
+        )
+        .await;
+    let token = res.body["token"].as_str().unwrap().to_string();
+
+    let res = app
+        .put(
+            &format!("/admin/users/{}/role", admin_id),
+            &other_admin,
+            json!({ "role": "user" }),
+        )
+        .await;
+    assert_eq!(res.status, Status::Ok, "{}", res.body);
+
+    assert_eq!(
+        app.get("/auth/me", &token).await.status,
+        Status::Unauthorized
+    );
+}
@@ -0,0 +1,97 @@
+mod common;
+
+use common::TestApp;
+use rocket::{http::Status, serde::json::json};
+
+async fn api_key(app: &TestApp, token: &str, scopes: Option<&[&str]>) -> (String, i64) {
+    let res = app
+        .post(
+            "/auth/api-keys",
+            token,
+            json!({ "name": "Import script", "scopes": scopes }),
+        )
+        .await;
+    assert_eq!(res.status, Status::Created, "{}", res.body);
+
+    (
+        res.body["key"].as_str().unwrap().to_string(),
+        res.body["id"].as_i64().unwrap(),
+    )
+}
+
+#[rocket::async_test]
+async fn scoped_keys_only_reach_their_routes() {
+    let app = TestApp::spawn().await;
+    let token = app.user("reader@example.com").await;
+    let (key, _) = api_key(&app, &token, Some(&["authors:read"])).await;
+
+    assert_eq!(app.get("/authors", &key).await.status, Status::Ok);
+    assert_eq!(app.get("/books", &key).await.status, Status::Forbidden);
+    assert_eq!(app.get("/auth/me", &key).await.status, Status::Forbidden);
+
+    let res = app
+        .post(
+            "/authors",
+            &key,
+            json!({ "firstname": "Italo", "lastname": "Calvino", "bio": "" }),
+        )
+        .await;
+    assert_eq!(res.status, Status::Forbidden);
+}
+
+#[rocket::async_test]
+async fn unrestricted_keys_act_as_the_user() {
+    let app = TestApp::spawn().await;
+    let token = app.user("reader@example.com").await;
+    let (key, _) = api_key(&app, &token, None).await;
+
+    let res = app.get("/auth/me", &key).await;
+
+    assert_eq!(res.body["email"], "reader@example.com");
+}
+
+#[rocket::async_test]
+async fn deleted_keys_stop_working() {
+    let app = TestApp::spawn().await;
+    let token = app.user("reader@example.com").await;
+    let (key, id) = api_key(&app, &token, Some(&["books:read"])).await;
+
+    let res = app.delete(&format!("/auth/api-keys/{}", id), &token).await;
+    assert_eq!(res.status, Status::Ok);
+
+    assert_eq!(app.get("/books", &key).await.status, Status::Unauthorized);
+}
+
+#[rocket::async_test]
+async fn rejects_unknown_scopes() {
+    let app = TestApp::spawn().await;
+    let token = app.user("reader@example.com").await;
+
+    let res = app
+        .post(
+            "/auth/api-keys",
+            &token,
+            json!({ "name": "Admin script", "scopes": ["admin"] }),
+        )
+        .await;
+
+    assert_eq!(res.status, Status::BadRequest);
+}
+
+#[rocket::async_test]
+async fn rejects_out_of_range_expiry() {
+    let app = TestApp::spawn().await;
+    let token = app.user("reader@example.com").await;
+
+    for days in [0, 3651, u64::MAX] {
+        let res = app
+            .post(
+                "/auth/api-keys",
+                &token,
+                json!({ "name": "Import script", "expires_in_days": days }),
+            )
+            .await;
+
+        assert_eq!(res.status, Status::BadRequest, "{} days", days);
+    }
+}
@@ -0,0 +1,158 @@
+mod common;
+
+use common::{PASSWORD, TestApp};
+use rocket::{
+    http::{ContentType, Header, Method, Status},
+    serde::json::json,
+};
+
+#[rocket::async_test]
+async fn signs_up_and_in() {
+    let app = TestApp::spawn().await;
+    let token = app.user("reader@example.com").await;
+
+    let res = app.get("/auth/me", &token).await;
+
+    assert_eq!(res.status, Status::Ok);
+    assert_eq!(res.body["email"], "reader@example.com");
+}
+
+#[rocket::async_test]
+async fn rejects_a_wrong_password() {
+    let app = TestApp::spawn().await;
+    app.sign_up("reader@example.com", PASSWORD).await;
+
+    let res = app.sign_in("reader@example.com", "wrong password").await;
+
+    assert_eq!(res.status, Status::Unauthorized);
+    assert_eq!(res.body["error"], "Invalid credentials");
+}
+
+#[rocket::async_test]
+async fn rejects_a_second_account_with_the_same_email() {
+    let app = TestApp::spawn().await;
+    app.sign_up("reader@example.com", PASSWORD).await;
+
+    let res = app.sign_up("reader@example.com", PASSWORD).await;
+    assert_eq!(res.status, Status::Conflict);
+
+    let res = app.sign_up("Reader@Example.com", PASSWORD).await;
+    assert_eq!(res.status, Status::Conflict);
+}
+
+#[rocket::async_test]
+async fn signs_in_whatever_the_case_of_the_email() {
+    let app = TestApp::spawn().await;
+    app.sign_up("reader@example.com", PASSWORD).await;
+
+    let res = app.sign_in("READER@example.com", PASSWORD).await;
+
+    assert_eq!(res.status, Status::Ok);
+}
+
+#[rocket::async_test]
+async fn requires_a_token() {
+    let app = TestApp::spawn().await;
+
+    for uri in ["/auth/me", "/authors", "/books"] {
+        let res = app.request(Method::Get, uri, None, None).await;
+
+        assert_eq!(res.status, Status::Unauthorized, "{}", uri);
+    }
+}
+
+#[rocket::async_test]
+async fn accepts_the_bearer_scheme_in_any_case() {
+    let app = TestApp::spawn().await;
+    let token = app.user("reader@example.com").await;
+
+    for scheme in ["bearer", "BEARER", "Bearer"] {
+        let res = app
+            .client
+            .get("/auth/me")
+            .header(Header::new(
+                "Authorization",
+                format!("{} {}", scheme, token),
+            ))
+            .dispatch()
+            .await;
+
+        assert_eq!(res.status(), Status::Ok, "{}", scheme);
+    }
+}
+
+#[rocket::async_test]
+async fn flags_the_token_header_as_deprecated() {
+    let app = TestApp::spawn().await;
+    let token = app.user("reader@example.com").await;
+
+    let res = app
+        .client
+        .get("/auth/me")
+        .header(Header::new("token", token.to_owned()))
+        .dispatch()
+        .await;
+    assert_eq!(res.status(), Status::Ok);
+    assert!(res.headers().get_one("Deprecation").is_some());
+
+    let res = app
+        .client
+        .get("/auth/me")
+        .header(Header::new("Authorization", format!("Bearer {}", token)))
+        .dispatch()
+        .await;
+    assert!(res.headers().get_one("Deprecation").is_none());
+}
+
+#[rocket::async_test]
+async fn cookie_sessions_need_the_csrf_token_to_write() {
+    let app = TestApp::spawn_with(
+        common::figment()
+            .merge(("auth_cookie", true))
+            .merge(("auth_cookie_secure", false)),
+    )
+    .await;
+    app.sign_up("reader@example.com", PASSWORD).await;
+    app.sign_in("reader@example.com", PASSWORD).await;
+
+    let res = app.client.get("/auth/me").dispatch().await;
+    assert_eq!(res.status(), Status::Ok);
+
+    let author = json!({ "firstname": "Italo", "lastname": "Calvino", "bio": "" }).to_string();
+    let res = app
+        .client
+        .post("/authors")
+        .header(ContentType::JSON)
+        .body(&author)
+        .dispatch()
+        .await;
+    assert_eq!(res.status(), Status::Unauthorized);
+
+    let csrf = app
+        .client
+        .cookies()
+        .get("csrf_token")
+        .unwrap()
+        .value()
+        .to_string();
+    let res = app
+        .client
+        .post("/authors")
+        .header(ContentType::JSON)
+        .header(Header::new("X-CSRF-Token", csrf.to_owned()))
+        .body(&author)
+        .dispatch()
+        .await;
+    assert_eq!(res.status(), Status::Created);
+
+    let res = app
+        .client
+        .post("/auth/sign-out")
+        .header(Header::new("X-CSRF-Token", csrf))
+        .dispatch()
+        .await;
+    assert_eq!(res.status(), Status::Ok);
+
+    let res = app.client.get("/auth/me").dispatch().await;
+    assert_eq!(res.status(), Status::Unauthorized);
+}
@@ -0,0 +1,133 @@
+mod common;
+
+use bookstore::entities::{author, prelude::*};
+use common::TestApp;
+use rocket::{
+    http::Status,
+    serde::json::{Value, json},
+};
+use sea_orm::{
+    ColumnTrait, EntityTrait, QueryFilter,
+    prelude::{DateTimeUtc, DateTimeWithTimeZone, Expr},
+};
+
+#[rocket::async_test]
+async fn creates_and_lists_authors() {
+    let app = TestApp::spawn().await;
+    let token = app.user("reader@example.com").await;
+
+    let author = app.create_author(&token, "Ursula", "Le Guin").await;
+    app.create_author(&token, "Italo", "Calvino").await;
+
+    let res = app.get("/authors", &token).await;
+
+    assert_eq!(res.status, Status::Ok);
+    assert_eq!(res.body["total"], 2);
+
+    let res = app.get(&format!("/authors/{}", author["id"]), &token).await;
+
+    assert_eq!(res.body["firstname"], "Ursula");
+    assert_eq!(res.body["lastname"], "Le Guin");
+}
+
+#[rocket::async_test]
+async fn updates_an_author() {
+    let app = TestApp::spawn().await;
+    let token = app.user("reader@example.com").await;
+    let author = app.create_author(&token, "Ursula", "Le Guin").await;
+
+    let res = app
+        .put(
+            &format!("/authors/{}", author["id"]),
+            &token,
+            json!({ "firstname": "Ursula K.", "lastname": "Le Guin", "bio": "Novelist." }),
+        )
+        .await;
+
+    assert_eq!(res.status, Status::Ok);
+    assert_eq!(res.body["firstname"], "Ursula K.");
+    assert_eq!(res.body["bio"], "Novelist.");
+}
+
+#[rocket::async_test]
+async fn deletes_an_author() {
+    let app = TestApp::spawn().await;
+    let token = app.user("reader@example.com").await;
+    let author = app.create_author(&token, "Ursula", "Le Guin").await;
+    let uri = format!("/authors/{}", author["id"]);
+
+    let res = app.delete(&uri, &token).await;
+    assert_eq!(res.status, Status::Ok);
+
+    let res = app.get(&uri, &token).await;
+    assert_eq!(res.status, Status::NotFound);
+}
+
+#[rocket::async_test]
+async fn keeps_an_author_with_books() {
+    let app = TestApp::spawn().await;
+    let token = app.user("reader@example.com").await;
+    let author = app.create_author(&token, "Ursula", "Le Guin").await;
+    let book = app
+        .create_book(&token, author["id"].as_i64().unwrap(), "The Dispossessed")
+        .await;
+
+    let res = app
+        .delete(&format!("/authors/{}", author["id"]), &token)
+        .await;
+    assert_eq!(res.status, Status::Conflict);
+
+    let res = app.get(&format!("/books/{}", book["id"]), &token).await;
+    assert_eq!(res.body["title"], "The Dispossessed");
+}
+
+#[rocket::async_test]
+async fn lists_the_books_of_an_author() {
+    let app = TestApp::spawn().await;
+    let token = app.user("reader@example.com").await;
+    let author = app.create_author(&token, "Italo", "Calvino").await;
+    let other = app.create_author(&token, "Ursula", "Le Guin").await;
+
+    let id = author["id"].as_i64().unwrap();
+    app.create_book(&token, id, "Invisible Cities").await;
+    app.create_book(&token, id, "Mr. Palomar").await;
+    app.create_book(&token, other["id"].as_i64().unwrap(), "The Lathe of Heaven")
+        .await;
+
+    let res = app.get(&format!("/authors/{}/books", id), &token).await;
+
+    assert_eq!(res.status, Status::Ok);
+    assert_eq!(res.body["total"], 2);
+}
+
+#[rocket::async_test]
+async fn stamps_updates() {
+    let app = TestApp::spawn().await;
+    let token = app.user("reader@example.com").await;
+    let author = app.create_author(&token, "Ursula", "Le Guin").await;
+
+    let time =
+        |value: &Value| DateTimeWithTimeZone::parse_from_rfc3339(value.as_str().unwrap()).unwrap();
+
+    assert_eq!(time(&author["created_at"]), time(&author["updated_at"]));
+
+    // Backdated, so the update is later however fast it comes.
+    let earlier: DateTimeUtc = "2000-01-01T00:00:00Z".parse().unwrap();
+    Author::update_many()
+        .col_expr(author::Column::UpdatedAt, Expr::value(earlier))
+        .filter(author::Column::Id.eq(author["id"].as_i64().unwrap() as i32))
+        .exec(&app.db)
+        .await
+        .unwrap();
+
+    let res = app
+        .put(
+            &format!("/authors/{}", author["id"]),
+            &token,
+            json!({ "firstname": "Ursula K.", "lastname": "Le Guin", "bio": "" }),
+        )
+        .await;
+
+    assert_eq!(res.body["created_at"], author["created_at"]);
+    assert!(time(&res.body["updated_at"]) > earlier);
+}
@@ -0,0 +1,135 @@
+mod common;
+
+use bookstore::entities::book;
+use common::TestApp;
+use rocket::{http::Status, serde::json::json};
+use sea_orm::{ActiveModelTrait, ActiveValue::Set};
+
+#[rocket::async_test]
+async fn creates_and_shows_a_book() {
+    let app = TestApp::spawn().await;
+    let token = app.user("reader@example.com").await;
+    let author = app.create_author(&token, "Italo", "Calvino").await;
+
+    let book = app
+        .create_book(&token, author["id"].as_i64().unwrap(), "Invisible Cities")
+        .await;
+
+    assert_eq!(book["author_id"], author["id"]);
+
+    let res = app.get(&format!("/books/{}", book["id"]), &token).await;
+
+    assert_eq!(res.body["title"], "Invisible Cities");
+
+    let res = app.get("/books", &token).await;
+
+    assert_eq!(res.body["total"], 1);
+}
+
+#[rocket::async_test]
+async fn updates_a_book() {
+    let app = TestApp::spawn().await;
+    let token = app.user("reader@example.com").await;
+    let author = app.create_author(&token, "Italo", "Calvino").await;
+    let book = app
+        .create_book(&token, author["id"].as_i64().unwrap(), "Invisible Cities")
+        .await;
+
+    let res = app
+        .put(
+            &format!("/books/{}", book["id"]),
+            &token,
+            json!({
+                "author_id": author["id"],
+                "title": "Le città invisibili",
+                "year": "1972",
+                "cover": "",
+            }),
+        )
+        .await;
+
+    assert_eq!(res.status, Status::Ok);
+    assert_eq!(res.body["title"], "Le città invisibili");
+    assert_eq!(res.body["year"], "1972");
+}
+
+#[rocket::async_test]
+async fn deletes_a_book() {
+    let app = TestApp::spawn().await;
+    let token = app.user("reader@example.com").await;
+    let author = app.create_author(&token, "Italo", "Calvino").await;
+    let book = app
+        .create_book(&token, author["id"].as_i64().unwrap(), "Invisible Cities")
+        .await;
+    let uri = format!("/books/{}", book["id"]);
+
+    let res = app.delete(&uri, &token).await;
+    assert_eq!(res.status, Status::Ok);
+
+    let res = app.get(&uri, &token).await;
+    assert_eq!(res.status, Status::NotFound);
+}
+
+#[rocket::async_test]
+async fn answers_404_for_a_missing_book() {
+    let app = TestApp::spawn().await;
+    let token = app.user("reader@example.com").await;
+
+    let res = app.get("/books/999", &token).await;
+
+    assert_eq!(res.status, Status::NotFound);
+}
+
+#[rocket::async_test]
+async fn rejects_a_second_book_with_the_same_title_by_an_author() {
+    let app = TestApp::spawn().await;
+    let token = app.user("reader@example.com").await;
+    let author = app.create_author(&token, "Italo", "Calvino").await;
+    app.create_book(&token, author["id"].as_i64().unwrap(), "Invisible Cities")
+        .await;
+
+    let res = app
+        .post(
+            "/books",
+            &token,
+            json!({ "author_id": author["id"], "title": "Invisible Cities", "year": "", "cover": "" }),
+        )
+        .await;
+
+    assert_eq!(res.status, Status::Conflict);
+}
+
+#[rocket::async_test]
+async fn rejects_an_empty_title() {
+    let app = TestApp::spawn().await;
+    let token = app.user("reader@example.com").await;
+    let author = app.create_author(&token, "Italo", "Calvino").await;
+
+    let res = app
+        .post(
+            "/books",
+            &token,
+            json!({ "author_id": author["id"], "title": "  ", "year": "", "cover": "" }),
+        )
+        .await;
+
+    assert_eq!(res.status, Status::BadRequest);
+}
+
+#[rocket::async_test]
+async fn checks_books_written_around_the_api() {
+    let app = TestApp::spawn().await;
+    let token = app.user("reader@example.com").await;
+    let author = app.create_author(&token, "Italo", "Calvino").await;
+    let book = app
+        .create_book(&token, author["id"].as_i64().unwrap(), "Invisible Cities")
+        .await;
+
+    let overlong = book::ActiveModel {
+        id: Set(book["id"].as_i64().unwrap() as i32),
+        year: Set("1".repeat(33)),
+        ..Default::default()
+    };
+
+    assert!(overlong.update(&app.db).await.is_err());
+}
@@ -0,0 +1,255 @@
+//! Runs the application against a fresh in-memory SQLite database per test,
+//! through Rocket's local client.
+
+#![allow(dead_code)]
+
+pub mod oidc_issuer;
+
+use std::{
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use bookstore::{
+    auth::ADMIN_ROLE,
+    build_rocket,
+    config::AppConfig,
+    db,
+    entities::{prelude::*, user},
+    migrator::Migrator,
+    seed::{self, Fixtures, SeedReport},
+};
+use rocket::{
+    figment::Figment,
+    http::{ContentType, Header, Method, Status},
+    local::asynchronous::Client,
+    serde::json::{Value, json},
+};
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, prelude::Expr};
+use sea_orm_migration::MigratorTrait;
+
+pub const PASSWORD: &str = "correct horse battery staple";
+
+static DATABASES: AtomicUsize = AtomicUsize::new(0);
+
+/// A migrated database of its own, named after the process and a counter
+/// so tests running in parallel never share one. Shared-cache mode lets
+/// every pooled connection see the same database, which lives as long as
+/// one of them is open.
+pub fn database_url() -> String {
+    format!(
+        "sqlite:file:bookstore-test-{}-{}?mode=memory&cache=shared",
+        process::id(),
+        DATABASES.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// The configuration every test starts from. It leaves out the usual
+/// files and environment variables so the developer's setup cannot leak
+/// into tests.
+pub fn figment() -> Figment {
+    Figment::new()
+        .merge(("database_url", database_url()))
+        .merge(("jwt_secret", "test-secret"))
+        .merge(("rate_limits", Vec::<String>::new()))
+}
+
+pub struct TestApp {
+    pub client: Client,
+    /// The application's own database, for checks the API cannot make.
+    pub db: DatabaseConnection,
+}
+
+/// A response, with its body parsed as JSON or kept as a JSON string when
+/// it is plain text.
+pub struct TestResponse {
+    pub status: Status,
+    pub body: Value,
+}
+
+impl TestApp {
+    pub async fn spawn() -> Self {
+        Self::spawn_with(figment()).await
+    }
+
+    /// Starts the application with a configuration built on [`figment`].
+    pub async fn spawn_with(figment: Figment) -> Self {
+        Self::start(figment, true).await
+    }
+
+    /// Like [`TestApp::spawn_with`], with a client that keeps no cookies,
+    /// as most API consumers.
+    pub async fn spawn_without_cookies(figment: Figment) -> Self {
+        Self::start(figment, false).await
+    }
+
+    async fn start(figment: Figment, tracked: bool) -> Self {
+        let config = AppConfig::load(&figment).expect("invalid test configuration");
+
+        let db = db::connect(&config)
+            .await
+            .expect("cannot open the test database");
+
+        Migrator::up(&db, None)
+            .await
+            .expect("cannot migrate the test database");
+
+        let replicas = db::connect_replicas(&config)
+            .await
+            .expect("cannot open the test replicas");
+
+        let rocket =
+            build_rocket(config, db.clone(), replicas).expect("cannot build the application");
+        let client = match tracked {
+            true => Client::tracked(rocket).await,
+            false => Client::untracked(rocket).await,
+        }
+        .expect("cannot start the application");
+
+        Self { client, db }
+    }
+
+    /// Loads YAML fixtures straight into the database.
+    pub async fn seed(&self, yaml: &str) -> SeedReport {
+        let fixtures = Fixtures::from_yaml(yaml).expect("invalid fixtures");
+
+        seed::load(&self.db, &fixtures)
+            .await
+            .expect("cannot load the fixtures")
+    }
+
+    pub async fn request(
+        &self,
+        method: Method,
+        uri: &str,
+        token: Option<&str>,
+        body: Option<Value>,
+    ) -> TestResponse {
+        let mut request = self.client.req(method, uri.to_string());
+
+        if let Some(token) = token {
+            request.add_header(Header::new("Authorization", format!("Bearer {}", token)));
+        }
+
+        if let Some(body) = body {
+            request = request.header(ContentType::JSON).body(body.to_string());
+        }
+
+        let response = request.dispatch().await;
+        let status = response.status();
+        let body = response.into_string().await.unwrap_or_default();
+
+        TestResponse {
+            status,
+            body: rocket::serde::json::from_str(&body).unwrap_or(Value::String(body)),
+        }
+    }
+
+    pub async fn get(&self, uri: &str, token: &str) -> TestResponse {
+        self.request(Method::Get, uri, Some(token), None).await
+    }
+
+    pub async fn post(&self, uri: &str, token: &str, body: Value) -> TestResponse {
+        self.request(Method::Post, uri, Some(token), Some(body))
+            .await
+    }
+
+    pub async fn put(&self, uri: &str, token: &str, body: Value) -> TestResponse {
+        self.request(Method::Put, uri, Some(token), Some(body))
+            .await
+    }
+
+    pub async fn delete(&self, uri: &str, token: &str) -> TestResponse {
+        self.request(Method::Delete, uri, Some(token), None).await
+    }
+
+    pub async fn sign_up(&self, email: &str, password: &str) -> TestResponse {
+        self.request(
+            Method::Post,
+            "/auth/sign-up",
+            None,
+            Some(json!({
+                "email": email,
+                "password": password,
+                "firstname": "Test",
+                "lastname": "User",
+            })),
+        )
+        .await
+    }
+
+    pub async fn sign_in(&self, email: &str, password: &str) -> TestResponse {
+        self.request(
+            Method::Post,
+            "/auth/sign-in",
+            None,
+            Some(json!({ "email": email, "password": password })),
+        )
+        .await
+    }
+
+    /// Signs up a user with [`PASSWORD`] and signs them in, returning their
+    /// access token.
+    pub async fn user(&self, email: &str) -> String {
+        let res = self.sign_up(email, PASSWORD).await;
+        assert_eq!(res.status, Status::Created, "sign up failed: {}", res.body);
+
+        let res = self.sign_in(email, PASSWORD).await;
+        assert_eq!(res.status, Status::Ok, "sign in failed: {}", res.body);
+
+        res.body["token"]
+            .as_str()
+            .expect("no token in the sign in response")
+            .to_string()
+    }
+
+    /// Like [`TestApp::user`], with the user made an admin.
+    pub async fn admin(&self, email: &str) -> String {
+        let token = self.user(email).await;
+
+        User::update_many()
+            .col_expr(user::Column::Role, Expr::value(ADMIN_ROLE))
+            .filter(user::Column::Email.eq(email))
+            .exec(&self.db)
+            .await
+            .expect("cannot make the user an admin");
+
+        token
+    }
+
+    pub async fn create_author(&self, token: &str, firstname: &str, lastname: &str) -> Value {
+        let res = self
+            .post(
+                "/authors",
+                token,
+                json!({ "firstname": firstname, "lastname": lastname, "bio": "" }),
+            )
+            .await;
+        assert_eq!(
+            res.status,
+            Status::Created,
+            "creating the author failed: {}",
+            res.body
+        );
+
+        res.body
+    }
+
+    pub async fn create_book(&self, token: &str, author_id: i64, title: &str) -> Value {
+        let res = self
+            .post(
+                "/books",
+                token,
+                json!({ "author_id": author_id, "title": title, "year": "2000", "cover": "" }),
+            )
+            .await;
+        assert_eq!(
+            res.status,
+            Status::Created,
+            "creating the book failed: {}",
+            res.body
+        );
+
+        res.body
+    }
+}
@@ -0,0 +1,219 @@
+//! A local OpenID Connect provider, for signing in through one without
+//! leaving the machine. It serves discovery, its keys and a token endpoint
+//! checking PKCE; tests play the user at its login page with
+//! [`MockIssuer::authorize`].
+
+use std::{
+    collections::HashMap,
+    net::TcpListener,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use bookstore::{auth::keys::KeyStore, config::AppConfig};
+use jsonwebtoken::{Algorithm, EncodingKey, Header, encode};
+use reqwest::Url;
+use rocket::{
+    Config, FromForm, State,
+    config::LogLevel,
+    figment::Figment,
+    form::Form,
+    get,
+    http::Status,
+    post, routes,
+    serde::json::{Json, Value, json},
+};
+use sha2::{Digest, Sha256};
+
+pub const NAME: &str = "mock";
+pub const KID: &str = "issuer-key";
+pub const CLIENT_ID: &str = "bookstore";
+
+struct Code {
+    code_challenge: String,
+    id_token: String,
+}
+
+struct Issuer {
+    url: String,
+    keys: KeyStore,
+    codes: Mutex<HashMap<String, Code>>,
+    jwks_fetches: AtomicUsize,
+}
+
+#[get("/.well-known/openid-configuration")]
+fn discovery(issuer: &State<Arc<Issuer>>) -> Json<Value> {
+    Json(json!({
+        "issuer": issuer.url,
+        "authorization_endpoint": format!("{}/authorize", issuer.url),
+        "token_endpoint": format!("{}/token", issuer.url),
+        "jwks_uri": format!("{}/jwks", issuer.url),
+    }))
+}
+
+#[get("/jwks")]
+fn jwks(issuer: &State<Arc<Issuer>>) -> Json<Value> {
+    issuer.jwks_fetches.fetch_add(1, Ordering::Relaxed);
+
+    Json(issuer.keys.jwks())
+}
+
+#[derive(FromForm)]
+struct TokenRequest<'r> {
+    code: &'r str,
+    code_verifier: &'r str,
+    client_id: &'r str,
+}
+
+#[post("/token", data = "<req>")]
+fn token(issuer: &State<Arc<Issuer>>, req: Form<TokenRequest<'_>>) -> Result<Json<Value>, Status> {
+    let code = issuer
+        .codes
+        .lock()
+        .unwrap()
+        .remove(req.code)
+        .ok_or(Status::BadRequest)?;
+
+    let code_challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(req.code_verifier.as_bytes()));
+
+    if code_challenge != code.code_challenge || req.client_id != CLIENT_ID {
+        return Err(Status::BadRequest);
+    }
+
+    Ok(Json(json!({
+        "access_token": "unused",
+        "token_type": "Bearer",
+        "id_token": code.id_token,
+    })))
+}
+
+pub struct MockIssuer {
+    pub url: String,
+    issuer: Arc<Issuer>,
+}
+
+impl MockIssuer {
+    /// Starts the provider on a free port.
+    pub async fn start() -> Self {
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let url = format!("http://127.0.0.1:{}", port);
+
+        let fixtures = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+        let key = format!(
+            "{}:RS256:{}/oidc-issuer.pub.pem:{}/oidc-issuer.pem",
+            KID, fixtures, fixtures
+        );
+        let config = AppConfig::load(
+            &super::figment()
+                .merge(("jwt_keys", [key]))
+                .merge(("jwt_active_kid", KID)),
+        )
+        .unwrap();
+
+        let issuer = Arc::new(Issuer {
+            url: url.to_owned(),
+            keys: KeyStore::load(&config).unwrap(),
+            codes: Mutex::new(HashMap::new()),
+            jwks_fetches: AtomicUsize::new(0),
+        });
+
+        let mut config = Config::debug_default();
+        config.port = port;
+        config.log_level = LogLevel::Off;
+        config.shutdown.ctrlc = false;
+
+        let rocket = rocket::custom(config)
+            .manage(issuer.clone())
+            .mount("/", routes![discovery, jwks, token]);
+
+        rocket::tokio::spawn(rocket.launch());
+
+        let discovery = format!("{}/.well-known/openid-configuration", url);
+
+        for _ in 0..100 {
+            if reqwest::get(&discovery).await.is_ok() {
+                break;
+            }
+
+            rocket::tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        Self { url, issuer }
+    }
+
+    /// Configures the application to sign in through the provider.
+    pub fn configure(&self, figment: Figment) -> Figment {
+        figment
+            .merge((format!("oidc.{}.issuer", NAME), &self.url))
+            .merge((format!("oidc.{}.client_id", NAME), CLIENT_ID))
+            .merge((
+                format!("oidc.{}.redirect_uri", NAME),
+                format!("http://localhost/auth/oidc/{}/callback", NAME),
+            ))
+    }
+
+    /// How often the application fetched the provider's keys.
+    pub fn jwks_fetches(&self) -> usize {
+        self.issuer.jwks_fetches.load(Ordering::Relaxed)
+    }
+
+    /// The user signs in at the login page the application sent them to,
+    /// `location`. Returns the code and state to call back with.
+    pub fn authorize(&self, location: &str, claims: Value) -> (String, String) {
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some(KID.to_string());
+
+        self.authorize_with(location, claims, header, self.issuer.keys.encoding_key())
+    }
+
+    /// Like [`MockIssuer::authorize`], with the ID token signed as given.
+    pub fn authorize_with(
+        &self,
+        location: &str,
+        claims: Value,
+        header: Header,
+        key: &EncodingKey,
+    ) -> (String, String) {
+        let location = Url::parse(location).unwrap();
+        let param = |name: &str| {
+            location
+                .query_pairs()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.into_owned())
+                .unwrap()
+        };
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        let mut claims = claims;
+        let object = claims.as_object_mut().unwrap();
+        object.insert("iss".to_string(), json!(self.url));
+        object.insert("aud".to_string(), json!(CLIENT_ID));
+        object.insert("iat".to_string(), json!(now));
+        object.insert("exp".to_string(), json!(now + 300));
+        object.insert("nonce".to_string(), json!(param("nonce")));
+
+        let code = format!("code-{}", param("state"));
+
+        self.issuer.codes.lock().unwrap().insert(
+            code.to_owned(),
+            Code {
+                code_challenge: param("code_challenge"),
+                id_token: encode(&header, &claims, key).unwrap(),
+            },
+        );
+
+        (code, param("state"))
+    }
+}
@@ -0,0 +1,41 @@
+mod common;
+
+use bookstore::config::AppConfig;
+use figment::Figment;
+
+fn errors(figment: Figment) -> String {
+    match AppConfig::load(&figment) {
+        Ok(_) => panic!("the configuration should be rejected"),
+        Err(errors) => errors,
+    }
+}
+
+#[test]
+fn accepts_the_test_configuration() {
+    assert!(AppConfig::load(&common::figment()).is_ok());
+}
+
+#[test]
+fn requires_a_jwt_secret() {
+    let figment = Figment::new().merge(("database_url", common::database_url()));
+
+    assert!(errors(figment).contains("jwt_secret (BOOKSTORE_JWT_SECRET) must be set"));
+}
+
+#[test]
+fn reports_every_error_at_once() {
+    let figment = common::figment()
+        .merge(("jwt_secret", ""))
+        .merge(("db_pool.min_connections", 10))
+        .merge(("db_pool.max_connections", 5))
+        .merge(("db_replicas.urls", ["redis://replica"]))
+        .merge(("cors.origins", ["*"]))
+        .merge(("cors.credentials", true));
+
+    let errors = errors(figment);
+
+    assert!(errors.contains("jwt_secret must not be empty"));
+    assert!(errors.contains("db_pool.min_connections cannot exceed db_pool.max_connections"));
+    assert!(errors.contains("db_replicas.urls[0] must start with"));
+    assert!(errors.contains("cors.credentials cannot be combined with the `*` origin"));
+}
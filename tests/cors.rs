@@ -0,0 +1,77 @@
+mod common;
+
+use common::TestApp;
+use rocket::http::{Header, Status};
+
+const ORIGIN: &str = "https://app.example.com";
+
+async fn app() -> TestApp {
+    TestApp::spawn_with(common::figment().merge(("cors.origins", [ORIGIN]))).await
+}
+
+#[rocket::async_test]
+async fn answers_preflights_from_allowed_origins() {
+    let app = app().await;
+
+    let res = app
+        .client
+        .options("/authors")
+        .header(Header::new("Origin", ORIGIN))
+        .header(Header::new("Access-Control-Request-Method", "POST"))
+        .dispatch()
+        .await;
+
+    assert_eq!(res.status(), Status::NoContent);
+    let headers = res.headers();
+    assert_eq!(headers.get_one("Access-Control-Allow-Origin"), Some(ORIGIN));
+    assert!(
+        headers
+            .get_one("Access-Control-Allow-Methods")
+            .unwrap()
+            .contains("POST")
+    );
+    assert!(
+        headers
+            .get_one("Access-Control-Allow-Headers")
+            .unwrap()
+            .contains("Authorization")
+    );
+    assert_eq!(headers.get_one("Access-Control-Max-Age"), Some("3600"));
+}
+
+#[rocket::async_test]
+async fn exposes_headers_to_allowed_origins() {
+    let app = app().await;
+
+    let res = app
+        .client
+        .get("/health/live")
+        .header(Header::new("Origin", ORIGIN))
+        .dispatch()
+        .await;
+
+    let headers = res.headers();
+    assert_eq!(headers.get_one("Access-Control-Allow-Origin"), Some(ORIGIN));
+    assert_eq!(headers.get_one("Vary"), Some("Origin"));
+    assert!(
+        headers
+            .get_one("Access-Control-Expose-Headers")
+            .unwrap()
+            .contains("Retry-After")
+    );
+}
+
+#[rocket::async_test]
+async fn ignores_other_origins() {
+    let app = app().await;
+
+    let res = app
+        .client
+        .options("/authors")
+        .header(Header::new("Origin", "https://evil.example.com"))
+        .header(Header::new("Access-Control-Request-Method", "POST"))
+        .dispatch()
+        .await;
+
+    assert_eq!(res.headers().get_one("Access-Control-Allow-Origin"), None);
+}
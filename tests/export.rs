@@ -0,0 +1,103 @@
+mod common;
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use common::TestApp;
+use rocket::http::Status;
+
+/// A fresh export directory for one test.
+fn export_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("bookstore-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    dir
+}
+
+fn zips(dir: &Path) -> usize {
+    fs::read_dir(dir)
+        .unwrap()
+        .filter(|entry| {
+            entry
+                .as_ref()
+                .unwrap()
+                .path()
+                .extension()
+                .is_some_and(|ext| ext == "zip")
+        })
+        .count()
+}
+
+async fn spawn(dir: &Path, ttl_secs: u64) -> TestApp {
+    TestApp::spawn_with(
+        common::figment()
+            .merge(("export_dir", dir.to_string_lossy()))
+            .merge(("export_inline_limit", 0))
+            .merge(("export_ttl_secs", ttl_secs)),
+    )
+    .await
+}
+
+#[rocket::async_test]
+async fn deletes_background_exports_once_expired() {
+    let dir = export_dir("expiring-exports");
+    let app = spawn(&dir, 1).await;
+    let token = app.user("reader@example.com").await;
+
+    let res = app.get("/auth/me/export", &token).await;
+    assert_eq!(res.status, Status::Accepted);
+    let download_url = res.body["download_url"].as_str().unwrap().to_string();
+
+    let mut res = app.get(&download_url, &token).await;
+    for _ in 0..50 {
+        if res.status != Status::Accepted {
+            break;
+        }
+
+        rocket::tokio::time::sleep(Duration::from_millis(10)).await;
+        res = app.get(&download_url, &token).await;
+    }
+    assert_eq!(res.status, Status::Ok);
+    assert_eq!(zips(&dir), 1);
+
+    rocket::tokio::time::sleep(Duration::from_millis(1100)).await;
+
+    assert_eq!(
+        app.get(&download_url, &token).await.status,
+        Status::NotFound
+    );
+    assert_eq!(zips(&dir), 0);
+}
+
+#[rocket::async_test]
+async fn deletes_exports_left_over_from_before_a_restart() {
+    let dir = export_dir("left-over-exports");
+    fs::write(dir.join("forgotten.zip"), b"").unwrap();
+    fs::write(dir.join("notes.txt"), b"").unwrap();
+
+    spawn(&dir, 0).await;
+
+    assert_eq!(zips(&dir), 0);
+    assert!(dir.join("notes.txt").exists());
+}
+
+#[rocket::async_test]
+async fn reuses_the_export_in_progress() {
+    let dir = export_dir("repeated-exports");
+    let app = spawn(&dir, 60).await;
+    let token = app.user("reader@example.com").await;
+
+    let first = app.get("/auth/me/export", &token).await;
+    let second = app.get("/auth/me/export", &token).await;
+
+    assert_eq!(first.body["job_id"], second.body["job_id"]);
+
+    let other = app.user("other@example.com").await;
+    let res = app.get("/auth/me/export", &other).await;
+
+    assert_ne!(first.body["job_id"], res.body["job_id"]);
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use common::TestApp;
+use rocket::http::{Method, Status};
+
+#[rocket::async_test]
+async fn is_live() {
+    let app = TestApp::spawn().await;
+
+    let res = app.request(Method::Get, "/health/live", None, None).await;
+
+    assert_eq!(res.status, Status::Ok);
+}
+
+#[rocket::async_test]
+async fn is_ready_when_every_check_passes() {
+    let app = TestApp::spawn().await;
+
+    let res = app.request(Method::Get, "/health/ready", None, None).await;
+
+    assert_eq!(res.status, Status::Ok, "{}", res.body);
+    assert_eq!(res.body["status"], "ok");
+    assert!(
+        res.body["checks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|check| check["healthy"] == true)
+    );
+}
+
+#[rocket::async_test]
+async fn is_unavailable_when_a_required_check_fails() {
+    // A file where the export directory should be cannot be written to.
+    let app = TestApp::spawn_with(common::figment().merge(("export_dir", file!()))).await;
+
+    let res = app.request(Method::Get, "/health/ready", None, None).await;
+
+    assert_eq!(res.status, Status::ServiceUnavailable);
+    assert_eq!(res.body["status"], "unavailable");
+
+    let check = res.body["checks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|check| check["name"] == "export_dir")
+        .unwrap();
+    assert_eq!(check["healthy"], false);
+}
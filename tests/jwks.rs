@@ -0,0 +1,56 @@
+mod common;
+
+use common::TestApp;
+use jsonwebtoken::{DecodingKey, Validation, decode, decode_header, jwk::JwkSet};
+use rocket::{
+    http::{Method, Status},
+    serde::json::{self, Value},
+};
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+#[rocket::async_test]
+async fn publishes_the_keys_tokens_are_signed_with() {
+    let key = format!(
+        "signing-key:RS256:{0}/oidc-issuer.pub.pem:{0}/oidc-issuer.pem",
+        FIXTURES
+    );
+    let app = TestApp::spawn_with(
+        common::figment()
+            .merge(("jwt_keys", [key]))
+            .merge(("jwt_active_kid", "signing-key")),
+    )
+    .await;
+    let token = app.user("reader@example.com").await;
+
+    let res = app
+        .request(Method::Get, "/.well-known/jwks.json", None, None)
+        .await;
+    assert_eq!(res.status, Status::Ok);
+
+    let jwks: JwkSet = json::from_value(res.body).unwrap();
+    let kid = decode_header(&token).unwrap().kid.unwrap();
+    assert_eq!(kid, "signing-key");
+
+    let jwk = jwks.find(&kid).unwrap();
+    let claims = decode::<Value>(
+        &token,
+        &DecodingKey::from_jwk(jwk).unwrap(),
+        &Validation::new(jsonwebtoken::Algorithm::RS256),
+    )
+    .unwrap()
+    .claims;
+
+    assert!(claims["sub"].is_number());
+}
+
+#[rocket::async_test]
+async fn publishes_no_shared_secret() {
+    let app = TestApp::spawn().await;
+
+    let res = app
+        .request(Method::Get, "/.well-known/jwks.json", None, None)
+        .await;
+
+    assert_eq!(res.body["keys"].as_array().unwrap().len(), 0);
+}
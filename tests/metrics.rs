@@ -0,0 +1,76 @@
+mod common;
+
+use common::TestApp;
+use rocket::http::{Header, Status};
+
+const PROXY: &str = "10.0.0.1:4000";
+const STRANGER: &str = "203.0.113.7:4000";
+
+async fn scrape(app: &TestApp, remote: &str, real_ip: Option<&str>) -> Status {
+    let mut request = app.client.get("/metrics").remote(remote.parse().unwrap());
+
+    if let Some(real_ip) = real_ip {
+        request.add_header(Header::new("X-Real-IP", real_ip.to_string()));
+    }
+
+    request.dispatch().await.status()
+}
+
+#[rocket::async_test]
+async fn serves_the_allowed_networks() {
+    let app = TestApp::spawn_with(common::figment().merge(("metrics.allow", ["10.0.0.0/8"]))).await;
+
+    assert_eq!(scrape(&app, PROXY, None).await, Status::Ok);
+    assert_eq!(scrape(&app, STRANGER, None).await, Status::Forbidden);
+}
+
+#[rocket::async_test]
+async fn serves_nobody_by_default() {
+    let app = TestApp::spawn().await;
+
+    assert_eq!(
+        scrape(&app, "127.0.0.1:4000", None).await,
+        Status::Forbidden
+    );
+}
+
+#[rocket::async_test]
+async fn ignores_a_forged_client_address() {
+    let app = TestApp::spawn_with(common::figment().merge(("metrics.allow", ["127.0.0.1"]))).await;
+
+    let status = scrape(&app, STRANGER, Some("127.0.0.1")).await;
+
+    assert_eq!(status, Status::Forbidden);
+}
+
+#[rocket::async_test]
+async fn trusts_the_client_address_passed_on_by_a_proxy() {
+    let app = TestApp::spawn_with(
+        common::figment()
+            .merge(("metrics.allow", ["127.0.0.1"]))
+            .merge(("trusted_proxies", ["10.0.0.1"])),
+    )
+    .await;
+
+    assert_eq!(scrape(&app, PROXY, Some("127.0.0.1")).await, Status::Ok);
+    assert_eq!(
+        scrape(&app, PROXY, Some("203.0.113.7")).await,
+        Status::Forbidden
+    );
+}
+
+#[rocket::async_test]
+async fn serves_anyone_with_the_token() {
+    let app = TestApp::spawn_with(common::figment().merge(("metrics.token", "scrape-token"))).await;
+
+    let status = app
+        .client
+        .get("/metrics")
+        .remote(STRANGER.parse().unwrap())
+        .header(Header::new("Authorization", "Bearer scrape-token"))
+        .dispatch()
+        .await
+        .status();
+
+    assert_eq!(status, Status::Ok);
+}
@@ -0,0 +1,256 @@
+mod common;
+
+use std::time::SystemTime;
+
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use bookstore::entities::{prelude::*, user};
+use common::TestApp;
+use reqwest::Url;
+use rocket::{
+    http::{ContentType, Header, Status},
+    serde::json::{Value, json},
+};
+use sea_orm::{
+    ColumnTrait, EntityTrait, QueryFilter,
+    prelude::{DateTimeUtc, Expr},
+};
+use sha2::{Digest, Sha256};
+
+const REDIRECT_URI: &str = "https://client.example.com/callback";
+const VERIFIER: &str = "a-code-verifier-long-enough-to-satisfy-the-pkce-rules";
+
+/// Registers a public client allowed to read authors.
+async fn public_client(app: &TestApp, token: &str) -> String {
+    let res = app
+        .post(
+            "/oauth/clients",
+            token,
+            json!({
+                "name": "Reading list",
+                "redirect_uris": [REDIRECT_URI],
+                "scopes": ["authors:read"],
+                "confidential": false,
+            }),
+        )
+        .await;
+    assert_eq!(res.status, Status::Created, "{}", res.body);
+
+    res.body["client_id"].as_str().unwrap().to_string()
+}
+
+fn authorize_uri(client_id: &str, pkce: bool) -> String {
+    let mut url = Url::parse("http://localhost/oauth/authorize").unwrap();
+    url.query_pairs_mut()
+        .append_pair("response_type", "code")
+        .append_pair("client_id", client_id)
+        .append_pair("redirect_uri", REDIRECT_URI)
+        .append_pair("scope", "authors:read")
+        .append_pair("state", "xyz");
+
+    if pkce {
+        url.query_pairs_mut()
+            .append_pair(
+                "code_challenge",
+                &URL_SAFE_NO_PAD.encode(Sha256::digest(VERIFIER)),
+            )
+            .append_pair("code_challenge_method", "S256");
+    }
+
+    format!("{}?{}", url.path(), url.query().unwrap())
+}
+
+/// Goes through the consent screen and returns the redirect it ends with.
+async fn authorize(app: &TestApp, token: &str, client_id: &str) -> Url {
+    let page = app
+        .client
+        .get(authorize_uri(client_id, true))
+        .header(Header::new("Authorization", format!("Bearer {}", token)))
+        .dispatch()
+        .await
+        .into_string()
+        .await
+        .unwrap();
+    let ticket = page
+        .split(r#"name="ticket" value=""#)
+        .nth(1)
+        .and_then(|rest| rest.split('"').next())
+        .expect("the consent page has no ticket");
+
+    let res = app
+        .client
+        .post("/oauth/authorize")
+        .header(ContentType::Form)
+        .body(format!("ticket={}&decision=allow", ticket))
+        .dispatch()
+        .await;
+    assert_eq!(res.status(), Status::SeeOther);
+
+    Url::parse(res.headers().get_one("Location").unwrap()).unwrap()
+}
+
+fn query(url: &Url, name: &str) -> Option<String> {
+    url.query_pairs()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.into_owned())
+}
+
+/// Posts `params` as a form, answering with the JSON response.
+async fn post_form(app: &TestApp, uri: &str, params: &[(&str, &str)]) -> (Status, Value) {
+    let form = Url::parse_with_params("http://localhost", params).unwrap();
+
+    let res = app
+        .client
+        .post(uri.to_string())
+        .header(ContentType::Form)
+        .body(form.query().unwrap())
+        .dispatch()
+        .await;
+    let status = res.status();
+
+    (status, res.into_json().await.unwrap())
+}
+
+async fn exchange(app: &TestApp, client_id: &str, code: &str, verifier: &str) -> (Status, Value) {
+    post_form(
+        app,
+        "/oauth/token",
+        &[
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", REDIRECT_URI),
+            ("code_verifier", verifier),
+            ("client_id", client_id),
+        ],
+    )
+    .await
+}
+
+#[rocket::async_test]
+async fn exchanges_a_code_for_a_scoped_token() {
+    let app = TestApp::spawn().await;
+    let token = app.user("reader@example.com").await;
+    let client_id = public_client(&app, &token).await;
+
+    let redirect = authorize(&app, &token, &client_id).await;
+    assert!(redirect.as_str().starts_with(REDIRECT_URI));
+    assert_eq!(query(&redirect, "state").as_deref(), Some("xyz"));
+
+    let code = query(&redirect, "code").unwrap();
+    let (status, body) = exchange(&app, &client_id, &code, VERIFIER).await;
+    assert_eq!(status, Status::Ok, "{}", body);
+    assert_eq!(body["scope"], "authors:read");
+
+    let access_token = body["access_token"].as_str().unwrap();
+    assert_eq!(app.get("/authors", access_token).await.status, Status::Ok);
+
+    let res = app
+        .post(
+            "/authors",
+            access_token,
+            json!({ "firstname": "Italo", "lastname": "Calvino", "bio": "" }),
+        )
+        .await;
+    assert_eq!(res.status, Status::Forbidden);
+}
+
+#[rocket::async_test]
+async fn rejects_a_wrong_verifier_and_burns_the_code() {
+    let app = TestApp::spawn().await;
+    let token = app.user("reader@example.com").await;
+    let client_id = public_client(&app, &token).await;
+    let code = query(&authorize(&app, &token, &client_id).await, "code").unwrap();
+
+    let (status, body) = exchange(&app, &client_id, &code, "not-the-verifier").await;
+    assert_eq!(status, Status::BadRequest);
+    assert_eq!(body["error"], "invalid_grant");
+
+    let (status, body) = exchange(&app, &client_id, &code, VERIFIER).await;
+    assert_eq!(status, Status::BadRequest);
+    assert_eq!(body["error"], "invalid_grant");
+}
+
+#[rocket::async_test]
+async fn codes_are_single_use() {
+    let app = TestApp::spawn().await;
+    let token = app.user("reader@example.com").await;
+    let client_id = public_client(&app, &token).await;
+    let code = query(&authorize(&app, &token, &client_id).await, "code").unwrap();
+
+    let (status, _) = exchange(&app, &client_id, &code, VERIFIER).await;
+    assert_eq!(status, Status::Ok);
+
+    let (status, body) = exchange(&app, &client_id, &code, VERIFIER).await;
+    assert_eq!(status, Status::BadRequest);
+    assert_eq!(body["error"], "invalid_grant");
+}
+
+#[rocket::async_test]
+async fn requires_pkce() {
+    let app = TestApp::spawn().await;
+    let token = app.user("reader@example.com").await;
+    let client_id = public_client(&app, &token).await;
+
+    let res = app
+        .client
+        .get(authorize_uri(&client_id, false))
+        .header(Header::new("Authorization", format!("Bearer {}", token)))
+        .dispatch()
+        .await;
+    assert_eq!(res.status(), Status::SeeOther);
+
+    let redirect = Url::parse(res.headers().get_one("Location").unwrap()).unwrap();
+    assert_eq!(
+        query(&redirect, "error").as_deref(),
+        Some("invalid_request")
+    );
+    assert_eq!(query(&redirect, "state").as_deref(), Some("xyz"));
+}
+
+#[rocket::async_test]
+async fn requires_the_redirect_uri_sent_to_authorize() {
+    let app = TestApp::spawn().await;
+    let token = app.user("reader@example.com").await;
+    let client_id = public_client(&app, &token).await;
+    let code = query(&authorize(&app, &token, &client_id).await, "code").unwrap();
+
+    let (status, body) = post_form(
+        &app,
+        "/oauth/token",
+        &[
+            ("grant_type", "authorization_code"),
+            ("code", &code),
+            ("code_verifier", VERIFIER),
+            ("client_id", &client_id),
+        ],
+    )
+    .await;
+
+    assert_eq!(status, Status::BadRequest);
+    assert_eq!(body["error"], "invalid_grant");
+}
+
+#[rocket::async_test]
+async fn introspects_tokens_of_disabled_users_as_inactive() {
+    let app = TestApp::spawn().await;
+    let token = app.user("reader@example.com").await;
+    let client_id = public_client(&app, &token).await;
+    let code = query(&authorize(&app, &token, &client_id).await, "code").unwrap();
+    let (_, body) = exchange(&app, &client_id, &code, VERIFIER).await;
+    let access_token = body["access_token"].as_str().unwrap();
+
+    let params = [("token", access_token), ("client_id", client_id.as_str())];
+    let introspect = || post_form(&app, "/oauth/introspect", &params);
+    assert_eq!(introspect().await.1["active"], true);
+
+    User::update_many()
+        .col_expr(
+            user::Column::DisabledAt,
+            Expr::value(DateTimeUtc::from(SystemTime::now())),
+        )
+        .filter(user::Column::Email.eq("reader@example.com"))
+        .exec(&app.db)
+        .await
+        .unwrap();
+
+    assert_eq!(introspect().await.1["active"], false);
+}
@@ -0,0 +1,209 @@
+mod common;
+
+use bookstore::config::AppConfig;
+use common::{
+    TestApp, TestResponse,
+    oidc_issuer::{KID, MockIssuer, NAME},
+};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use rocket::{
+    figment::Figment,
+    http::{Header as HttpHeader, Method, Status},
+    serde::json::{Value, json},
+};
+
+async fn spawn(issuer: &MockIssuer) -> TestApp {
+    TestApp::spawn_with(issuer.configure(common::figment())).await
+}
+
+fn claims(sub: &str, email: &str) -> Value {
+    json!({
+        "sub": sub,
+        "email": email,
+        "email_verified": true,
+        "given_name": "Ursula",
+        "family_name": "Le Guin",
+    })
+}
+
+/// Where the application sends the user to sign in at the provider.
+async fn login_location(app: &TestApp, uri: &str, token: Option<&str>) -> String {
+    let mut request = app.client.get(uri.to_string());
+
+    if let Some(token) = token {
+        request.add_header(HttpHeader::new(
+            "Authorization",
+            format!("Bearer {}", token),
+        ));
+    }
+
+    let res = request.dispatch().await;
+    assert_eq!(res.status(), Status::SeeOther);
+
+    res.headers().get_one("Location").unwrap().to_string()
+}
+
+async fn callback(app: &TestApp, (code, state): (String, String)) -> TestResponse {
+    let uri = format!("/auth/oidc/{}/callback?code={}&state={}", NAME, code, state);
+
+    app.request(Method::Get, &uri, None, None).await
+}
+
+async fn sign_in(app: &TestApp, issuer: &MockIssuer, claims: Value) -> TestResponse {
+    let location = login_location(app, &format!("/auth/oidc/{}/login", NAME), None).await;
+
+    callback(app, issuer.authorize(&location, claims)).await
+}
+
+async fn email_of(app: &TestApp, res: &TestResponse) -> Value {
+    assert_eq!(res.status, Status::Ok, "sign in failed: {}", res.body);
+
+    app.get("/auth/me", res.body["token"].as_str().unwrap())
+        .await
+        .body["email"]
+        .clone()
+}
+
+#[rocket::async_test]
+async fn signs_up_and_in_through_the_provider() {
+    let issuer = MockIssuer::start().await;
+    let app = spawn(&issuer).await;
+
+    let res = sign_in(&app, &issuer, claims("alice", "alice@example.com")).await;
+    assert_eq!(email_of(&app, &res).await, "alice@example.com");
+
+    let res = sign_in(&app, &issuer, claims("alice", "alice@example.com")).await;
+    assert_eq!(email_of(&app, &res).await, "alice@example.com");
+
+    // The provider's keys are reused between logins.
+    assert_eq!(issuer.jwks_fetches(), 1);
+}
+
+#[rocket::async_test]
+async fn does_not_link_an_existing_account_by_email() {
+    let issuer = MockIssuer::start().await;
+    let app = spawn(&issuer).await;
+    app.user("reader@example.com").await;
+
+    let res = sign_in(&app, &issuer, claims("mallory", "reader@example.com")).await;
+
+    assert_eq!(res.status, Status::Conflict);
+}
+
+#[rocket::async_test]
+async fn links_by_email_when_the_provider_is_trusted_to() {
+    let issuer = MockIssuer::start().await;
+    let app = TestApp::spawn_with(
+        issuer
+            .configure(common::figment())
+            .merge((format!("oidc.{}.link_by_email", NAME), true)),
+    )
+    .await;
+    app.user("reader@example.com").await;
+
+    let res = sign_in(&app, &issuer, claims("reader", "reader@example.com")).await;
+
+    assert_eq!(email_of(&app, &res).await, "reader@example.com");
+}
+
+#[rocket::async_test]
+async fn links_the_provider_to_the_signed_in_account() {
+    let issuer = MockIssuer::start().await;
+    let app = spawn(&issuer).await;
+    let token = app.user("reader@example.com").await;
+
+    let location = login_location(
+        &app,
+        &format!("/auth/oidc/{}/link", NAME),
+        Some(token.as_str()),
+    )
+    .await;
+    let res = callback(
+        &app,
+        issuer.authorize(&location, claims("reader", "elsewhere@example.org")),
+    )
+    .await;
+    assert_eq!(email_of(&app, &res).await, "reader@example.com");
+
+    let res = sign_in(&app, &issuer, claims("reader", "elsewhere@example.org")).await;
+    assert_eq!(email_of(&app, &res).await, "reader@example.com");
+}
+
+#[rocket::async_test]
+async fn rejects_an_id_token_without_a_key_id() {
+    let issuer = MockIssuer::start().await;
+    let app = spawn(&issuer).await;
+    let location = login_location(&app, &format!("/auth/oidc/{}/login", NAME), None).await;
+
+    let unsigned = issuer.authorize_with(
+        &location,
+        claims("alice", "alice@example.com"),
+        Header::new(Algorithm::RS256),
+        &EncodingKey::from_rsa_pem(include_bytes!("fixtures/oidc-issuer.pem")).unwrap(),
+    );
+
+    assert_eq!(callback(&app, unsigned).await.status, Status::Unauthorized);
+
+    let res = sign_in(&app, &issuer, claims("alice", "alice@example.com")).await;
+    assert_eq!(res.status, Status::Ok);
+}
+
+#[rocket::async_test]
+async fn only_completes_logins_started_in_the_same_browser() {
+    let issuer = MockIssuer::start().await;
+    let app = spawn(&issuer).await;
+    let token = app.user("mallory@example.com").await;
+
+    // Mallory starts linking her account but sends the provider URL on
+    // instead of following it.
+    let location = login_location(
+        &app,
+        &format!("/auth/oidc/{}/link", NAME),
+        Some(token.as_str()),
+    )
+    .await;
+    let lure = issuer.authorize(&location, claims("victim", "victim@example.com"));
+
+    // The victim's browser holds the cookie of its own login, not hers.
+    login_location(&app, &format!("/auth/oidc/{}/login", NAME), None).await;
+    let res = callback(&app, lure).await;
+    assert_eq!(res.status, Status::Unauthorized);
+
+    let res = sign_in(&app, &issuer, claims("victim", "victim@example.com")).await;
+    assert_eq!(email_of(&app, &res).await, "victim@example.com");
+}
+
+#[rocket::async_test]
+async fn rejects_an_id_token_signed_with_a_shared_secret() {
+    let issuer = MockIssuer::start().await;
+    let app = spawn(&issuer).await;
+    let location = login_location(&app, &format!("/auth/oidc/{}/login", NAME), None).await;
+
+    let mut header = Header::new(Algorithm::HS256);
+    header.kid = Some(KID.to_string());
+
+    let res = callback(
+        &app,
+        issuer.authorize_with(
+            &location,
+            claims("alice", "alice@example.com"),
+            header,
+            &EncodingKey::from_secret(include_bytes!("fixtures/oidc-issuer.pub.pem")),
+        ),
+    )
+    .await;
+
+    assert_eq!(res.status, Status::Unauthorized);
+}
+
+#[rocket::async_test]
+async fn refuses_to_configure_shared_secret_algorithms() {
+    let issuer = MockIssuer::start().await;
+    let figment: Figment = issuer
+        .configure(common::figment())
+        .merge((format!("oidc.{}.algorithms", NAME), ["RS256", "HS256"]));
+
+    let err = AppConfig::load(&figment).err().unwrap();
+
+    assert!(err.contains("cannot include HS256"), "{}", err);
+}
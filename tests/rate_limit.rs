@@ -0,0 +1,81 @@
+mod common;
+
+use bookstore::fairings::rate_limit::{MemoryStore, RateLimitStore, RouteLimit};
+use common::{PASSWORD, TestApp};
+use rocket::{
+    http::{ContentType, Header, Status},
+    serde::json::json,
+};
+
+async fn spawn_with_limits(limits: &[&str]) -> TestApp {
+    TestApp::spawn_with(common::figment().merge(("rate_limits", limits))).await
+}
+
+#[rocket::async_test]
+async fn limits_sign_ins_per_client_address() {
+    let app = spawn_with_limits(&["POST /auth/sign-in=2/60:ip"]).await;
+    let mut statuses = Vec::new();
+
+    // A forged address per request must not earn a bucket per request.
+    for i in 0..3 {
+        let res = app
+            .client
+            .post("/auth/sign-in")
+            .remote("203.0.113.7:4000".parse().unwrap())
+            .header(Header::new("X-Real-IP", format!("198.51.100.{}", i)))
+            .header(ContentType::JSON)
+            .body(json!({ "email": "reader@example.com", "password": PASSWORD }).to_string())
+            .dispatch()
+            .await;
+
+        statuses.push(res.status());
+    }
+
+    assert_eq!(
+        statuses,
+        [
+            Status::Unauthorized,
+            Status::Unauthorized,
+            Status::TooManyRequests
+        ]
+    );
+}
+
+#[rocket::async_test]
+async fn sends_rate_limit_headers() {
+    let app = spawn_with_limits(&["/=5/60"]).await;
+
+    let res = app.client.get("/").dispatch().await;
+
+    assert_eq!(res.headers().get_one("RateLimit-Limit"), Some("5"));
+    assert_eq!(res.headers().get_one("RateLimit-Remaining"), Some("4"));
+}
+
+#[rocket::async_test]
+async fn does_not_count_preflights() {
+    let app = spawn_with_limits(&["/=1/60"]).await;
+
+    for _ in 0..3 {
+        app.client.options("/books").dispatch().await;
+    }
+
+    let res = app.client.get("/").dispatch().await;
+
+    assert_eq!(res.status(), Status::Ok);
+}
+
+#[rocket::async_test]
+async fn keeps_buckets_of_longer_windows_when_pruning() {
+    let store = MemoryStore::default();
+    let long = RouteLimit::parse("/=1/600").unwrap();
+    let short = RouteLimit::parse("/=1/0").unwrap();
+
+    assert!(store.take("long", &long).await.unwrap().allowed);
+
+    // Enough buckets for the store to prune idle ones.
+    for i in 0..10_000 {
+        store.take(&format!("short-{}", i), &short).await.unwrap();
+    }
+
+    assert!(!store.take("long", &long).await.unwrap().allowed);
+}
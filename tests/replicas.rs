@@ -0,0 +1,191 @@
+mod common;
+
+use std::time::Duration;
+
+use bookstore::{db::replicas::LAST_WRITE_COOKIE, migrator::Migrator};
+use common::TestApp;
+use rocket::{
+    figment::Figment,
+    http::{ContentType, Cookie, Header, Status},
+    serde::json::json,
+};
+use sea_orm::{Database, DatabaseConnection};
+use sea_orm_migration::MigratorTrait;
+
+/// The configuration of an application with a replica that never receives
+/// the primary's changes, so reads served by it find nothing.
+async fn with_replica(read_your_writes_ms: u64) -> (Figment, DatabaseConnection) {
+    let url = common::database_url();
+
+    // Keeps the in-memory replica alive for the test.
+    let replica = Database::connect(&url).await.unwrap();
+    Migrator::up(&replica, None).await.unwrap();
+
+    let figment = common::figment()
+        .merge(("db_replicas.urls", vec![url]))
+        .merge(("db_replicas.read_your_writes_ms", read_your_writes_ms));
+
+    (figment, replica)
+}
+
+async fn spawn_with_replica(read_your_writes_ms: u64) -> (TestApp, DatabaseConnection) {
+    let (figment, replica) = with_replica(read_your_writes_ms).await;
+
+    (TestApp::spawn_with(figment).await, replica)
+}
+
+#[rocket::async_test]
+async fn reads_from_the_replica() {
+    let (app, _replica) = spawn_with_replica(0).await;
+    let token = app.user("reader@example.com").await;
+    app.create_author(&token, "Italo", "Calvino").await;
+
+    let res = app.get("/authors", &token).await;
+
+    assert_eq!(res.body["total"], 0);
+}
+
+#[rocket::async_test]
+async fn reads_your_own_writes_from_the_primary_until_the_replicas_catch_up() {
+    let (app, _replica) = spawn_with_replica(300).await;
+    let token = app.user("writer@example.com").await;
+    app.create_author(&token, "Italo", "Calvino").await;
+
+    let res = app.get("/authors", &token).await;
+    assert_eq!(res.body["total"], 1);
+
+    rocket::tokio::time::sleep(Duration::from_millis(350)).await;
+
+    let res = app.get("/authors", &token).await;
+    assert_eq!(res.body["total"], 0);
+}
+
+#[rocket::async_test]
+async fn reads_your_own_writes_without_cookies() {
+    let (figment, _replica) = with_replica(60_000).await;
+    let app = TestApp::spawn_without_cookies(figment).await;
+    let writer = app.user("writer@example.com").await;
+    let reader = app.user("reader@example.com").await;
+    app.create_author(&writer, "Italo", "Calvino").await;
+
+    let res = app.get("/authors", &writer).await;
+    assert_eq!(res.body["total"], 1);
+
+    // Others still read from the replica.
+    let res = app.get("/authors", &reader).await;
+    assert_eq!(res.body["total"], 0);
+}
+
+#[rocket::async_test]
+async fn reads_your_own_writes_on_another_instance() {
+    let (figment, _replica) = with_replica(60_000).await;
+    let app = TestApp::spawn_with(figment.clone()).await;
+    let other = TestApp::spawn_with(figment).await;
+    let token = app.user("writer@example.com").await;
+
+    let res = app
+        .client
+        .post("/authors")
+        .header(Header::new("Authorization", format!("Bearer {}", token)))
+        .header(ContentType::JSON)
+        .body(json!({ "firstname": "Italo", "lastname": "Calvino", "bio": "" }).to_string())
+        .dispatch()
+        .await;
+    assert_eq!(res.status(), Status::Created);
+    let last_write = res
+        .cookies()
+        .get(LAST_WRITE_COOKIE)
+        .unwrap()
+        .value()
+        .to_string();
+
+    let res = other.get("/authors", &token).await;
+    assert_eq!(res.body["total"], 0);
+
+    let res = other
+        .client
+        .get("/authors")
+        .header(Header::new("Authorization", format!("Bearer {}", token)))
+        .cookie(Cookie::new(LAST_WRITE_COOKIE, last_write.to_owned()))
+        .dispatch()
+        .await;
+    let body = res.into_json::<rocket::serde::json::Value>().await.unwrap();
+    assert_eq!(body["total"], 1);
+
+    // The cookie only counts for the user who wrote.
+    let reader = other.user("reader@example.com").await;
+    let res = other
+        .client
+        .get("/authors")
+        .header(Header::new("Authorization", format!("Bearer {}", reader)))
+        .cookie(Cookie::new(LAST_WRITE_COOKIE, last_write))
+        .dispatch()
+        .await;
+    let body = res.into_json::<rocket::serde::json::Value>().await.unwrap();
+    assert_eq!(body["total"], 0);
+
+    let res = other
+        .client
+        .get("/authors")
+        .header(Header::new("Authorization", format!("Bearer {}", token)))
+        .cookie(Cookie::new(LAST_WRITE_COOKIE, "forged"))
+        .dispatch()
+        .await;
+    let body = res.into_json::<rocket::serde::json::Value>().await.unwrap();
+    assert_eq!(body["total"], 0);
+}
+
+#[rocket::async_test]
+async fn reads_from_the_primary_while_the_replica_is_down() {
+    let app = TestApp::spawn_with(
+        common::figment()
+            .merge((
+                "db_replicas.urls",
+                vec!["sqlite:/nonexistent/bookstore-replica.db"],
+            ))
+            .merge(("db_replicas.read_your_writes_ms", 0)),
+    )
+    .await;
+    let token = app.user("reader@example.com").await;
+    app.create_author(&token, "Italo", "Calvino").await;
+
+    let res = app.get("/authors", &token).await;
+    assert_eq!(res.body["total"], 1);
+
+    let res = app.get("/health/ready", &token).await;
+    assert_eq!(res.status, Status::Ok);
+    assert_eq!(res.body["status"], "degraded");
+    let replicas = res.body["checks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|check| check["name"] == "replicas")
+        .unwrap();
+    assert_eq!(replicas["healthy"], false);
+}
+
+#[rocket::async_test]
+async fn only_authenticated_writes_are_remembered() {
+    let (app, _replica) = spawn_with_replica(60_000).await;
+    let token = app.user("writer@example.com").await;
+
+    let res = app
+        .client
+        .post("/auth/sign-up")
+        .header(Header::new("Authorization", format!("Bearer {}", token)))
+        .header(ContentType::JSON)
+        .body(
+            json!({
+                "email": "reader@example.com",
+                "password": common::PASSWORD,
+                "firstname": "Test",
+                "lastname": "Reader",
+            })
+            .to_string(),
+        )
+        .dispatch()
+        .await;
+
+    assert_eq!(res.status(), Status::Created);
+    assert!(res.cookies().get(LAST_WRITE_COOKIE).is_none());
+}
@@ -0,0 +1,207 @@
+mod common;
+
+use bookstore::{
+    entities::{author, prelude::*},
+    seed::{
+        Counts, Fixtures,
+        fake::{self, FakeOptions},
+    },
+};
+use common::{PASSWORD, TestApp};
+use rocket::http::Status;
+use sea_orm::{EntityTrait, PaginatorTrait, QueryOrder};
+
+const LIBRARY: &str = r#"
+users:
+  reader:
+    email: reader@example.com
+    password: correct horse battery staple
+    firstname: Test
+    lastname: Reader
+authors:
+  calvino:
+    user: reader
+    firstname: Italo
+    lastname: Calvino
+books:
+  invisible_cities:
+    author: calvino
+    title: Invisible Cities
+    year: 1972
+  palomar:
+    author: calvino
+    title: Mr. Palomar
+    year: "1983"
+"#;
+
+fn counts(created: usize, updated: usize, unchanged: usize) -> Counts {
+    Counts {
+        created,
+        updated,
+        unchanged,
+    }
+}
+
+#[rocket::async_test]
+async fn links_records_by_key() {
+    let app = TestApp::spawn().await;
+
+    let report = app.seed(LIBRARY).await;
+
+    assert_eq!(report.users, counts(1, 0, 0));
+    assert_eq!(report.authors, counts(1, 0, 0));
+    assert_eq!(report.books, counts(2, 0, 0));
+
+    let res = app.sign_in("reader@example.com", PASSWORD).await;
+    let token = res.body["token"].as_str().unwrap();
+    let authors = app.get("/authors", token).await;
+    let id = &authors.body["authors"][0]["id"];
+
+    let res = app.get(&format!("/authors/{}/books", id), token).await;
+
+    assert_eq!(res.status, Status::Ok);
+    assert_eq!(res.body["total"], 2);
+}
+
+#[rocket::async_test]
+async fn loading_again_changes_nothing() {
+    let app = TestApp::spawn().await;
+    app.seed(LIBRARY).await;
+
+    let report = app.seed(LIBRARY).await;
+
+    assert_eq!(report.users, counts(0, 0, 1));
+    assert_eq!(report.authors, counts(0, 0, 1));
+    assert_eq!(report.books, counts(0, 0, 2));
+    assert_eq!(Book::find().count(&app.db).await.unwrap(), 2);
+}
+
+#[rocket::async_test]
+async fn updates_changed_records() {
+    let app = TestApp::spawn().await;
+    app.seed(LIBRARY).await;
+
+    let report = app.seed(&LIBRARY.replace("year: 1972", "year: 1973")).await;
+
+    assert_eq!(report.books, counts(0, 1, 1));
+}
+
+#[test]
+fn rejects_unknown_keys() {
+    let err = Fixtures::from_yaml(
+        r#"
+authors:
+  calvino: { user: nobody, firstname: Italo, lastname: Calvino }
+books:
+  palomar: { author: somebody, title: Mr. Palomar }
+"#,
+    )
+    .err()
+    .unwrap();
+
+    assert!(err.contains("authors.calvino: no user `nobody`"), "{}", err);
+    assert!(
+        err.contains("books.palomar: no author `somebody`"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn reads_json() {
+    let fixtures = Fixtures::from_json(
+        r#"{ "users": { "reader": { "email": "reader@example.com", "firstname": "Test", "lastname": "Reader" } } }"#,
+    )
+    .unwrap();
+
+    assert_eq!(fixtures.users["reader"].email, "reader@example.com");
+}
+
+#[test]
+fn parses_the_demo_data() {
+    let fixtures = Fixtures::demo().unwrap();
+
+    assert_eq!(fixtures.books.len(), 4);
+}
+
+#[rocket::async_test]
+async fn generates_fake_data_and_replaces_it() {
+    let app = TestApp::spawn().await;
+    let mut options = FakeOptions {
+        email: "fake@example.com".to_string(),
+        authors: 40,
+        books_per_author: 3,
+        seed: 7,
+    };
+
+    let report = fake::generate(&app.db, &options).await.unwrap();
+
+    assert_eq!((report.authors, report.books), (40, 120));
+    assert!(report.generated_password.is_some());
+
+    options.authors = 10;
+    let report = fake::generate(&app.db, &options).await.unwrap();
+
+    assert_eq!((report.authors, report.books), (10, 30));
+    assert!(report.generated_password.is_none());
+    assert_eq!(Author::find().count(&app.db).await.unwrap(), 10);
+    assert_eq!(Book::find().count(&app.db).await.unwrap(), 30);
+}
+
+#[rocket::async_test]
+async fn refuses_to_generate_for_an_existing_account() {
+    let app = TestApp::spawn().await;
+    let token = app.user("reader@example.com").await;
+    let author = app.create_author(&token, "Italo", "Calvino").await;
+    app.create_book(&token, author["id"].as_i64().unwrap(), "Invisible Cities")
+        .await;
+
+    let options = FakeOptions {
+        email: "Reader@example.com".to_string(),
+        authors: 10,
+        books_per_author: 3,
+        seed: 7,
+    };
+
+    let err = fake::generate(&app.db, &options).await.unwrap_err();
+
+    assert!(err.contains("was not created by the generator"), "{}", err);
+    assert_eq!(app.get("/auth/me", &token).await.body["firstname"], "Test");
+    assert_eq!(Author::find().count(&app.db).await.unwrap(), 1);
+    assert_eq!(Book::find().count(&app.db).await.unwrap(), 1);
+}
+
+#[rocket::async_test]
+async fn keeps_books_other_users_filed_under_generated_authors() {
+    let app = TestApp::spawn().await;
+    let options = FakeOptions {
+        email: "fake@example.com".to_string(),
+        authors: 10,
+        books_per_author: 3,
+        seed: 7,
+    };
+    fake::generate(&app.db, &options).await.unwrap();
+
+    let generated = Author::find()
+        .order_by_asc(author::Column::Id)
+        .one(&app.db)
+        .await
+        .unwrap()
+        .unwrap();
+    let token = app.user("reader@example.com").await;
+    app.create_book(&token, generated.id.into(), "Invisible Cities")
+        .await;
+
+    let report = fake::generate(&app.db, &options).await.unwrap();
+
+    assert_eq!((report.authors, report.books), (10, 30));
+    assert!(
+        Author::find_by_id(generated.id)
+            .one(&app.db)
+            .await
+            .unwrap()
+            .is_some()
+    );
+    assert_eq!(Author::find().count(&app.db).await.unwrap(), 11);
+    assert_eq!(Book::find().count(&app.db).await.unwrap(), 31);
+}
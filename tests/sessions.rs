@@ -0,0 +1,59 @@
+mod common;
+
+use common::{PASSWORD, TestApp};
+use rocket::http::{Method, Status};
+
+#[rocket::async_test]
+async fn signing_out_ends_the_session() {
+    let app = TestApp::spawn().await;
+    let token = app.user("reader@example.com").await;
+
+    let res = app
+        .request(Method::Post, "/auth/sign-out", Some(&token), None)
+        .await;
+    assert_eq!(res.status, Status::Ok);
+
+    let res = app.get("/auth/me", &token).await;
+    assert_eq!(res.status, Status::Unauthorized);
+}
+
+#[rocket::async_test]
+async fn lists_and_ends_other_sessions() {
+    let app = TestApp::spawn().await;
+    let laptop = app.user("reader@example.com").await;
+    let phone = app.sign_in("reader@example.com", PASSWORD).await.body["token"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let res = app.get("/auth/sessions", &laptop).await;
+    assert_eq!(res.body["total"], 2);
+    let sessions = res.body["sessions"].as_array().unwrap();
+    assert_eq!(sessions.iter().filter(|s| s["current"] == true).count(), 1);
+    let other = sessions.iter().find(|s| s["current"] == false).unwrap();
+
+    let res = app
+        .delete(&format!("/auth/sessions/{}", other["id"]), &laptop)
+        .await;
+    assert_eq!(res.status, Status::Ok);
+
+    assert_eq!(
+        app.get("/auth/me", &phone).await.status,
+        Status::Unauthorized
+    );
+    assert_eq!(app.get("/auth/me", &laptop).await.status, Status::Ok);
+}
+
+#[rocket::async_test]
+async fn cannot_end_the_sessions_of_others() {
+    let app = TestApp::spawn().await;
+    let reader = app.user("reader@example.com").await;
+    let other = app.user("other@example.com").await;
+
+    let res = app.get("/auth/sessions", &other).await;
+    let id = &res.body["sessions"][0]["id"];
+
+    let res = app.delete(&format!("/auth/sessions/{}", id), &reader).await;
+    assert_eq!(res.status, Status::NotFound);
+    assert_eq!(app.get("/auth/me", &other).await.status, Status::Ok);
+}
@@ -0,0 +1,61 @@
+mod common;
+
+use bookstore::{
+    controllers::{ErrorResponse, Response, SuccessResponse},
+    db::transaction::Transaction,
+    entities::{audit_event, prelude::*},
+    fairings::transaction::RequestTransactions,
+};
+use common::TestApp;
+use rocket::{Config, http::Status, local::asynchronous::Client, post, routes};
+use sea_orm::{ActiveModelTrait, ActiveValue::Set, EntityTrait, PaginatorTrait};
+
+/// Records an event, then answers with the status asked for.
+#[post("/<user_id>/<status>")]
+async fn record(txn: Transaction<'_>, user_id: i32, status: u16) -> Response<String> {
+    let txn = txn.get().await?;
+
+    audit_event::ActiveModel {
+        user_id: Set(user_id),
+        action: Set("test".to_string()),
+        ..Default::default()
+    }
+    .insert(&*txn)
+    .await?;
+
+    match status {
+        200 => Ok(SuccessResponse((Status::Ok, "Recorded.".to_string()))),
+        _ => Err(ErrorResponse::new(Status::new(status), "Failed.")),
+    }
+}
+
+async fn recorded_events(status: u16) -> u64 {
+    let app = TestApp::spawn().await;
+    let token = app.user("reader@example.com").await;
+    let user_id = app.get("/auth/me", &token).await.body["id"].clone();
+    let before = AuditEvent::find().count(&app.db).await.unwrap();
+
+    let rocket = rocket::custom(Config::debug_default())
+        .manage(app.db.clone())
+        .attach(RequestTransactions)
+        .mount("/", routes![record]);
+    let client = Client::tracked(rocket).await.unwrap();
+
+    let res = client
+        .post(format!("/{}/{}", user_id, status))
+        .dispatch()
+        .await;
+    assert_eq!(res.status().code, status);
+
+    AuditEvent::find().count(&app.db).await.unwrap() - before
+}
+
+#[rocket::async_test]
+async fn commits_when_the_request_succeeds() {
+    assert_eq!(recorded_events(200).await, 1);
+}
+
+#[rocket::async_test]
+async fn rolls_back_when_the_request_fails() {
+    assert_eq!(recorded_events(422).await, 0);
+}